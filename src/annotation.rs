@@ -0,0 +1,230 @@
+#[cfg(feature = "render")]
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::escape::{escape_drawtext, escape_filter_option};
+use crate::time::Time;
+use crate::VidGridError;
+
+/// A reviewer's note drawn on a cell for a stretch of the grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// The cell the note is drawn on, counted from 1
+    pub cell: usize,
+    /// Seconds into the grid at which the note appears
+    pub start: f64,
+    /// Seconds into the grid at which the note disappears. Shown to the end when not given
+    pub end: Option<f64>,
+    /// Color of the note, e.g. `yellow` or `#ff8800`
+    pub color: String,
+    pub mark: Mark,
+}
+
+/// What an annotation draws. Positions and sizes are fractions of the cell's video, so notes stay
+/// on what they point at in grids of any size
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mark {
+    /// Text with its top-left corner at `x`, `y`, `size` cell heights high
+    Text {
+        text: String,
+        x: f64,
+        y: f64,
+        size: f64,
+    },
+    /// The outline of a rectangle
+    Box {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    /// An arrow pointing in `direction` with its tip at `x`, `y`
+    Arrow {
+        x: f64,
+        y: f64,
+        direction: Direction,
+    },
+}
+
+/// Where an arrow points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// The arrow glyph, and the `drawtext` position of its top-left corner putting its tip at
+    /// `x`, `y`
+    fn glyph(self, x: &str, y: &str) -> (&'static str, String, String) {
+        match self {
+            Direction::Left => ("←", x.to_string(), format!("{}-text_h/2", y)),
+            Direction::Right => ("→", format!("{}-text_w", x), format!("{}-text_h/2", y)),
+            Direction::Up => ("↑", format!("{}-text_w/2", x), y.to_string()),
+            Direction::Down => ("↓", format!("{}-text_w/2", x), format!("{}-text_h", y)),
+        }
+    }
+}
+
+/// Font size of text annotations when the file does not say, in cell heights
+const DEFAULT_TEXT_SIZE: f64 = 1.0 / 16.0;
+
+/// An annotation as written in an annotations file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationEntry {
+    #[serde(rename = "type")]
+    kind: Kind,
+    cell: usize,
+    start: Time,
+    end: Option<Time>,
+    #[serde(default = "default_color")]
+    color: String,
+    x: f64,
+    y: f64,
+    text: Option<String>,
+    size: Option<f64>,
+    width: Option<f64>,
+    height: Option<f64>,
+    direction: Option<Direction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Text,
+    Box,
+    Arrow,
+}
+
+/// The color of an annotation or timeline event that gives none
+pub(crate) fn default_color() -> String {
+    "yellow".to_string()
+}
+
+/// Reads annotations from a JSON array such as
+///
+/// ```json
+/// [
+///   { "type": "text", "cell": 1, "start": "0:05", "end": "0:09", "x": 0.05, "y": 0.8,
+///     "text": "Dropped frame here" },
+///   { "type": "box", "cell": 2, "start": 5, "end": 9, "x": 0.4, "y": 0.3, "width": 0.2,
+///     "height": 0.25, "color": "red" },
+///   { "type": "arrow", "cell": 2, "start": 5, "end": 9, "x": 0.4, "y": 0.4,
+///     "direction": "right" }
+/// ]
+/// ```
+///
+/// Times are in seconds or `[hh:]mm:ss` into the grid, and positions fractions of the cell.
+#[cfg(feature = "render")]
+pub fn read_annotations(path: &Path) -> Result<Vec<Annotation>, VidGridError> {
+    parse_annotations(&fs::read_to_string(path)?, path)
+}
+
+/// Parses annotations written as `read_annotations` reads them, e.g. from a file read elsewhere.
+/// Errors name the file as `path`.
+pub fn parse_annotations(json: &str, path: &Path) -> Result<Vec<Annotation>, VidGridError> {
+    let invalid = |number: usize, message: String| {
+        VidGridError::Parse(format!(
+            "Invalid annotation {} in {}: {}",
+            number,
+            path.display(),
+            message
+        ))
+    };
+    let entries: Vec<AnnotationEntry> = serde_json::from_str(json).map_err(|e| {
+        VidGridError::Parse(format!(
+            "Invalid annotations file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let mut annotations = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let invalid = |message: &str| invalid(index + 1, message.to_string());
+        let start = entry.start.seconds().map_err(|e| invalid(&e))?;
+        let end = entry
+            .end
+            .map(|end| end.seconds())
+            .transpose()
+            .map_err(|e| invalid(&e))?;
+        if end.is_some_and(|end| end <= start) {
+            return Err(invalid("it ends before it starts"));
+        }
+        if entry.cell == 0 {
+            return Err(invalid("cells are counted from 1"));
+        }
+
+        let (x, y) = (entry.x, entry.y);
+        let mark = match entry.kind {
+            Kind::Text => Mark::Text {
+                text: entry.text.ok_or_else(|| invalid("a text needs `text`"))?,
+                x,
+                y,
+                size: entry.size.unwrap_or(DEFAULT_TEXT_SIZE),
+            },
+            Kind::Box => Mark::Box {
+                x,
+                y,
+                width: entry.width.ok_or_else(|| invalid("a box needs `width`"))?,
+                height: entry
+                    .height
+                    .ok_or_else(|| invalid("a box needs `height`"))?,
+            },
+            Kind::Arrow => Mark::Arrow {
+                x,
+                y,
+                direction: entry
+                    .direction
+                    .ok_or_else(|| invalid("an arrow needs `direction`"))?,
+            },
+        };
+        annotations.push(Annotation {
+            cell: entry.cell,
+            start,
+            end,
+            color: entry.color,
+            mark,
+        });
+    }
+    Ok(annotations)
+}
+
+impl Annotation {
+    /// The `drawtext` or `drawbox` filter drawing the note on its cell while it is shown.
+    /// `font_option` selects the cell's font.
+    pub fn filter(&self, font_option: &str) -> String {
+        let enable = match self.end {
+            Some(end) => format!("between(t,{},{})", self.start, end),
+            None => format!("gte(t,{})", self.start),
+        };
+        let enable = escape_filter_option(&enable);
+        let color = escape_filter_option(&self.color);
+        match &self.mark {
+            Mark::Text { text, x, y, size } => format!(
+                "drawtext=text={text}{font_option}:enable={enable}:x=w*{x}:y=h*{y}:fontsize=h*{size}:fontcolor={color}:box=1:boxcolor=black@0.5:boxborderw=6",
+                text = escape_drawtext(text),
+            ),
+            Mark::Box {
+                x,
+                y,
+                width,
+                height,
+            } => format!(
+                "drawbox=enable={enable}:x=iw*{x}:y=ih*{y}:w=iw*{width}:h=ih*{height}:color={color}:t=ih/120"
+            ),
+            Mark::Arrow { x, y, direction } => {
+                let (glyph, x, y) = direction.glyph(&format!("w*{}", x), &format!("h*{}", y));
+                format!(
+                    "drawtext=text={glyph}{font_option}:enable={enable}:x={x}:y={y}:fontsize=h/8:fontcolor={color}:borderw=2:bordercolor=black"
+                )
+            }
+        }
+    }
+}
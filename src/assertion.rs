@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// A condition that fails a run when it holds for a value of its report, e.g.
+/// `metrics:vmaf<85` (`--fail-on`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    /// The keys leading to the value in the report, e.g. `["metrics", "vmaf"]`. Array elements
+    /// are picked by index
+    pub path: Vec<String>,
+    pub comparison: Comparison,
+    pub threshold: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// The operators in the order they are looked for, longer ones first so `<=` isn't read as `<`
+    const ALL: [(&'static str, Comparison); 6] = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn symbol(self) -> &'static str {
+        Comparison::ALL
+            .iter()
+            .find(|(_, comparison)| *comparison == self)
+            .map(|(symbol, _)| *symbol)
+            .expect("every comparison has a symbol")
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+        }
+    }
+}
+
+impl FromStr for Assertion {
+    type Err = String;
+
+    /// Parses `<key>[:<key>...]<operator><number>`, with keys separated by `:` or `.`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, symbol, comparison) = Comparison::ALL
+            .iter()
+            .filter_map(|&(symbol, comparison)| Some((s.find(symbol)?, symbol, comparison)))
+            .min_by_key(|&(at, _, _)| at)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid assertion '{}', expected e.g. metrics:vmaf<85 with one of < <= > >= == !=",
+                    s
+                )
+            })?;
+        let (key, threshold) = (&s[..at], &s[at + symbol.len()..]);
+        let path: Vec<String> = key
+            .split([':', '.'])
+            .map(|part| part.trim().to_string())
+            .collect();
+        if path.iter().any(String::is_empty) {
+            return Err(format!("Invalid key '{}' in assertion '{}'", key, s));
+        }
+        let threshold = threshold
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid number '{}' in assertion '{}'", threshold, s))?;
+        Ok(Assertion {
+            path,
+            comparison,
+            threshold,
+        })
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.path.join(":"),
+            self.comparison.symbol(),
+            self.threshold
+        )
+    }
+}
+
+/// How an assertion came out, recorded in the report it was checked against
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub assertion: String,
+    /// The value the assertion was checked against
+    pub value: f64,
+    /// Whether the assertion held, failing the run
+    pub failed: bool,
+}
+
+impl Assertion {
+    /// Checks the assertion against a report serialized to JSON. Fails if the report has no
+    /// number at the assertion's path, e.g. for a metric that wasn't measured
+    pub fn check(&self, report: &Value) -> Result<Outcome, Box<dyn Error>> {
+        let mut value = report;
+        for key in &self.path {
+            value = match value {
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+                _ => value.get(key),
+            }
+            .ok_or_else(|| format!("Nothing was measured as {} for --fail-on {}", key, self))?;
+        }
+        let value = value
+            .as_f64()
+            .ok_or_else(|| format!("--fail-on {} does not name a number", self))?;
+        Ok(Outcome {
+            assertion: self.to_string(),
+            value,
+            failed: self.comparison.holds(value, self.threshold),
+        })
+    }
+}
+
+/// Checks every assertion against `report`
+pub fn check_all<T: Serialize>(
+    assertions: &[Assertion],
+    report: &T,
+) -> Result<Vec<Outcome>, Box<dyn Error>> {
+    let report = serde_json::to_value(report)?;
+    assertions
+        .iter()
+        .map(|assertion| assertion.check(&report))
+        .collect()
+}
+
+/// Fails with the assertions that held, if any did
+pub fn verdict(outcomes: &[Outcome]) -> Result<(), Box<dyn Error>> {
+    let failed: Vec<String> = outcomes
+        .iter()
+        .filter(|outcome| outcome.failed)
+        .map(|outcome| format!("{} (was {})", outcome.assertion, outcome.value))
+        .collect();
+    match failed.as_slice() {
+        [] => Ok(()),
+        _ => Err(format!("Failed on {}", failed.join(", ")).into()),
+    }
+}
@@ -0,0 +1,44 @@
+use std::error::Error;
+
+use vidgrid::cell::Cell;
+use vidgrid::create_video_grid;
+use vidgrid::input::InputSource;
+use vidgrid::settings::GridSettings;
+
+use crate::options::AudioGridArgs;
+
+/// Renders each audio file as a labeled visualizer cell of a grid. Fewer than four files leave
+/// the rest of a 2x2 grid black.
+pub fn run(args: &AudioGridArgs) -> Result<(), Box<dyn Error>> {
+    let mut cells: Vec<Cell> = args
+        .inputs
+        .iter()
+        .map(|path| Cell {
+            label: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            ..Cell::new(InputSource::Audio {
+                path: path.clone(),
+                visualizer: args.visualizer,
+            })
+        })
+        .collect();
+    cells.resize_with(cells.len().max(4), || Cell::new(InputSource::Empty));
+
+    let settings = GridSettings {
+        width: args.width,
+        height: args.height,
+        duration: args.duration,
+        max_framerate: args.framerate,
+        output_path: args.output_path.clone(),
+        ..GridSettings::default()
+    };
+
+    create_video_grid(&cells, &settings)?;
+
+    if args.open {
+        open::that(&args.output_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use vidgrid::probe::MediaInfo;
+use vidgrid::process::{spawn_group, wait_group_until};
+use vidgrid::VidGridError;
+
+use crate::limits::ResourceLimits;
+use crate::manifest::Manifest;
+use crate::options::BatchArgs;
+
+/// How long to wait before probing an input that failed to probe again
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How a job of a batch ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    Succeeded,
+    /// An input could not be probed, or was quarantined
+    FailedProbe,
+    /// Rendering the grid failed
+    FailedEncode,
+    /// The output was already newer than every input, so the job wasn't run
+    SkippedCached,
+    /// The job ran out of time, or ffmpeg was stopped for running too long on one of its inputs
+    TimedOut,
+}
+
+impl Status {
+    const ALL: [Status; 5] = [
+        Status::Succeeded,
+        Status::FailedProbe,
+        Status::FailedEncode,
+        Status::SkippedCached,
+        Status::TimedOut,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Status::Succeeded => "succeeded",
+            Status::FailedProbe => "failed-probe",
+            Status::FailedEncode => "failed-encode",
+            Status::SkippedCached => "skipped-cached",
+            Status::TimedOut => "timed-out",
+        }
+    }
+
+    fn failed(self) -> bool {
+        matches!(
+            self,
+            Status::FailedProbe | Status::FailedEncode | Status::TimedOut
+        )
+    }
+
+    /// Tells what went wrong from the exit status of a job's vidgrid process
+    fn of_failure(status: ExitStatus) -> Self {
+        match status.code().and_then(|code| u8::try_from(code).ok()) {
+            Some(VidGridError::EXIT_PROBE_FAILED) => Status::FailedProbe,
+            Some(VidGridError::EXIT_TIMED_OUT) => Status::TimedOut,
+            _ => Status::FailedEncode,
+        }
+    }
+}
+
+/// How a job of a batch ended, as listed in the report
+#[derive(Debug, Serialize)]
+struct Outcome {
+    job: usize,
+    output: String,
+    log: PathBuf,
+    status: Status,
+    /// Why the job failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    /// Wall-clock seconds the job took
+    seconds: f64,
+}
+
+/// How many jobs ended with a status, and the wall-clock seconds they took together
+#[derive(Debug, Serialize)]
+struct Total {
+    status: Status,
+    jobs: usize,
+    seconds: f64,
+}
+
+/// The end-of-run report of a batch, written as JSON to `report.json` in the log directory
+#[derive(Debug, Serialize)]
+struct Report {
+    /// Wall-clock seconds of the whole run, which jobs running side by side take less than the
+    /// sum of
+    seconds: f64,
+    totals: Vec<Total>,
+    jobs: Vec<Outcome>,
+}
+
+/// Renders every grid of a batch file, a JSON array of manifests, with a pool of workers. Each
+/// job runs as its own vidgrid process logging to its own file, and a failed job doesn't stop
+/// the others. The outcome of every job is reported at the end, as a table and as JSON.
+pub fn run(args: &BatchArgs) -> Result<(), Box<dyn Error>> {
+    let json = fs::read_to_string(&args.list)
+        .map_err(|e| format!("Cannot read {}: {}", args.list.display(), e))?;
+    let manifests: Vec<Manifest> = serde_json::from_str(&json)
+        .map_err(|e| format!("Invalid batch file {}: {}", args.list.display(), e))?;
+    let dir = args.list.parent().unwrap_or(Path::new(""));
+
+    // Every job is checked before any is started, so a typo doesn't surface hours into a run
+    let mut jobs = Vec::new();
+    for (index, manifest) in manifests.iter().enumerate() {
+        let mut argv = manifest
+            .to_args(dir)
+            .map_err(|e| format!("Job {}: {}", index + 1, e))?;
+        argv.extend(args.grid_args.iter().cloned());
+        if !argv.iter().any(|arg| arg == "--input-timeout") {
+            argv.extend([
+                "--input-timeout".to_string(),
+                args.input_timeout.to_string(),
+            ]);
+        }
+        jobs.push(argv);
+    }
+    fs::create_dir_all(&args.log_dir)?;
+
+    // Inputs ffprobe keeps failing on, e.g. corrupted or truncated files, are moved aside rather
+    // than stalling this run and every later one. Missing files are left to fail their jobs.
+    let timeout = Duration::from_secs_f64(args.input_timeout);
+    let mut quarantined: HashMap<PathBuf, String> = HashMap::new();
+    let mut blocked = vec![None; jobs.len()];
+    for (index, manifest) in manifests.iter().enumerate() {
+        for input in manifest.input_files(dir) {
+            if !quarantined.contains_key(&input) && input.is_file() {
+                let Err(reason) = probe(&input, timeout, args.probe_attempts) else {
+                    continue;
+                };
+                let moved = quarantine(&input, &reason, &args.quarantine_dir)?;
+                println!(
+                    "Quarantined {} to {}: {}",
+                    input.display(),
+                    moved.display(),
+                    reason
+                );
+                quarantined.insert(input.clone(), reason);
+            }
+            if let Some(reason) = quarantined.get(&input) {
+                blocked[index].get_or_insert(format!(
+                    "{} was quarantined, as it {}",
+                    input.display(),
+                    reason
+                ));
+            }
+        }
+    }
+
+    let started = Instant::now();
+    let executable = std::env::current_exe()?;
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Outcome>>> = Mutex::new(jobs.iter().map(|_| None).collect());
+    // In a container, workers beyond its CPUs would only fight over them, and each job's ffmpeg
+    // gets its share of the CPUs rather than sizing itself to all of them
+    let container = ResourceLimits::detect();
+    let mut workers = usize::from(args.jobs).min(jobs.len());
+    if let Some(threads) = container.threads() {
+        workers = workers.min(threads);
+        let threads_per_job = (threads / workers.max(1)).max(1).to_string();
+        for argv in jobs.iter_mut() {
+            if !argv.iter().any(|arg| arg == "--threads") {
+                argv.extend(["--threads".to_string(), threads_per_job.clone()]);
+            }
+        }
+    }
+    println!("Rendering {} grids with {} workers", jobs.len(), workers);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(argv) = jobs.get(index) else {
+                    break;
+                };
+                let outcome = run_job(
+                    &executable,
+                    argv,
+                    Job {
+                        number: index + 1,
+                        blocked: blocked[index].as_deref(),
+                        inputs: args
+                            .skip_existing
+                            .then(|| manifests[index].input_files(dir)),
+                        timeout: args.job_timeout.map(Duration::from_secs_f64),
+                    },
+                    &args.log_dir,
+                );
+                match (&outcome.status, &outcome.reason) {
+                    (Status::SkippedCached, _) => println!(
+                        "[{}/{}] {} is up to date",
+                        index + 1,
+                        jobs.len(),
+                        outcome.output
+                    ),
+                    (_, Some(reason)) => println!(
+                        "[{}/{}] {} {}: {}",
+                        index + 1,
+                        jobs.len(),
+                        outcome.output,
+                        outcome.status.name(),
+                        reason
+                    ),
+                    (_, None) => {
+                        println!("[{}/{}] {} done", index + 1, jobs.len(), outcome.output)
+                    }
+                }
+                outcomes.lock().expect("a worker panicked")[index] = Some(outcome);
+            });
+        }
+    });
+
+    let outcomes: Vec<Outcome> = outcomes
+        .into_inner()
+        .expect("a worker panicked")
+        .into_iter()
+        .flatten()
+        .collect();
+    let report = Report {
+        seconds: started.elapsed().as_secs_f64(),
+        totals: Status::ALL
+            .into_iter()
+            .map(|status| {
+                let jobs = outcomes.iter().filter(|job| job.status == status);
+                Total {
+                    status,
+                    jobs: jobs.clone().count(),
+                    seconds: jobs.fold(0.0, |seconds, job| seconds + job.seconds),
+                }
+            })
+            .collect(),
+        jobs: outcomes,
+    };
+    print_report(&report);
+    let path = args.log_dir.join("report.json");
+    fs::write(&path, serde_json::to_string_pretty(&report)?)
+        .map_err(|e| format!("Cannot write the report {}: {}", path.display(), e))?;
+    println!("Report written to {}", path.display());
+
+    match report.jobs.iter().filter(|job| job.status.failed()).count() {
+        0 => Ok(()),
+        1 => Err("1 job failed".into()),
+        count => Err(format!("{} jobs failed", count).into()),
+    }
+}
+
+/// Prints the outcome of every job, then how many jobs ended each way and how long they took
+fn print_report(report: &Report) {
+    println!("\n{:>4}  {:<14}  {:>9}  Output", "Job", "Status", "Time");
+    for job in &report.jobs {
+        println!(
+            "{:>4}  {:<14}  {:>9}  {}",
+            job.job,
+            job.status.name(),
+            format_seconds(job.seconds),
+            job.output
+        );
+        if let Some(reason) = &job.reason {
+            println!("{:>31}{} (see {})", "", reason, job.log.display());
+        }
+    }
+
+    println!("\n{:<14}  {:>4}  {:>9}", "Status", "Jobs", "Time");
+    for total in &report.totals {
+        println!(
+            "{:<14}  {:>4}  {:>9}",
+            total.status.name(),
+            total.jobs,
+            format_seconds(total.seconds)
+        );
+    }
+    println!(
+        "{:<14}  {:>4}  {:>9}",
+        "wall clock",
+        report.jobs.len(),
+        format_seconds(report.seconds)
+    );
+}
+
+/// Seconds as minutes and seconds, e.g. `12:05.3`
+fn format_seconds(seconds: f64) -> String {
+    format!("{}:{:04.1}", (seconds / 60.0) as u64, seconds % 60.0)
+}
+
+/// What a worker needs to know about a job besides its arguments
+struct Job<'a> {
+    /// Counted from 1
+    number: usize,
+    /// Why the job can't run, when an input was quarantined
+    blocked: Option<&'a str>,
+    /// The input files of the job, when it is skipped if its output is newer than all of them
+    inputs: Option<Vec<PathBuf>>,
+    /// How long the job may run before it is stopped
+    timeout: Option<Duration>,
+}
+
+/// Runs a job as a vidgrid process, logging its output to `job_<number>.log` in `log_dir`. Jobs
+/// `blocked` by a quarantined input fail without being run, logging the reason.
+fn run_job(executable: &Path, argv: &[String], job: Job, log_dir: &Path) -> Outcome {
+    let started = Instant::now();
+    // The output path is the last one given, as later options override earlier ones
+    let output = argv
+        .windows(2)
+        .rev()
+        .find(|pair| pair[0] == "--output-path" || pair[0] == "-o")
+        .map(|pair| pair[1].clone())
+        .unwrap_or_else(|| "output.mp4".to_string());
+    let log = log_dir.join(format!("job_{:03}.log", job.number));
+
+    let result = (|| {
+        let log_error = |e: std::io::Error| {
+            (
+                Status::FailedEncode,
+                format!("Cannot create the log: {}", e),
+            )
+        };
+        if let Some(reason) = job.blocked {
+            fs::write(&log, format!("Error: {}\n", reason)).map_err(log_error)?;
+            return Err((Status::FailedProbe, reason.to_string()));
+        }
+        if let Some(inputs) = &job.inputs {
+            if up_to_date(Path::new(&output), inputs) {
+                return Ok(Status::SkippedCached);
+            }
+        }
+        let file = File::create(&log).map_err(log_error)?;
+        let stderr = file.try_clone().map_err(log_error)?;
+        // The job runs in a process group of its own, so a timeout stops its ffmpeg too
+        let mut child = spawn_group(
+            Command::new(executable)
+                .args(argv)
+                .stdin(Stdio::null())
+                .stdout(file)
+                .stderr(stderr),
+        )
+        .map_err(|e| (Status::FailedEncode, format!("Cannot start vidgrid: {}", e)))?;
+        let status = wait_group_until(&mut child, started, job.timeout)
+            .map_err(|e| (Status::FailedEncode, format!("Cannot run vidgrid: {}", e)))?;
+        let Some(status) = status else {
+            return Err((
+                Status::TimedOut,
+                format!(
+                    "stopped after {} seconds",
+                    job.timeout.unwrap_or_default().as_secs_f64()
+                ),
+            ));
+        };
+        if status.success() {
+            return Ok(Status::Succeeded);
+        }
+
+        // vidgrid explains a failure on the last line it prints
+        let text = fs::read_to_string(&log).unwrap_or_default();
+        let reason = text
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map_or_else(
+                || status.to_string(),
+                |line| line.trim().trim_start_matches("Error: ").to_string(),
+            );
+        Err((Status::of_failure(status), reason))
+    })();
+
+    let (status, reason) = match result {
+        Ok(status) => (status, None),
+        Err((status, reason)) => (status, Some(reason)),
+    };
+    Outcome {
+        job: job.number,
+        output,
+        log,
+        status,
+        reason,
+        seconds: started.elapsed().as_secs_f64(),
+    }
+}
+
+/// Whether `output` was written after every one of `inputs` was last changed. Jobs without
+/// input files, e.g. of streams, are never up to date
+fn up_to_date(output: &Path, inputs: &[PathBuf]) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    let Ok(written) = modified(output) else {
+        return false;
+    };
+    !inputs.is_empty()
+        && inputs
+            .iter()
+            .all(|input| modified(input).is_ok_and(|changed| changed <= written))
+}
+
+/// Probes `input` up to `attempts` times, each for at most `timeout`, returning why it failed if
+/// it never succeeded
+fn probe(input: &Path, timeout: Duration, attempts: u16) -> Result<(), String> {
+    let mut error = String::new();
+    for attempt in 1..=attempts {
+        match MediaInfo::probe_within(input, Some(timeout)) {
+            Ok(_) => return Ok(()),
+            Err(e) => error = e.to_string(),
+        }
+        if attempt < attempts {
+            thread::sleep(RETRY_DELAY);
+        }
+    }
+    Err(match attempts {
+        1 => format!("failed to probe: {}", error),
+        _ => format!("failed to probe {} times: {}", attempts, error),
+    })
+}
+
+/// Moves `input` into `dir` next to a `<name>.reason.txt` holding `reason`, and returns where it
+/// was moved. Inputs of the same name are numbered rather than overwritten.
+fn quarantine(input: &Path, reason: &str, dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Cannot create the quarantine {}: {}", dir.display(), e))?;
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    let mut moved = dir.join(name.as_ref());
+    let mut count = 1;
+    while moved.exists() {
+        count += 1;
+        moved = dir.join(format!("{}_{}", count, name));
+    }
+
+    // Renaming fails across file systems, where the input is copied and removed instead
+    if fs::rename(input, &moved).is_err() {
+        fs::copy(input, &moved)
+            .and_then(|_| fs::remove_file(input))
+            .map_err(|e| format!("Cannot quarantine {}: {}", input.display(), e))?;
+    }
+    let mut reason_name = moved.file_name().unwrap_or_default().to_os_string();
+    reason_name.push(".reason.txt");
+    fs::write(
+        moved.with_file_name(reason_name),
+        format!("{}\n{}\n", input.display(), reason),
+    )?;
+    Ok(moved)
+}
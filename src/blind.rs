@@ -0,0 +1,122 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::provenance::JobSpec;
+use crate::shuffle::{random_seed, SplitMix64};
+
+/// The label shown on a cell instead of its input's name: A, B, C, ..., Z, AA, AB, ...
+pub fn blind_label(index: usize) -> String {
+    let letter = (b'A' + (index % 26) as u8) as char;
+    if index < 26 {
+        letter.to_string()
+    } else {
+        format!("{}{}", blind_label(index / 26 - 1), letter)
+    }
+}
+
+/// Salt mixed into the keystream, so keys can't be read by simply XOR-ing with the nonce
+const KEY_SALT: u64 = 0x7669_6467_7269_6421;
+
+/// Which input is hiding behind a blind label
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub label: String,
+    /// 1-based position of the cell
+    pub cell: usize,
+    /// 1-based number of the input shown in the cell
+    pub input: usize,
+    pub source: String,
+}
+
+/// The answer key of a blind test
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnswerKey {
+    /// Seed that reproduces the shuffle with `--shuffle --seed`
+    pub seed: u64,
+    pub entries: Vec<KeyEntry>,
+    /// The job that rendered the test, kept out of the grid so it doesn't give the answer away
+    #[serde(default)]
+    pub job: Option<JobSpec>,
+}
+
+/// The answer key as stored on disk, obscured so it isn't revealed by a casual glance
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedKey {
+    vidgrid_blind_key: u32,
+    nonce: u64,
+    data: String,
+}
+
+/// Seals the answer key and writes it to `path`. Use `vidgrid reveal` to read it back.
+///
+/// The key is obscured, not encrypted: it keeps evaluators from accidentally seeing the answer,
+/// but is not meant to withstand a determined attempt to read it.
+pub fn write_key(key: &AnswerKey, path: &Path) -> Result<(), Box<dyn Error>> {
+    let nonce = random_seed();
+    let plain = serde_json::to_vec(key)?;
+
+    let mut data = String::with_capacity(plain.len() * 2);
+    for byte in apply_keystream(&plain, nonce) {
+        write!(data, "{:02x}", byte)?;
+    }
+
+    let sealed = SealedKey {
+        vidgrid_blind_key: 1,
+        nonce,
+        data,
+    };
+    fs::write(path, serde_json::to_string_pretty(&sealed)?)?;
+    Ok(())
+}
+
+/// Reads a sealed answer key written by `--blind`
+pub fn read_key(path: &Path) -> Result<AnswerKey, Box<dyn Error>> {
+    let sealed: SealedKey = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|_| format!("{} is not a vidgrid blind test key", path.display()))?;
+
+    if !sealed.data.len().is_multiple_of(2) {
+        return Err(format!("{} is corrupted", path.display()).into());
+    }
+    let obscured = (0..sealed.data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&sealed.data[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("{} is corrupted", path.display()))?;
+
+    let plain = apply_keystream(&obscured, sealed.nonce);
+    Ok(serde_json::from_slice(&plain)?)
+}
+
+/// Prints which input is behind each label of a blind test
+pub fn reveal(path: &Path) -> Result<(), Box<dyn Error>> {
+    let key = read_key(path)?;
+    for entry in &key.entries {
+        println!(
+            "{} (cell {}): input {} - {}",
+            entry.label, entry.cell, entry.input, entry.source
+        );
+    }
+    if let Some(job) = &key.job {
+        println!("Job: {}", serde_json::to_string(job)?);
+    }
+    Ok(())
+}
+
+/// XORs the data with a keystream derived from the nonce. Applying it twice restores the data.
+fn apply_keystream(data: &[u8], nonce: u64) -> Vec<u8> {
+    let mut rng = SplitMix64(nonce ^ KEY_SALT);
+    data.chunks(8)
+        .flat_map(|chunk| {
+            let stream = rng.next().to_le_bytes();
+            chunk
+                .iter()
+                .zip(stream)
+                .map(|(byte, key)| byte ^ key)
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
@@ -0,0 +1,904 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::annotation::Annotation;
+use crate::escape::{escape_drawtext, escape_filter_option};
+use crate::input::{InputSource, Region};
+use crate::locale::Locale;
+use crate::overlay_data::DataEvent;
+use crate::settings::RedactStyle;
+use crate::time::{format_timecode, parse_seconds, timecode_frames};
+use crate::VidGridError;
+
+/// How long an RTSP feed may send nothing before it is given up on
+const RTSP_TIMEOUT_MICROSECONDS: u64 = 10_000_000;
+
+/// A single cell of the grid: where its video comes from and how it is rendered
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub source: InputSource,
+    /// Index of the video stream of the source file that feeds the cell
+    pub stream: usize,
+    /// Position in the source, in seconds, at which the cell starts playing
+    pub start: Option<f64>,
+    /// Position in the source, in seconds, at which the cell stops playing
+    pub end: Option<f64>,
+    /// Text drawn in a corner of the cell
+    pub label: Option<String>,
+    /// How the label is drawn
+    pub label_style: LabelStyle,
+    /// Font file or fontconfig family used for text drawn on the cell
+    pub font: Option<String>,
+    /// How numbers, dates and times drawn on the cell are written
+    pub locale: Locale,
+    /// Show only a single plane of the source, to inspect it in isolation
+    pub plane: Option<Plane>,
+    /// Number of recent frames blended together to draw a motion trail
+    pub trail: Option<u32>,
+    /// Regions of the source obscured for privacy
+    pub redactions: Vec<Redaction>,
+    /// Moments at which the cell holds its frame while the rest of the grid plays on
+    pub freezes: Vec<Freeze>,
+    /// Timestamped events captioned on the cell while they are active
+    pub events: Vec<DataEvent>,
+    /// Reviewers' notes drawn on the cell, timed in the grid
+    pub annotations: Vec<Annotation>,
+    /// Text file drawn at the top of the cell and read again for every frame, so whatever writes
+    /// to it, e.g. a scoreboard, updates the cell as it plays
+    pub live_text: Option<PathBuf>,
+    /// A quality score drawn in the top-left corner of the cell, e.g. its VMAF against another
+    /// cell
+    pub quality_score: Option<String>,
+    /// Subtitle file holding the telemetry readouts drawn on the cell
+    pub telemetry: Option<PathBuf>,
+    /// Hex key for reading an encrypted (CENC) source file
+    pub decryption_key: Option<String>,
+    /// Decode the source file on this hardware instead of the CPU
+    pub hw_decoder: Option<HwDecoder>,
+    /// How many times reading an HTTP(S) source is retried after the connection drops
+    pub network_retries: u32,
+    /// Trim the source by decoding it from the start rather than by seeking, so the cell starts on
+    /// exactly the frame at its start point
+    pub accurate_seek: bool,
+    /// Draw the running time or frame number of the cell in its bottom-right corner
+    pub timestamp: Option<TimestampFormat>,
+    /// Where the time of the timestamp is taken from
+    pub timecode_from: TimecodeSource,
+    /// The date and time on the clock where the source was recorded when its first frame was, in
+    /// seconds since the Unix epoch as read in UTC. Drawn advancing at the bottom of the cell
+    pub wall_clock: Option<f64>,
+    /// Subtitles burned into the cell
+    pub subtitles: Option<Subtitles>,
+    /// Subtitle file added to the output as a track of its own, timed like the cell
+    pub captions: Option<PathBuf>,
+    /// ffmpeg audio filters applied to the cell's audio wherever it is heard, e.g.
+    /// `highpass=f=80,agate` to tame wind rumble
+    pub audio_filter: Option<String>,
+    /// Image shown on top of the cell for the last seconds it plays
+    pub end_card: Option<EndCard>,
+    /// Keeps the cell obscured until a moment of the grid, so it can't spoil what the other
+    /// cells are building up to
+    pub spoiler: Option<Spoiler>,
+    /// How the source is fitted to the cell when their aspect ratios differ
+    pub fit: Fit,
+}
+
+impl Cell {
+    pub fn new(source: InputSource) -> Self {
+        Cell {
+            source,
+            stream: 0,
+            start: None,
+            end: None,
+            label: None,
+            label_style: LabelStyle::default(),
+            font: None,
+            locale: Locale::default(),
+            plane: None,
+            trail: None,
+            redactions: Vec::new(),
+            freezes: Vec::new(),
+            events: Vec::new(),
+            annotations: Vec::new(),
+            live_text: None,
+            quality_score: None,
+            telemetry: None,
+            decryption_key: None,
+            hw_decoder: None,
+            network_retries: 0,
+            accurate_seek: false,
+            timestamp: None,
+            timecode_from: TimecodeSource::Output,
+            wall_clock: None,
+            subtitles: None,
+            captions: None,
+            audio_filter: None,
+            end_card: None,
+            spoiler: None,
+            fit: Fit::Pad,
+        }
+    }
+
+    /// Input options for reading the cell's source file: reconnecting to it, decrypting it,
+    /// decoding it on hardware and trimming it to its start and end points
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.network_retries > 0 && self.is_http_source() {
+            // ffmpeg waits 0, 1, 3, 7, ... seconds before each attempt and gives up once the wait
+            // would exceed the maximum delay, so this allows exactly `network_retries` attempts
+            let max_delay = (1u64 << (self.network_retries - 1).min(32)) - 1;
+            args.extend([
+                "-reconnect".to_string(),
+                "1".to_string(),
+                "-reconnect_streamed".to_string(),
+                "1".to_string(),
+                "-reconnect_on_network_error".to_string(),
+                "1".to_string(),
+                "-reconnect_delay_max".to_string(),
+                max_delay.to_string(),
+            ]);
+        }
+        if self.is_rtsp_source() {
+            // Over TCP no packets are lost on the way, and a feed that stops sending is given up
+            // on rather than waited for forever. RTSP has no reconnection, so isn't retried
+            args.extend([
+                "-rtsp_transport".to_string(),
+                "tcp".to_string(),
+                "-timeout".to_string(),
+                RTSP_TIMEOUT_MICROSECONDS.to_string(),
+            ]);
+        }
+        if let Some(key) = &self.decryption_key {
+            args.push("-decryption_key".to_string());
+            args.push(key.clone());
+        }
+        if let Some(decoder) = self.hw_decoder {
+            // Without `-hwaccel_output_format`, decoded frames are copied back to system memory,
+            // so the filters see them like any other cell's
+            args.push("-hwaccel".to_string());
+            args.push(decoder.name().to_string());
+        }
+        if self.accurate_seek {
+            return args;
+        }
+        if let Some(start) = self.start {
+            args.push("-ss".to_string());
+            args.push(start.to_string());
+        }
+        if let Some(end) = self.end {
+            args.push("-to".to_string());
+            args.push(end.to_string());
+        }
+        args
+    }
+
+    /// The filter trimming a stream of the source to the cell's start and end points, when they
+    /// aren't seeked to by the input options. `audio` selects the audio variants of the filters.
+    /// Like `-ss`, the points count from the start of the source, so timestamps are shifted to
+    /// start at zero first for sources that don't, such as MPEG-TS recordings.
+    pub fn trim_filter(&self, audio: bool) -> Option<String> {
+        if !self.accurate_seek || (self.start.is_none() && self.end.is_none()) {
+            return None;
+        }
+        let mut points = Vec::new();
+        if let Some(start) = self.start {
+            points.push(format!("start={}", start));
+        }
+        if let Some(end) = self.end {
+            points.push(format!("end={}", end));
+        }
+        let prefix = if audio { "a" } else { "" };
+        Some(format!(
+            "{prefix}setpts=PTS-STARTPTS,{prefix}trim={},{prefix}setpts=PTS-STARTPTS",
+            points.join(":")
+        ))
+    }
+
+    /// The filters pausing the cell's audio for its freezes, as a chain that splits the audio at
+    /// each freeze and delays what follows with silence. Pads inside it are named after `label`.
+    pub fn freeze_audio_filter(&self, label: &str) -> Option<String> {
+        let start = self.start.unwrap_or(0.0);
+        let mut freezes: Vec<&Freeze> = self
+            .freezes
+            .iter()
+            .filter(|freeze| freeze.at >= start)
+            .collect();
+        if freezes.is_empty() {
+            return None;
+        }
+        freezes.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        let parts = freezes.len() + 1;
+        let split: String = (0..parts)
+            .map(|part| format!("[{label}fz{part}]"))
+            .collect();
+        let mut filters = vec![format!("asetpts=PTS-STARTPTS,asplit={parts}{split}")];
+        for part in 0..parts {
+            let mut points = Vec::new();
+            if part > 0 {
+                points.push(format!("start={}", freezes[part - 1].at - start));
+            }
+            if let Some(freeze) = freezes.get(part) {
+                points.push(format!("end={}", freeze.at - start));
+            }
+            let delay = match part {
+                0 => String::new(),
+                _ => format!(
+                    ",adelay=delays={}:all=1",
+                    freezes[part - 1].duration * 1000.0
+                ),
+            };
+            filters.push(format!(
+                "[{label}fz{part}]atrim={},asetpts=PTS-STARTPTS{delay}[{label}fs{part}]",
+                points.join(":")
+            ));
+        }
+        let segments: String = (0..parts)
+            .map(|part| format!("[{label}fs{part}]"))
+            .collect();
+        filters.push(format!("{segments}concat=n={parts}:v=0:a=1"));
+        Some(filters.join("; "))
+    }
+
+    /// Whether the cell's source is streamed over HTTP(S), e.g. an HLS playlist
+    fn is_http_source(&self) -> bool {
+        self.source
+            .as_file()
+            .and_then(|path| path.to_str())
+            .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+    }
+
+    /// Whether the cell's source is a live RTSP feed, e.g. from an IP camera
+    pub fn is_rtsp_source(&self) -> bool {
+        self.source
+            .as_file()
+            .and_then(|path| path.to_str())
+            .is_some_and(|path| path.starts_with("rtsp://") || path.starts_with("rtsps://"))
+    }
+
+    /// How long the cell plays for, given the duration of its source file
+    pub fn trimmed_duration(&self, source_duration: f64) -> f64 {
+        let start = self.start.unwrap_or(0.0);
+        let end = self
+            .end
+            .map_or(source_duration, |end| end.min(source_duration));
+        let frozen: f64 = self
+            .freezes
+            .iter()
+            .filter(|freeze| (start..end).contains(&freeze.at))
+            .map(|freeze| freeze.duration)
+            .sum();
+        (end - start).max(0.0) + frozen
+    }
+
+    /// Converts a time in the cell's source to the time it is shown at in the cell, accounting
+    /// for the cell's trim and the freezes before it
+    fn cell_time(&self, source_time: f64) -> f64 {
+        let start = self.start.unwrap_or(0.0);
+        let frozen: f64 = self
+            .freezes
+            .iter()
+            .filter(|freeze| freeze.at >= start && freeze.at <= source_time)
+            .map(|freeze| freeze.duration)
+            .sum();
+        source_time - start + frozen
+    }
+
+    /// Filters applied to each source stream of the cell before it is conformed to the grid
+    pub fn source_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+
+        // Burned in at the size of the source, so the text keeps its proportion to the cell once
+        // scaled. Subtitle times refer to the source, so the trimmed stream is shifted back to
+        // them while they are drawn
+        let subtitles = match (&self.subtitles, self.source.as_file()) {
+            (Some(Subtitles::File(path)), _) => Some(escape_filter_option(&path.to_string_lossy())),
+            (Some(Subtitles::Track(track)), Some(path)) => Some(format!(
+                "{}:si={}",
+                escape_filter_option(&path.to_string_lossy()),
+                track
+            )),
+            _ => None,
+        };
+        if let Some(subtitles) = subtitles {
+            let burn = format!("subtitles=filename={}", subtitles);
+            filters.push(match self.start {
+                Some(start) if start > 0.0 => {
+                    format!("setpts=PTS+{start}/TB,{burn},setpts=PTS-{start}/TB")
+                }
+                _ => burn,
+            });
+        }
+
+        // Extract before scaling, so subsampled chroma planes are shown at their native
+        // resolution blown up to the cell rather than pre-interpolated
+        if let Some(plane) = self.plane {
+            filters.push(format!("extractplanes={}", plane.name()));
+        }
+
+        // Delay everything after each freeze; the frame rate conversion that follows fills the
+        // gap by repeating the frame shown at the freeze
+        let start = self.start.unwrap_or(0.0);
+        let delays: Vec<String> = self
+            .freezes
+            .iter()
+            .filter(|freeze| freeze.at >= start)
+            .map(|freeze| format!("gte(T,{})*{}", freeze.at - start, freeze.duration))
+            .collect();
+        if !delays.is_empty() {
+            filters.push(format!(
+                "setpts={}",
+                escape_filter_option(&format!("PTS+({})/TB", delays.join("+")))
+            ));
+        }
+
+        filters
+    }
+
+    /// The `drawtext` option selecting the cell's font, if it has one. Existing files are used
+    /// directly, anything else is looked up as a fontconfig family.
+    pub fn font_option(&self) -> String {
+        match &self.font {
+            Some(font) if is_font_file(font) => {
+                format!(":fontfile={}", escape_filter_option(font))
+            }
+            Some(font) => format!(":font={}", escape_filter_option(font)),
+            None => String::new(),
+        }
+    }
+
+    /// The `drawtext` filter burning the timecode of the source into the cell as its timestamp,
+    /// given the `timecode` of the source's first frame at `fps`. Applied to the source's own
+    /// frames, so the timecode advances by its frames rather than by those of the grid.
+    pub fn timecode_filter(&self, timecode: &str, fps: f64) -> Result<String, String> {
+        let skipped = (self.start.unwrap_or(0.0) * fps).round() as u64;
+        let first = timecode_frames(timecode, fps)? + skipped;
+        Ok(format!(
+            "drawtext=timecode={}:timecode_rate={}{}:x=w-text_w-h/40:y=h-text_h-h/40:fontsize=h/20:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=6",
+            escape_filter_option(&format_timecode(first, fps, timecode.contains(';'))),
+            fps,
+            self.font_option()
+        ))
+    }
+
+    /// Filters applied to the cell after it has been conformed to the grid
+    pub fn effect_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+
+        // Blend the most recent frames together so movement leaves a ghost trail
+        if let Some(frames) = self.trail {
+            filters.push(format!("tmix=frames={}", frames));
+        }
+
+        if let Some(label) = &self.label {
+            let style = &self.label_style;
+            filters.push(format!(
+                "drawtext=text={}{}:x=h/40:y={}:fontsize=h*{}/16:fontcolor={}:box=1:boxcolor=black@0.5:boxborderw=8",
+                escape_drawtext(label),
+                self.font_option(),
+                style.position.y(),
+                style.scale,
+                escape_filter_option(&style.color)
+            ));
+        }
+
+        // The time counts from the start of the source, so trimmed cells show where they are in it.
+        // It is a running time rather than a time of day, so only its decimals follow the locale.
+        // Source timecodes are drawn by `timecode_filter` instead
+        if let (Some(format), TimecodeSource::Output) = (self.timestamp, self.timecode_from) {
+            let offset = self.start.unwrap_or(0.0);
+            let time = match self.locale.decimal_separator {
+                '.' => format!("%{{pts:hms:{}}}", offset),
+                separator => format!(
+                    "%{{eif:trunc((t+{o})/3600):d:2}}:%{{eif:mod(trunc((t+{o})/60),60):d:2}}:\
+                     %{{eif:mod(trunc(t+{o}),60):d:2}}{separator}%{{eif:mod((t+{o})*1000,1000):d:3}}",
+                    o = offset
+                ),
+            };
+            let text = match format {
+                TimestampFormat::Time => time,
+                TimestampFormat::Frames => "%{n}".to_string(),
+                TimestampFormat::Both => format!("{}  #%{{n}}", time),
+            };
+            filters.push(format!(
+                "drawtext=text={}{}:x=w-text_w-h/40:y=h-text_h-h/40:fontsize=h/20:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=6",
+                escape_filter_option(&text),
+                self.font_option()
+            ));
+        }
+
+        // Formatted from the frame's own timestamp, so the clock advances exactly with the video.
+        // The seconds are counted apart, as strftime has no fractions of a second.
+        if let Some(clock) = self.wall_clock {
+            let first = clock + self.start.unwrap_or(0.0);
+            let mut text = format!(
+                "%{{pts:gmtime:{first}:{date} {time}}}{separator}%{{eif:mod((t+{fract})*1000,1000):d:3}}",
+                date = self.locale.date_format,
+                time = self.locale.time_format().replace(':', "\\:"),
+                separator = self.locale.decimal_separator,
+                fract = first.fract()
+            );
+            if self.locale.twelve_hour {
+                text.push_str(&format!(" %{{pts:gmtime:{}:%p}}", first));
+            }
+            filters.push(format!(
+                "drawtext=text={}{}:x=(w-text_w)/2:y=h-text_h-h/40:fontsize=h/20:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=6",
+                escape_filter_option(&text),
+                self.font_option()
+            ));
+        }
+
+        if let Some(path) = &self.live_text {
+            filters.push(format!(
+                "drawtext=textfile={}:reload=1{}:x=(w-text_w)/2:y=h/40:fontsize=h/18:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=6",
+                escape_filter_option(&path.to_string_lossy()),
+                self.font_option()
+            ));
+        }
+
+        if let Some(score) = &self.quality_score {
+            filters.push(format!(
+                "drawtext=text={}{}:x=h/40:y=h/40:fontsize=h/20:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=6",
+                escape_drawtext(score),
+                self.font_option()
+            ));
+        }
+
+        if let Some(readouts) = &self.telemetry {
+            filters.push(format!(
+                "subtitles=filename={}:force_style={}",
+                escape_filter_option(&readouts.to_string_lossy()),
+                escape_filter_option("Alignment=1,FontSize=14,Outline=2,MarginL=10")
+            ));
+        }
+
+        // Event times refer to the source, so shift them by the cell's trim and freezes
+        for event in &self.events {
+            let time = self.cell_time(event.time);
+            let enable = format!("between(t,{},{})", time, time + event.duration);
+            filters.push(format!(
+                "drawtext=text={}{}:enable={}:x=(w-text_w)/2:y=h-text_h-h/20:fontsize=h/14:fontcolor=yellow:box=1:boxcolor=black@0.6:boxborderw=8",
+                escape_drawtext(&event.text),
+                self.font_option(),
+                escape_filter_option(&enable)
+            ));
+        }
+
+        for annotation in &self.annotations {
+            filters.push(annotation.filter(&self.font_option()));
+        }
+
+        filters
+    }
+}
+
+/// How a cell's label is drawn. Sizes are relative to the cell, so labels keep their proportions
+/// in grids of any size
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelStyle {
+    /// Font size, as a multiple of the default of 1/16 of the cell height
+    pub scale: f64,
+    /// Text color, as an ffmpeg color such as `white` or `#ffcc00`
+    pub color: String,
+    /// Whether the label sits at the top or the bottom of the cell
+    pub position: LabelPosition,
+}
+
+impl Default for LabelStyle {
+    /// White text of 1/16 of the cell height in the top-left corner
+    fn default() -> Self {
+        LabelStyle {
+            scale: 1.0,
+            color: "white".to_string(),
+            position: LabelPosition::Top,
+        }
+    }
+}
+
+/// The edge of the cell a label is drawn along
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LabelPosition {
+    Top,
+    Bottom,
+}
+
+impl LabelPosition {
+    /// The `drawtext` expression placing the text at this edge
+    fn y(self) -> &'static str {
+        match self {
+            LabelPosition::Top => "h/40",
+            LabelPosition::Bottom => "h-text_h-h/40",
+        }
+    }
+}
+
+/// What the timestamp drawn on a cell shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    /// The time in the source as `hh:mm:ss.mmm`
+    Time,
+    /// The number of the frame in the cell
+    Frames,
+    /// Both the time and the frame number
+    Both,
+}
+
+/// Where the time of a cell's timestamp is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimecodeSource {
+    /// The running time of the source as the grid plays it
+    Output,
+    /// The timecode embedded in the source, e.g. by a professional camera
+    Source,
+}
+
+/// Subtitles burned into a cell (`--subs 2:dub.srt` or `--subs 2:track:0`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subtitles {
+    /// A subtitle file, such as SRT or ASS
+    File(PathBuf),
+    /// A subtitle stream of the cell's source file, counted among its subtitle streams
+    Track(usize),
+}
+
+impl FromStr for Subtitles {
+    type Err = String;
+
+    /// Parses `track:<index>` as a subtitle stream of the source and anything else as a file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("track:") {
+            Some(track) => track
+                .parse()
+                .map(Subtitles::Track)
+                .map_err(|_| format!("Invalid subtitle track '{}'", track)),
+            None => Ok(Subtitles::File(PathBuf::from(s))),
+        }
+    }
+}
+
+/// An image shown on top of a cell for the last seconds it plays, e.g. a team's logo or result
+/// (`--endcard 2:logo.png:3s`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndCard {
+    pub image: PathBuf,
+    /// How many seconds before the cell ends the image is shown
+    pub duration: f64,
+}
+
+impl FromStr for EndCard {
+    type Err = String;
+
+    /// Parses an end card of the form `<image>:<time>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (image, duration) = s
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Invalid end card '{}', expected <image>:<time>", s))?;
+        Ok(EndCard {
+            image: PathBuf::from(image),
+            duration: parse_seconds(duration)?,
+        })
+    }
+}
+
+/// A region of a cell's source obscured for privacy (`--blur-region 2:x:y:w:h:start-end`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Redaction {
+    /// The region, in source pixels
+    pub region: Region,
+    /// Start and end times in the source, in seconds, between which the region is obscured. The
+    /// end may be infinite. Obscured throughout when not given.
+    pub time: Option<(f64, f64)>,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    /// Parses a redaction of the form `x:y:w:h[:start-end]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The times may contain colons themselves (`1:05-1:10`)
+        let parts: Vec<&str> = s.splitn(5, ':').collect();
+        let region = Region::from_str(&parts[..parts.len().min(4)].join(":"))?;
+        let time = match parts.get(4) {
+            Some(time) => {
+                let (start, end) = time
+                    .split_once('-')
+                    .ok_or_else(|| format!("Invalid time range '{}', expected start-end", time))?;
+                let (start, end) = (parse_seconds(start)?, parse_seconds(end)?);
+                if end <= start {
+                    return Err(format!("Time range '{}' ends before it starts", time));
+                }
+                Some((start, end))
+            }
+            None => None,
+        };
+        Ok(Redaction { region, time })
+    }
+}
+
+impl Redaction {
+    /// Parses the regions found by an external detector: a JSON array of rectangles in source
+    /// pixels, with optional start and end times in seconds of the source, e.g.
+    /// `[{"x": 10, "y": 20, "width": 64, "height": 64, "start": 1.5, "end": 4}]`
+    pub fn list_from_json(json: &str) -> Result<Vec<Redaction>, VidGridError> {
+        #[derive(Deserialize)]
+        struct Detection {
+            x: u32,
+            y: u32,
+            width: u32,
+            height: u32,
+            start: Option<f64>,
+            end: Option<f64>,
+        }
+
+        let detections: Vec<Detection> = serde_json::from_str(json)
+            .map_err(|e| VidGridError::Parse(format!("Invalid regions: {}", e)))?;
+        Ok(detections
+            .into_iter()
+            .filter(|detection| detection.width > 0 && detection.height > 0)
+            .map(|detection| Redaction {
+                region: Region {
+                    x: detection.x,
+                    y: detection.y,
+                    width: detection.width,
+                    height: detection.height,
+                },
+                time: match (detection.start, detection.end) {
+                    (None, None) => None,
+                    (start, end) => Some((start.unwrap_or(0.0), end.unwrap_or(f64::INFINITY))),
+                },
+            })
+            .collect())
+    }
+}
+
+/// A pause of a single cell (`--freeze 2:at=12s:for=3s`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Freeze {
+    /// Time in the cell's source, in seconds, whose frame is held
+    pub at: f64,
+    /// How long the frame is held for, in seconds
+    pub duration: f64,
+}
+
+impl FromStr for Freeze {
+    type Err = String;
+
+    /// Parses a freeze of the form `at=<time>:for=<time>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Times may themselves contain colons (`at=1:30`), so parts without a key continue the
+        // value before them
+        let mut options: Vec<(&str, String)> = Vec::new();
+        for part in s.split(':') {
+            match (part.split_once('='), options.last_mut()) {
+                (Some((key, value)), _) => options.push((key, value.to_string())),
+                (None, Some((_, value))) => *value = format!("{}:{}", value, part),
+                (None, None) => {
+                    return Err(format!(
+                        "Invalid freeze '{}', expected at=<time>:for=<time>",
+                        s
+                    ))
+                }
+            }
+        }
+
+        let mut at = None;
+        let mut duration = None;
+        for (key, value) in options {
+            match key {
+                "at" => at = Some(parse_seconds(&value)?),
+                "for" => duration = Some(parse_seconds(&value)?),
+                _ => return Err(format!("Unknown freeze option '{}'", key)),
+            }
+        }
+
+        match (at, duration) {
+            (Some(at), Some(duration)) => Ok(Freeze { at, duration }),
+            _ => Err(format!(
+                "Invalid freeze '{}', expected at=<time>:for=<time>",
+                s
+            )),
+        }
+    }
+}
+
+/// A cell kept obscured until a moment of the grid and then revealed
+/// (`--spoiler 3:until=30s:fade=2s`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spoiler {
+    /// Time in the grid, in seconds, at which the cell is revealed
+    pub until: f64,
+    /// How many seconds the cell takes to come into view, ending at `until`. Revealed at once
+    /// when zero
+    pub fade: f64,
+    /// How the cell is obscured
+    pub style: RedactStyle,
+}
+
+impl Spoiler {
+    /// The filters obscuring a `width`x`height` cell until it is revealed, to put before the
+    /// filter chain read from the cell's pad. `label` names the pads in between
+    pub fn filter(&self, label: &str, width: u32, height: u32) -> String {
+        let fade = if self.fade > 0.0 {
+            format!(
+                ",format=yuva420p,fade=t=out:st={}:d={}:alpha=1",
+                (self.until - self.fade).max(0.0),
+                self.fade
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "split[{label}clear][{label}hidden]; \
+             [{label}hidden]{obscure}{fade}[{label}veil]; \
+             [{label}clear][{label}veil]overlay=eof_action=pass:enable={enable},",
+            obscure = self.style.filter(width, height),
+            enable = escape_filter_option(&format!("lt(t,{})", self.until)),
+        )
+    }
+}
+
+impl FromStr for Spoiler {
+    type Err = String;
+
+    /// Parses a spoiler of the form `until=<time>[:fade=<time>][:style=blur|pixelate]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Times may themselves contain colons (`until=1:30`), so parts without a key continue
+        // the value before them
+        let mut options: Vec<(&str, String)> = Vec::new();
+        for part in s.split(':') {
+            match (part.split_once('='), options.last_mut()) {
+                (Some((key, value)), _) => options.push((key, value.to_string())),
+                (None, Some((_, value))) => *value = format!("{}:{}", value, part),
+                (None, None) => {
+                    return Err(format!(
+                        "Invalid spoiler '{}', expected until=<time>[:fade=<time>]",
+                        s
+                    ))
+                }
+            }
+        }
+
+        let mut until = None;
+        let mut fade = 0.0;
+        let mut style = RedactStyle::Blur;
+        for (key, value) in options {
+            match key {
+                "until" => until = Some(parse_seconds(&value)?),
+                "fade" => fade = parse_seconds(&value)?,
+                "style" => {
+                    style =
+                        <RedactStyle as clap::ValueEnum>::from_str(&value, true).map_err(|_| {
+                            format!("Invalid style '{}', expected blur or pixelate", value)
+                        })?
+                }
+                _ => return Err(format!("Unknown spoiler option '{}'", key)),
+            }
+        }
+
+        let until = until.ok_or_else(|| {
+            format!(
+                "Invalid spoiler '{}', expected until=<time>[:fade=<time>]",
+                s
+            )
+        })?;
+        if fade > until {
+            return Err(format!(
+                "The fade of spoiler '{}' starts before the grid does",
+                s
+            ));
+        }
+        Ok(Spoiler { until, fade, style })
+    }
+}
+
+/// How a source is fitted to its cell when their aspect ratios differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Show all of the source, filling the rest of the cell as `--pad` says
+    Pad,
+    /// Fill the cell, cutting off the edges of the source that don't fit
+    Crop,
+    /// Fill the cell with all of the source, distorting it
+    Stretch,
+}
+
+impl FromStr for Fit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Fit as clap::ValueEnum>::from_str(s, true)
+            .map_err(|_| format!("Invalid fit '{}', expected pad, crop or stretch", s))
+    }
+}
+
+/// Hardware a cell's source is decoded on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HwDecoder {
+    /// NVIDIA NVDEC
+    Cuda,
+    /// VA-API on Linux, e.g. Intel and AMD GPUs
+    Vaapi,
+    /// Apple VideoToolbox
+    Videotoolbox,
+    /// Intel Quick Sync Video
+    Qsv,
+    /// Direct3D 11 on Windows
+    D3d11va,
+    /// Whichever hardware ffmpeg finds first
+    Auto,
+}
+
+impl HwDecoder {
+    /// The name ffmpeg's `-hwaccel` option knows the decoder by
+    fn name(self) -> &'static str {
+        match self {
+            HwDecoder::Cuda => "cuda",
+            HwDecoder::Vaapi => "vaapi",
+            HwDecoder::Videotoolbox => "videotoolbox",
+            HwDecoder::Qsv => "qsv",
+            HwDecoder::D3d11va => "d3d11va",
+            HwDecoder::Auto => "auto",
+        }
+    }
+}
+
+impl FromStr for HwDecoder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <HwDecoder as clap::ValueEnum>::from_str(s, true).map_err(|_| {
+            format!(
+                "Invalid hardware decoder '{}', expected cuda, vaapi, videotoolbox, qsv, d3d11va \
+                 or auto",
+                s
+            )
+        })
+    }
+}
+
+/// A single plane of a YUV video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    Y,
+    U,
+    V,
+}
+
+impl Plane {
+    /// The name the `extractplanes` filter uses for this plane
+    fn name(self) -> &'static str {
+        match self {
+            Plane::Y => "y",
+            Plane::U => "u",
+            Plane::V => "v",
+        }
+    }
+}
+
+impl FromStr for Plane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "y" => Ok(Plane::Y),
+            "u" => Ok(Plane::U),
+            "v" => Ok(Plane::V),
+            _ => Err(format!("Invalid plane '{}', expected y, u or v", s)),
+        }
+    }
+}
+
+/// Whether `font` is an existing font file rather than the name of a fontconfig family
+#[cfg(feature = "render")]
+fn is_font_file(font: &str) -> bool {
+    std::path::Path::new(font).is_file()
+}
+
+/// Without rendering there are no files to look for, so every font is taken for a family
+#[cfg(not(feature = "render"))]
+fn is_font_file(_font: &str) -> bool {
+    false
+}
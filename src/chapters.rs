@@ -0,0 +1,52 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+/// A chapter marker of a media file
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    /// Start of the chapter, in seconds
+    pub start: f64,
+    /// End of the chapter, in seconds
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+/// Reads the chapter markers of a media file using ffprobe
+pub fn read_chapters(path: &Path) -> Result<Vec<Chapter>, Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_chapters")
+        .arg("-of")
+        .arg("json")
+        .arg(path)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(format!("ffprobe failed for {}", path.display()).into());
+    }
+
+    let probe: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let mut chapters = Vec::new();
+    for chapter in probe["chapters"].as_array().into_iter().flatten() {
+        // ffprobe prints times as strings to keep their precision
+        let time = |key: &str| -> Result<f64, Box<dyn Error>> {
+            let time = chapter[key]
+                .as_str()
+                .ok_or("Chapter has no start or end time")?;
+            Ok(f64::from_str(time)?)
+        };
+        chapters.push(Chapter {
+            start: time("start_time")?,
+            end: time("end_time")?,
+            title: chapter["tags"]["title"].as_str().map(str::to_string),
+        });
+    }
+
+    if chapters.is_empty() {
+        return Err(format!("{} has no chapters", path.display()).into());
+    }
+    Ok(chapters)
+}
@@ -0,0 +1,38 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Hash algorithms available for `--checksums`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Hashes the file at `path`, returning the digest as lowercase hex
+    pub fn hash_file(self, path: &Path) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let mut buffer = vec![0; 1 << 20];
+        match self {
+            ChecksumAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                loop {
+                    let read = file.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buffer[..read]);
+                }
+                Ok(hasher
+                    .finalize()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+
+/// Whether a file is read as TOML rather than JSON, going by its `.toml` extension
+pub fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Parses the contents of the file at `path`: TOML for `.toml` files, JSON for any other
+pub fn parse<T: DeserializeOwned>(path: &Path, text: &str) -> Result<T, String> {
+    if is_toml(path) {
+        serde_json::from_value(parse_toml(text)?).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+/// Parses a TOML document into the JSON value of the same shape. Dates and times, which JSON
+/// has no counterpart for, are refused, as are infinite and NaN floats.
+pub fn parse_toml(text: &str) -> Result<Value, String> {
+    let mut parser = TomlParser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser
+        .document()
+        .map_err(|message| format!("line {}: {}", parser.line, message))
+}
+
+struct TomlParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl TomlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.pos + offset) == Some(&c))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+            None => Err(format!(
+                "expected '{}', found the end of the file",
+                expected
+            )),
+        }
+    }
+
+    /// Skips spaces and tabs, and a comment running to the end of the line
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.next();
+            }
+        }
+    }
+
+    /// Skips blank lines and comments as well
+    fn skip_lines(&mut self) {
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some('\n') => {
+                    self.next();
+                }
+                Some('\r') if self.chars.get(self.pos + 1) == Some(&'\n') => {
+                    self.next();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_space();
+        if self.peek() == Some('\r') {
+            self.next();
+        }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(format!("expected the end of the line, found '{}'", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, String> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_lines();
+            match self.peek() {
+                None => return Ok(Value::Object(root)),
+                Some('[') if self.starts_with("[[") => {
+                    self.pos += 2;
+                    let key = self.key()?;
+                    self.expect(']')?;
+                    self.expect(']')?;
+                    let (last, parents) = key.split_last().expect("keys have a part");
+                    let parent = table_at(&mut root, parents)?;
+                    let array = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    match array {
+                        Value::Array(tables) => tables.push(Value::Object(Map::new())),
+                        _ => return Err(format!("'{}' is not an array of tables", last)),
+                    }
+                    current = key;
+                }
+                Some('[') => {
+                    self.next();
+                    let key = self.key()?;
+                    self.expect(']')?;
+                    table_at(&mut root, &key)?;
+                    current = key;
+                }
+                Some(_) => {
+                    let table = table_at(&mut root, &current)?;
+                    self.key_value(table)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    /// Parses `key = value` into `table`
+    fn key_value(&mut self, table: &mut Map<String, Value>) -> Result<(), String> {
+        let key = self.key()?;
+        self.expect('=')?;
+        self.skip_space();
+        let value = self.value()?;
+        let (last, parents) = key.split_last().expect("keys have a part");
+        let table = table_at(table, parents)?;
+        if table.contains_key(last) {
+            return Err(format!("'{}' is defined twice", last));
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    /// Parses a dotted key, with the spaces around it
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_space();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.next();
+                    }
+                    if self.pos == start {
+                        return Err("expected a key".to_string());
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_space();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.multiline_basic_string().map(Value::from)
+            }
+            Some('"') => self.basic_string().map(Value::from),
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Value::from)
+            }
+            Some('\'') => self.literal_string().map(Value::from),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) => self.scalar(),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_lines();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_lines();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(values)),
+                _ => return Err("expected ',' or ']' in an array".to_string()),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut table = Map::new();
+        self.skip_space();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.key_value(&mut table)?;
+            self.skip_space();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(table)),
+                _ => return Err("expected ',' or '}' in an inline table".to_string()),
+            }
+        }
+    }
+
+    /// Parses a boolean or a number
+    fn scalar(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !",]}#".contains(c))
+        {
+            self.next();
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        match token.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        let is_date = token.chars().take(4).filter(char::is_ascii_digit).count() == 4
+            && token.chars().nth(4) == Some('-');
+        if is_date || token.contains(':') {
+            return Err(format!(
+                "dates and times such as {} are not supported",
+                token
+            ));
+        }
+
+        let digits = token.replace('_', "");
+        let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+            .into_iter()
+            .find(|(prefix, _)| digits.starts_with(prefix));
+        if let Some((prefix, radix)) = radix {
+            return i64::from_str_radix(&digits[prefix.len()..], radix)
+                .map(Value::from)
+                .map_err(|_| format!("invalid integer {}", token));
+        }
+        if let Ok(integer) = digits.parse::<i64>() {
+            return Ok(Value::from(integer));
+        }
+        let is_float = digits
+            .trim_start_matches(['+', '-'])
+            .starts_with(|c: char| c.is_ascii_digit());
+        digits
+            .parse::<f64>()
+            .ok()
+            .filter(|_| is_float)
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("invalid value {}", token))
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let mut string = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.pos += 3;
+                return Ok(string);
+            }
+            match self.next() {
+                // A backslash ending a line joins it with the next non-blank one
+                Some('\\') if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) => {
+                    while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.next();
+                    }
+                }
+                Some('\\') => string.push(self.escape()?),
+                Some(c) => string.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(string),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let mut string = String::new();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.pos += 3;
+                return Ok(string);
+            }
+            string.push(self.next().ok_or("unterminated string")?);
+        }
+    }
+
+    /// Skips the newline right after the opening quotes of a multi-line string
+    fn skip_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        if self.peek() == Some('\n') {
+            self.next();
+        }
+    }
+
+    /// Parses the escape sequence following a backslash
+    fn escape(&mut self) -> Result<char, String> {
+        let unicode = |parser: &mut Self, digits: usize| {
+            let hex: String = (0..digits).filter_map(|_| parser.next()).collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("invalid unicode escape \\u{}", hex))
+        };
+        match self.next() {
+            Some('b') => Ok('\u{8}'),
+            Some('t') => Ok('\t'),
+            Some('n') => Ok('\n'),
+            Some('f') => Ok('\u{c}'),
+            Some('r') => Ok('\r'),
+            Some('e') => Ok('\u{1b}'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => unicode(self, 4),
+            Some('U') => unicode(self, 8),
+            Some(c) => Err(format!("invalid escape \\{}", c)),
+            None => Err("unterminated string".to_string()),
+        }
+    }
+}
+
+/// The table at `path` below `table`, created as needed. Arrays of tables lead to their last
+/// table.
+fn table_at<'a>(
+    mut table: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>, String> {
+    for part in path {
+        let value = table
+            .entry(part.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let value = match value {
+            Value::Array(values) => values
+                .last_mut()
+                .ok_or_else(|| format!("'{}' is not a table", part))?,
+            value => value,
+        };
+        table = value
+            .as_object_mut()
+            .ok_or_else(|| format!("'{}' is not a table", part))?;
+    }
+    Ok(table)
+}
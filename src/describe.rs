@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::escape::escape_filter_option;
+use crate::probe::MediaInfo;
+use crate::time::parse_seconds;
+use crate::VidGridError;
+
+/// What is spoken over the audio-described variant of a grid
+#[derive(Debug, Clone)]
+pub enum Narration {
+    /// Lines read out by ffmpeg's flite text-to-speech in `voice`, each at its start time
+    Script { cues: Vec<Cue>, voice: String },
+    /// A recorded narration, played from the start of the grid
+    Recording(PathBuf),
+}
+
+/// A line of a narration script
+#[derive(Debug, Clone)]
+pub struct Cue {
+    /// When the line is spoken, in seconds into the grid
+    pub start: f64,
+    pub text: String,
+}
+
+/// Reads the cues of an SRT narration script. Only their start times are kept, as a line takes
+/// as long as the voice needs to say it.
+pub fn read_script(path: &Path) -> Result<Vec<Cue>, VidGridError> {
+    let contents = fs::read_to_string(path)?;
+    let invalid = |message: String| {
+        VidGridError::Parse(format!("Invalid script {}: {}", path.display(), message))
+    };
+
+    let mut cues = Vec::new();
+    for block in contents.replace("\r\n", "\n").split("\n\n") {
+        let mut lines = block
+            .trim()
+            .lines()
+            .skip_while(|line| !line.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let (start, _) = timing.split_once("-->").expect("the line holds an arrow");
+        let start = parse_seconds(&start.trim().replace(',', ".")).map_err(invalid)?;
+        let text = lines.collect::<Vec<_>>().join(" ");
+        if !text.trim().is_empty() {
+            cues.push(Cue {
+                start,
+                text: text.trim().to_string(),
+            });
+        }
+    }
+
+    if cues.is_empty() {
+        return Err(invalid("it has no cues".to_string()));
+    }
+    Ok(cues)
+}
+
+/// Where the audio-described variant of `output` is written: next to it, with `.described`
+/// before its extension
+pub fn described_path(output: &Path) -> PathBuf {
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(".described");
+    if let Some(extension) = output.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    output.with_file_name(name)
+}
+
+/// Writes a copy of the rendered `grid` to `output` with `narration` mixed over its audio. The
+/// grid's own audio is ducked while the narration speaks, and the video is copied untouched.
+pub fn render_described(
+    grid: &Path,
+    narration: &Narration,
+    output: &Path,
+    quiet: bool,
+) -> Result<(), VidGridError> {
+    let has_audio = MediaInfo::probe(grid)?.has_audio(0);
+
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", if quiet { "error" } else { "warning" }, "-nostdin"]);
+    command.arg("-i").arg(grid);
+
+    let mut filters = Vec::new();
+    match narration {
+        Narration::Script { cues, voice } => {
+            let mut labels = String::new();
+            for (index, cue) in cues.iter().enumerate() {
+                filters.push(format!(
+                    "flite=text={text}:voice={voice},aresample=48000,\
+                     adelay=delays={delay}:all=1[line{index}];",
+                    text = escape_filter_option(&cue.text),
+                    voice = escape_filter_option(voice),
+                    delay = (cue.start * 1000.0).round() as u64,
+                ));
+                labels.push_str(&format!("[line{}]", index));
+            }
+            filters.push(format!(
+                "{labels}amix=inputs={count}:duration=longest:normalize=0[narration];",
+                count = cues.len()
+            ));
+        }
+        Narration::Recording(path) => {
+            command.arg("-i").arg(path);
+            filters.push("[1:a:0]aresample=48000[narration];".to_string());
+        }
+    }
+    if has_audio {
+        filters.push(
+            "[0:a:0]aresample=48000[grid]; [narration]asplit[voice][key]; \
+             [grid][key]sidechaincompress=threshold=0.02:ratio=8:release=400[ducked]; \
+             [ducked][voice]amix=inputs=2:duration=first:normalize=0[described]"
+                .to_string(),
+        );
+    } else {
+        filters.push("[narration]apad[described]".to_string());
+    }
+
+    command
+        .arg("-filter_complex")
+        .arg(filters.join(" "))
+        .args(["-map", "0:v:0", "-map", "[described]"])
+        .args(["-c:v", "copy", "-c:a", "aac", "-shortest", "-y"])
+        .arg(output);
+
+    let status = command.status()?;
+    if !status.success() {
+        return Err(VidGridError::Ffmpeg {
+            task: "mix the narration into the described output".to_string(),
+            status,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Serialize;
+use vidgrid::locale::Locale;
+use vidgrid::probe::MediaInfo;
+use vidgrid::Cell;
+
+use crate::assertion::{self, Outcome};
+use crate::options::DiffArgs;
+
+/// The PSNR reported for identical frames, whose true PSNR is infinite
+const IDENTICAL_PSNR: f64 = 100.0;
+
+/// How two outputs compare, printed as JSON
+#[derive(Debug, Serialize)]
+struct Report {
+    old: PathBuf,
+    new: PathBuf,
+    /// The video showing where the outputs differ
+    visualization: PathBuf,
+    old_duration: f64,
+    new_duration: f64,
+    /// The number of frames compared, those of the shorter output
+    frames: usize,
+    /// Mean difference in brightness over all pixels of all frames, from 0 to 255
+    mean_difference: f64,
+    /// Largest difference of any pixel in any plane of any frame, from 0 to 255
+    max_difference: u8,
+    /// When the frame with the largest difference is shown, in seconds
+    max_difference_at: Option<f64>,
+    /// When the first frame differing by more than the threshold is shown, in seconds
+    first_divergence: Option<f64>,
+    /// How close the new output is to the old one, measured with `--metrics`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<Metrics>,
+    /// How the `--fail-on` assertions came out
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assertions: Vec<Outcome>,
+}
+
+/// Full-reference quality of the new output, with the old one as the reference, averaged over
+/// the frames compared
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    /// Peak signal-to-noise ratio in decibels, 100 for identical outputs
+    pub psnr: f64,
+    /// Structural similarity, from 0 to 1
+    pub ssim: f64,
+    /// Netflix's VMAF score, from 0 to 100. Left out when ffmpeg is built without libvmaf
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vmaf: Option<f64>,
+}
+
+impl Metrics {
+    /// The score burned into a cell with `--metrics-overlay`: the VMAF, or the SSIM without it
+    pub fn score(&self, locale: &Locale) -> String {
+        match self.vmaf {
+            Some(vmaf) => format!("VMAF {}", locale.number(vmaf, 1)),
+            None => format!("SSIM {}", locale.number(self.ssim, 3)),
+        }
+    }
+}
+
+/// The difference between the frames of the outputs at one moment
+struct FrameDifference {
+    time: f64,
+    mean: f64,
+    max: u8,
+}
+
+/// Compares two outputs frame by frame, writing a video of where they differ and printing
+/// statistics of the differences as JSON. Fails if the outputs diverge, so refactors that change
+/// what is rendered are caught.
+pub fn run(args: &DiffArgs) -> Result<(), Box<dyn Error>> {
+    if args.output == args.old || args.output == args.new {
+        return Err("The visualization would replace one of the outputs compared".into());
+    }
+    let old = MediaInfo::probe(&args.old)?;
+    let new = MediaInfo::probe(&args.new)?;
+    let (old_video, new_video) = (
+        old.video_stream(&args.old, 0)?,
+        new.video_stream(&args.new, 0)?,
+    );
+    if (old_video.width, old_video.height) != (new_video.width, new_video.height) {
+        return Err(format!(
+            "The outputs differ in size: {}x{} and {}x{}",
+            old_video.width, old_video.height, new_video.width, new_video.height
+        )
+        .into());
+    }
+
+    // The statistics are written to a file named relative to the directory ffmpeg runs in, which
+    // keeps its path free of characters the filtergraph would need escaped
+    let stats_dir = std::env::temp_dir();
+    let stats_name = format!("vidgrid_diff_{}.txt", std::process::id());
+    let visualization = std::env::current_dir()?.join(&args.output);
+    // The metrics are measured alongside the difference, in the same pass over the outputs
+    let (old_pad, new_pad) = match args.metrics {
+        true => ("[old]", "[new]"),
+        false => ("[0:v:0]", "[1:v:0]"),
+    };
+    let mut filters = format!(
+        "{old_pad}{new_pad}blend=all_mode=difference,signalstats,\
+         metadata=mode=print:file={stats},\
+         lutyuv=y=val*4:u=128:v=128[diff]",
+        stats = stats_name
+    );
+    let measurement = Measurement {
+        name: "diff",
+        vmaf: args.metrics && has_libvmaf(),
+    };
+    if args.metrics {
+        // The old output is the reference the new one is measured against
+        filters.push_str(&format!(
+            "; [0:v:0]split[old][reference]; [1:v:0]split[new][distorted]; {},nullsink",
+            measurement.filters("[reference]", "[distorted]")
+        ));
+    }
+    let status = Command::new("ffmpeg")
+        .current_dir(&stats_dir)
+        .args(["-v", "error", "-i"])
+        .arg(std::env::current_dir()?.join(&args.old))
+        .arg("-i")
+        .arg(std::env::current_dir()?.join(&args.new))
+        .arg("-filter_complex")
+        .arg(filters)
+        .args(["-map", "[diff]", "-an", "-y"])
+        .arg(&visualization)
+        .status()?;
+    let stats = std::fs::read_to_string(stats_dir.join(&stats_name)).unwrap_or_default();
+    let _ = std::fs::remove_file(stats_dir.join(&stats_name));
+    let metrics = args.metrics.then(|| measurement.read(&stats_dir));
+    if !status.success() {
+        return Err(format!("ffmpeg failed to compare the outputs ({})", status).into());
+    }
+
+    let frames = parse_stats(&stats);
+    let worst = frames
+        .iter()
+        .max_by_key(|frame| frame.max)
+        .filter(|frame| frame.max > 0);
+    let mut first_divergence = frames
+        .iter()
+        .find(|frame| frame.max > args.threshold)
+        .map(|frame| frame.time);
+    // Outputs of different lengths diverge where the shorter one ends at the latest
+    if (old.duration - new.duration).abs() > 0.001 && first_divergence.is_none() {
+        first_divergence = Some(old.duration.min(new.duration));
+    }
+
+    let report = Report {
+        old: args.old.clone(),
+        new: args.new.clone(),
+        visualization: args.output.clone(),
+        old_duration: old.duration,
+        new_duration: new.duration,
+        frames: frames.len(),
+        mean_difference: match frames.len() {
+            0 => 0.0,
+            count => frames.iter().map(|frame| frame.mean).sum::<f64>() / count as f64,
+        },
+        max_difference: worst.map_or(0, |frame| frame.max),
+        max_difference_at: worst.map(|frame| frame.time),
+        first_divergence,
+        metrics,
+        assertions: Vec::new(),
+    };
+    let outcomes = assertion::check_all(&args.fail_on, &report)?;
+    let report = Report {
+        assertions: outcomes,
+        ..report
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    // Assertions take over from divergence, as outputs of another encoder never match exactly
+    if !args.fail_on.is_empty() {
+        return assertion::verdict(&report.assertions);
+    }
+    match first_divergence {
+        Some(time) => Err(format!("The outputs diverge {:.3} seconds in", time).into()),
+        None => Ok(()),
+    }
+}
+
+/// Measures the quality of `distorted` against `reference` over the first `duration` seconds of
+/// both, or all of them
+pub fn measure(
+    reference: &Path,
+    distorted: &Path,
+    duration: Option<f64>,
+) -> Result<Metrics, Box<dyn Error>> {
+    measure_from((reference, 0.0), (distorted, 0.0), duration)
+}
+
+/// Measures the quality of every video file cell against the file of cell `reference`, counted
+/// from 1, over the part of them the grid shows, at most `duration` seconds. `None` for the
+/// reference and cells that aren't video files
+pub fn measure_cells(
+    cells: &[Cell],
+    reference: usize,
+    duration: Option<f64>,
+) -> Result<Vec<Option<Metrics>>, Box<dyn Error>> {
+    let reference_cell = &cells[reference - 1];
+    let reference_path = reference_cell.source.as_file().ok_or_else(|| {
+        format!(
+            "Cell {} is not a video file to measure the others against",
+            reference
+        )
+    })?;
+    let start = |cell: &Cell| cell.start.unwrap_or(0.0);
+    let length = |cell: &Cell| cell.end.map(|end| end - start(cell));
+    let mut metrics = Vec::new();
+    for (index, cell) in cells.iter().enumerate() {
+        let path = match cell.source.as_file() {
+            Some(path) if index != reference - 1 => path,
+            _ => {
+                metrics.push(None);
+                continue;
+            }
+        };
+        let duration = [duration, length(reference_cell), length(cell)]
+            .into_iter()
+            .flatten()
+            .reduce(f64::min);
+        metrics.push(Some(measure_from(
+            (reference_path, start(reference_cell)),
+            (path, start(cell)),
+            duration,
+        )?));
+    }
+    Ok(metrics)
+}
+
+/// Measures the quality of `distorted` against `reference`, each a file and the second it is
+/// read from, over the next `duration` seconds of both, or all of them
+fn measure_from(
+    (reference, reference_start): (&Path, f64),
+    (distorted, distorted_start): (&Path, f64),
+    duration: Option<f64>,
+) -> Result<Metrics, Box<dyn Error>> {
+    // Written relative to the directory ffmpeg runs in, like the statistics of `run`
+    let stats_dir = std::env::temp_dir();
+    let measurement = Measurement {
+        name: "measure",
+        vmaf: has_libvmaf(),
+    };
+    let filters = measurement.filters("[1:v:0]", "[0:v:0]");
+    let mut command = Command::new("ffmpeg");
+    command.current_dir(&stats_dir).args(["-v", "error"]);
+    for (input, start) in [(distorted, distorted_start), (reference, reference_start)] {
+        if start > 0.0 {
+            command.arg("-ss").arg(start.to_string());
+        }
+        if let Some(duration) = duration {
+            command.arg("-t").arg(duration.to_string());
+        }
+        command.arg("-i").arg(std::env::current_dir()?.join(input));
+    }
+    let status = command
+        .arg("-lavfi")
+        .arg(filters)
+        .args(["-f", "null", "-"])
+        .status()?;
+    let metrics = measurement.read(&stats_dir);
+    if !status.success() {
+        return Err(format!(
+            "ffmpeg failed to measure the quality of {} ({})",
+            distorted.display(),
+            status
+        )
+        .into());
+    }
+    Ok(metrics)
+}
+
+/// The full-reference metrics of one ffmpeg run, written as statistics files to the directory
+/// ffmpeg runs in
+struct Measurement {
+    /// What the statistics files are named after, so runs of different commands don't mix
+    name: &'static str,
+    /// Whether VMAF is measured as well as PSNR and SSIM
+    vmaf: bool,
+}
+
+impl Measurement {
+    fn stats_name(&self, kind: &str) -> String {
+        format!("vidgrid_{}_{}_{}.txt", self.name, kind, std::process::id())
+    }
+
+    /// The filters measuring the video of the `distorted` pad against that of the `reference`
+    /// pad, which both are used up. Their last output is left unconnected
+    fn filters(&self, reference: &str, distorted: &str) -> String {
+        let (references, vmaf_filter) = match self.vmaf {
+            true => (
+                "split=3[psnrref][ssimref][vmafref]",
+                format!(
+                    "[ssimed]; [ssimed][vmafref]libvmaf=log_fmt=json:log_path={}",
+                    self.stats_name("vmaf")
+                ),
+            ),
+            false => ("split[psnrref][ssimref]", String::new()),
+        };
+        format!(
+            "{reference}{references}; \
+             {distorted}[psnrref]psnr=stats_file={psnr}[psnred]; \
+             [psnred][ssimref]ssim=stats_file={ssim}{vmaf_filter}",
+            psnr = self.stats_name("psnr"),
+            ssim = self.stats_name("ssim"),
+        )
+    }
+
+    /// Reads the metrics from the statistics ffmpeg wrote to `dir`, removing them
+    fn read(&self, dir: &Path) -> Metrics {
+        let read_stats = |kind: &str| {
+            let path = dir.join(self.stats_name(kind));
+            let stats = std::fs::read_to_string(&path).unwrap_or_default();
+            let _ = std::fs::remove_file(&path);
+            stats
+        };
+        Metrics {
+            psnr: parse_psnr(&read_stats("psnr")),
+            ssim: average_field(&read_stats("ssim"), "All:"),
+            vmaf: self.vmaf.then(|| parse_vmaf(&read_stats("vmaf"))).flatten(),
+        }
+    }
+}
+
+/// Whether ffmpeg is built with libvmaf
+fn has_libvmaf() -> bool {
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-filters"])
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.split_whitespace().nth(1) == Some("libvmaf"))
+        })
+}
+
+/// The average of the `<field><number>` values of the lines of a `psnr` or `ssim` stats file
+fn average_field(stats: &str, field: &str) -> f64 {
+    let values: Vec<f64> = stats
+        .lines()
+        .filter_map(|line| {
+            line.split_whitespace()
+                .find_map(|word| word.strip_prefix(field)?.parse().ok())
+        })
+        .collect();
+    match values.len() {
+        0 => 0.0,
+        count => values.iter().sum::<f64>() / count as f64,
+    }
+}
+
+/// The PSNR of the average squared error over all frames, as ffmpeg sums it up
+fn parse_psnr(stats: &str) -> f64 {
+    let mse = average_field(stats, "mse_avg:");
+    match mse {
+        0.0 => IDENTICAL_PSNR,
+        mse => (10.0 * (255.0 * 255.0 / mse).log10()).min(IDENTICAL_PSNR),
+    }
+}
+
+/// The mean VMAF score from the JSON log of `libvmaf`
+fn parse_vmaf(log: &str) -> Option<f64> {
+    let log: serde_json::Value = serde_json::from_str(log).ok()?;
+    log["pooled_metrics"]["vmaf"]["mean"].as_f64()
+}
+
+/// Reads the per-frame statistics the `metadata` filter printed for the difference of the outputs
+fn parse_stats(stats: &str) -> Vec<FrameDifference> {
+    let mut frames: Vec<FrameDifference> = Vec::new();
+    for line in stats.lines() {
+        if line.starts_with("frame:") {
+            let time = line
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("pts_time:"))
+                .and_then(|time| time.parse().ok())
+                .unwrap_or_default();
+            frames.push(FrameDifference {
+                time,
+                mean: 0.0,
+                max: 0,
+            });
+            continue;
+        }
+        let (Some(frame), Some((key, value))) = (frames.last_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<f64>() else {
+            continue;
+        };
+        match key.trim_start_matches("lavfi.signalstats.") {
+            "YAVG" => frame.mean = value,
+            // Chroma differences are measured in the same planes, so any of them counts
+            "YMAX" | "UMAX" | "VMAX" => frame.max = frame.max.max(value as u8),
+            _ => {}
+        }
+    }
+    frames
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files picked up from a directory when no pattern is given
+const VIDEO_EXTENSIONS: [&str; 9] = [
+    "mp4", "m4v", "mov", "mkv", "webm", "avi", "mts", "m2ts", "ts",
+];
+
+/// Lists the videos directly inside `dir`, sorted by name. When `pattern` is given, files whose
+/// name matches it are listed instead, whatever their extension.
+pub fn find_videos(dir: &Path, pattern: Option<&str>) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+
+    let mut videos = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let found = match pattern {
+            Some(pattern) => glob_match(pattern, &name),
+            None => path.extension().is_some_and(|ext| {
+                VIDEO_EXTENSIONS
+                    .iter()
+                    .any(|video| ext.eq_ignore_ascii_case(video))
+            }),
+        };
+        if found {
+            videos.push(path);
+        }
+    }
+
+    if videos.is_empty() {
+        return Err(format!("No videos found in {}", dir.display()).into());
+    }
+    videos.sort();
+    Ok(videos)
+}
+
+/// Matches a file name against a pattern in which `*` stands for any run of characters and `?`
+/// for any single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Where to resume after the most recent `*` if the rest fails to match: the position after
+    // it in the pattern, and how much of the name it has swallowed
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, swallowed)) => {
+                    star = Some((after, swallowed + 1));
+                    p = after;
+                    n = swallowed + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
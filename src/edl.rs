@@ -0,0 +1,237 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use vidgrid::get_video_framerate;
+use vidgrid::time::timecode_frames;
+
+/// A single clip of an edit decision list, used as the source of one cell
+#[derive(Debug, Clone)]
+pub struct EdlClip {
+    /// The media file the clip is taken from
+    pub path: PathBuf,
+    /// In point within the media file, in seconds
+    pub start: f64,
+    /// Out point within the media file, in seconds
+    pub end: f64,
+}
+
+/// Reads the video clips of an edit decision list.
+///
+/// Both CMX3600 `.edl` files and OpenTimelineIO `.otio` files are supported. Clip paths are
+/// resolved relative to the directory containing the list.
+pub fn read_edl(edl_path: &Path) -> Result<Vec<EdlClip>, Box<dyn Error>> {
+    let contents = fs::read_to_string(edl_path)?;
+    let base_dir = edl_path.parent().unwrap_or(Path::new(""));
+
+    let is_otio = edl_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("otio"));
+    if is_otio {
+        parse_otio(&contents, base_dir)
+    } else {
+        parse_cmx3600(&contents, base_dir)
+    }
+}
+
+/// Parses the video events of a CMX3600 EDL.
+///
+/// Source timecodes are converted to seconds using the frame rate of the clip they refer to.
+fn parse_cmx3600(contents: &str, base_dir: &Path) -> Result<Vec<EdlClip>, Box<dyn Error>> {
+    // (reel, source in, source out, clip name)
+    let mut events: Vec<(String, String, String, Option<String>)> = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+
+        // Clip names are given by comments following the event they belong to
+        if let Some(comment) = line.strip_prefix('*') {
+            let comment = comment.trim();
+            let name = comment
+                .strip_prefix("FROM CLIP NAME:")
+                .or_else(|| comment.strip_prefix("SOURCE FILE:"));
+            if let (Some(name), Some(event)) = (name, events.last_mut()) {
+                event.3 = Some(name.trim().to_string());
+            }
+            continue;
+        }
+
+        // 001  AX  V  C  <src in> <src out> <rec in> <rec out>
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let is_event = tokens.len() >= 8 && tokens[0].chars().all(|c| c.is_ascii_digit());
+        if !is_event || !tokens[2].contains('V') {
+            continue;
+        }
+        let n = tokens.len();
+        events.push((
+            tokens[1].to_string(),
+            tokens[n - 4].to_string(),
+            tokens[n - 3].to_string(),
+            None,
+        ));
+    }
+
+    let mut clips = Vec::new();
+    for (reel, start, end, name) in events {
+        let path = base_dir.join(name.unwrap_or(reel));
+        let fps = get_video_framerate(&path, 0)?;
+        clips.push(EdlClip {
+            start: parse_timecode(&start, fps)?,
+            end: parse_timecode(&end, fps)?,
+            path,
+        });
+    }
+
+    Ok(clips)
+}
+
+/// Converts a `hh:mm:ss:ff` timecode to seconds at the given frame rate
+fn parse_timecode(timecode: &str, fps: f64) -> Result<f64, Box<dyn Error>> {
+    Ok(timecode_frames(timecode, fps)? as f64 / fps)
+}
+
+/// Parses the clips of the video tracks of an OpenTimelineIO timeline
+fn parse_otio(contents: &str, base_dir: &Path) -> Result<Vec<EdlClip>, Box<dyn Error>> {
+    let timeline: serde_json::Value = serde_json::from_str(contents)?;
+
+    let tracks = timeline["tracks"]["children"]
+        .as_array()
+        .ok_or("OTIO timeline has no tracks")?;
+
+    let mut clips = Vec::new();
+    for track in tracks {
+        if track["kind"].as_str().is_some_and(|kind| kind != "Video") {
+            continue;
+        }
+
+        for item in track["children"].as_array().into_iter().flatten() {
+            let is_clip = item["OTIO_SCHEMA"]
+                .as_str()
+                .is_some_and(|schema| schema.starts_with("Clip."));
+            if !is_clip {
+                continue;
+            }
+
+            let url = item["media_reference"]["target_url"]
+                .as_str()
+                .ok_or("OTIO clip has no media reference")?;
+            let path = base_dir.join(url.strip_prefix("file://").unwrap_or(url));
+
+            let range = &item["source_range"];
+            let start = rational_time_seconds(&range["start_time"])?;
+            let duration = rational_time_seconds(&range["duration"])?;
+
+            clips.push(EdlClip {
+                path,
+                start,
+                end: start + duration,
+            });
+        }
+    }
+
+    Ok(clips)
+}
+
+/// Converts an OTIO `RationalTime` to seconds
+fn rational_time_seconds(time: &serde_json::Value) -> Result<f64, Box<dyn Error>> {
+    let value = time["value"].as_f64().ok_or("OTIO time has no value")?;
+    let rate = time["rate"].as_f64().ok_or("OTIO time has no rate")?;
+    if rate == 0.0 {
+        return Err("OTIO time has a rate of 0".into());
+    }
+    Ok(value / rate)
+}
+
+/// Writes clips as an OpenTimelineIO timeline with a single video track, readable by `read_edl`
+pub fn write_otio(clips: &[EdlClip], path: &Path) -> Result<(), Box<dyn Error>> {
+    let rational_time = |seconds: f64| {
+        serde_json::json!({
+            "OTIO_SCHEMA": "RationalTime.1",
+            "value": seconds,
+            "rate": 1.0,
+        })
+    };
+
+    let children: Vec<serde_json::Value> = clips
+        .iter()
+        .map(|clip| {
+            serde_json::json!({
+                "OTIO_SCHEMA": "Clip.2",
+                "name": clip.path.file_name().map(|name| name.to_string_lossy()),
+                "media_reference": {
+                    "OTIO_SCHEMA": "ExternalReference.1",
+                    "target_url": clip.path,
+                },
+                "source_range": {
+                    "OTIO_SCHEMA": "TimeRange.1",
+                    "start_time": rational_time(clip.start),
+                    "duration": rational_time(clip.end - clip.start),
+                },
+            })
+        })
+        .collect();
+
+    let timeline = serde_json::json!({
+        "OTIO_SCHEMA": "Timeline.1",
+        "name": "vidgrid picks",
+        "tracks": {
+            "OTIO_SCHEMA": "Stack.1",
+            "children": [{
+                "OTIO_SCHEMA": "Track.1",
+                "kind": "Video",
+                "children": children,
+            }],
+        },
+    });
+
+    fs::write(path, serde_json::to_string_pretty(&timeline)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_timecode;
+
+    const NTSC: f64 = 30000.0 / 1001.0;
+
+    fn assert_seconds(timecode: &str, fps: f64, seconds: f64) {
+        let parsed = parse_timecode(timecode, fps).unwrap();
+        assert!(
+            (parsed - seconds).abs() < 1e-6,
+            "{} at {} fps is {}, not {}",
+            timecode,
+            fps,
+            parsed,
+            seconds
+        );
+    }
+
+    #[test]
+    fn non_drop_frame() {
+        assert_seconds("00:00:01:00", NTSC, 30.0 / NTSC);
+        assert_seconds("00:01:00:15", NTSC, 1815.0 / NTSC);
+        assert_seconds("01:00:00:00", NTSC, 108_000.0 / NTSC);
+    }
+
+    #[test]
+    fn drop_frame() {
+        // 00:00:59;29 is followed by 00:01:00;02
+        assert_seconds("00:00:59;29", NTSC, 1799.0 / NTSC);
+        assert_seconds("00:01:00;02", NTSC, 1800.0 / NTSC);
+        // No frames are dropped in tenth minutes
+        assert_seconds("00:10:00;00", NTSC, 17_982.0 / NTSC);
+        assert_seconds("01:00:00;00", NTSC, 107_892.0 / NTSC);
+    }
+
+    #[test]
+    fn pal() {
+        assert_seconds("00:00:01:00", 25.0, 1.0);
+        assert_seconds("01:00:00:12", 25.0, 3600.48);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse_timecode("00:00:01", 25.0).is_err());
+        assert!(parse_timecode("00:00:aa:00", 25.0).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+#[cfg(feature = "render")]
+use std::process::Command;
+#[cfg(feature = "render")]
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+
+use crate::VidGridError;
+
+/// A video codec the output can be encoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum VideoCodec {
+    /// H.264, playable nearly everywhere
+    #[value(name = "libx264", alias = "h264")]
+    H264,
+    /// H.265/HEVC, smaller files at the same quality
+    #[value(name = "libx265", alias = "h265", alias = "hevc")]
+    H265,
+    Vp9,
+    Av1,
+}
+
+/// Hardware whose encoder is used instead of the software one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HwAccel {
+    /// NVIDIA NVENC
+    Nvenc,
+    /// VA-API on Linux, e.g. Intel and AMD GPUs
+    Vaapi,
+    /// Apple VideoToolbox
+    Videotoolbox,
+    /// Intel Quick Sync Video
+    Qsv,
+}
+
+/// The device VA-API encoders upload frames to
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+/// How the output video is encoded. Anything not given is left to ffmpeg's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct EncoderOptions {
+    /// The codec of the output. H.264 when other options are given without one
+    pub codec: Option<VideoCodec>,
+    /// Constant quality, on the scale of the encoder's own quality option. Lower is better
+    pub crf: Option<u32>,
+    /// The encoder's speed/quality preset, e.g. `slow` or `p7`
+    pub preset: Option<String>,
+    /// The pixel format of the output, e.g. `yuv420p10le`
+    pub pix_fmt: Option<String>,
+    /// Encode on this hardware
+    pub hwaccel: Option<HwAccel>,
+    /// Encode for the least delay between a frame being rendered and shown, for streams watched
+    /// live: without B-frames, with a keyframe every second and with no lookahead
+    pub low_latency: bool,
+}
+
+impl EncoderOptions {
+    /// Whether any option differs from ffmpeg's defaults
+    fn is_default(&self) -> bool {
+        self.codec.is_none()
+            && self.crf.is_none()
+            && self.preset.is_none()
+            && self.pix_fmt.is_none()
+            && self.hwaccel.is_none()
+            && !self.low_latency
+    }
+
+    /// The name of the ffmpeg encoder these options select
+    pub fn encoder_name(&self) -> Result<&'static str, VidGridError> {
+        let codec = self.codec.unwrap_or(VideoCodec::H264);
+        let name = match (self.hwaccel, codec) {
+            (None, VideoCodec::H264) => Some("libx264"),
+            (None, VideoCodec::H265) => Some("libx265"),
+            (None, VideoCodec::Vp9) => Some("libvpx-vp9"),
+            (None, VideoCodec::Av1) => Some("libaom-av1"),
+            (Some(HwAccel::Nvenc), VideoCodec::H264) => Some("h264_nvenc"),
+            (Some(HwAccel::Nvenc), VideoCodec::H265) => Some("hevc_nvenc"),
+            (Some(HwAccel::Nvenc), VideoCodec::Av1) => Some("av1_nvenc"),
+            (Some(HwAccel::Vaapi), VideoCodec::H264) => Some("h264_vaapi"),
+            (Some(HwAccel::Vaapi), VideoCodec::H265) => Some("hevc_vaapi"),
+            (Some(HwAccel::Vaapi), VideoCodec::Vp9) => Some("vp9_vaapi"),
+            (Some(HwAccel::Vaapi), VideoCodec::Av1) => Some("av1_vaapi"),
+            (Some(HwAccel::Videotoolbox), VideoCodec::H264) => Some("h264_videotoolbox"),
+            (Some(HwAccel::Videotoolbox), VideoCodec::H265) => Some("hevc_videotoolbox"),
+            (Some(HwAccel::Qsv), VideoCodec::H264) => Some("h264_qsv"),
+            (Some(HwAccel::Qsv), VideoCodec::H265) => Some("hevc_qsv"),
+            (Some(HwAccel::Qsv), VideoCodec::Vp9) => Some("vp9_qsv"),
+            (Some(HwAccel::Qsv), VideoCodec::Av1) => Some("av1_qsv"),
+            _ => None,
+        };
+        name.ok_or_else(|| {
+            let hwaccel = self.hwaccel.expect("software encoders cover every codec");
+            VidGridError::InvalidGrid(format!(
+                "There is no {} encoder for {}",
+                value_name(&hwaccel),
+                value_name(&codec)
+            ))
+        })
+    }
+
+    /// Global options ffmpeg needs before its inputs to use the hardware encoder
+    pub fn global_args(&self) -> Vec<String> {
+        match self.hwaccel {
+            Some(HwAccel::Vaapi) => vec!["-vaapi_device".to_string(), VAAPI_DEVICE.to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The filter handing finished frames to the hardware encoder, if it needs one
+    pub fn upload_filter(&self) -> Option<String> {
+        match self.hwaccel {
+            // VA-API encoders only take frames in GPU memory
+            Some(HwAccel::Vaapi) => Some(format!(
+                "format={},hwupload",
+                self.pix_fmt.as_deref().unwrap_or("nv12")
+            )),
+            _ => None,
+        }
+    }
+
+    /// Checks that the options can be combined, without asking ffmpeg which encoders it has
+    pub fn check(&self) -> Result<(), VidGridError> {
+        let encoder = self.encoder_name()?;
+        let has_presets = !matches!(self.hwaccel, Some(HwAccel::Vaapi | HwAccel::Videotoolbox))
+            && !matches!(encoder, "libvpx-vp9" | "libaom-av1");
+        if self.preset.is_some() && !has_presets {
+            return Err(VidGridError::InvalidGrid(format!(
+                "The {} encoder has no presets",
+                encoder
+            )));
+        }
+        Ok(())
+    }
+
+    /// Output options selecting and configuring the encoder. Checks that the encoder is built
+    /// into ffmpeg.
+    #[cfg(feature = "render")]
+    pub fn output_args(&self) -> Result<Vec<String>, VidGridError> {
+        self.check_available()?;
+        self.planned_args()
+    }
+
+    /// Checks that the ffmpeg on the `PATH` has the encoder
+    #[cfg(feature = "render")]
+    pub fn check_available(&self) -> Result<(), VidGridError> {
+        if !self.is_default() {
+            let encoder = self.encoder_name()?;
+            if !available_encoders()?.iter().any(|name| name == encoder) {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "This ffmpeg has no {} encoder",
+                    encoder
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Output options selecting and configuring the encoder, without asking ffmpeg whether it
+    /// has it, e.g. to show the command a grid would be rendered with
+    pub fn planned_args(&self) -> Result<Vec<String>, VidGridError> {
+        if self.is_default() {
+            return Ok(Vec::new());
+        }
+
+        self.check()?;
+        let encoder = self.encoder_name()?;
+        let mut args = vec!["-c:v".to_string(), encoder.to_string()];
+
+        // Every encoder names its constant quality mode differently
+        if let Some(crf) = self.crf {
+            let quality: &[&str] = match encoder {
+                "libx264" | "libx265" => &["-crf"],
+                "libvpx-vp9" | "libaom-av1" => &["-b:v", "0", "-crf"],
+                "h264_nvenc" | "hevc_nvenc" | "av1_nvenc" => &["-rc", "vbr", "-b:v", "0", "-cq"],
+                "h264_videotoolbox" | "hevc_videotoolbox" => &["-q:v"],
+                "h264_qsv" | "hevc_qsv" | "vp9_qsv" | "av1_qsv" => &["-global_quality"],
+                _ => &["-rc_mode", "CQP", "-qp"],
+            };
+            args.extend(quality.iter().map(|arg| arg.to_string()));
+            args.push(crf.to_string());
+        }
+
+        if let Some(preset) = &self.preset {
+            args.extend(["-preset".to_string(), preset.clone()]);
+        }
+
+        // A viewer joining the stream waits for the next keyframe, and B-frames hold frames back
+        // until the frames they refer to are encoded
+        if self.low_latency {
+            if matches!(encoder, "libx264" | "libx265") {
+                args.extend(["-tune".to_string(), "zerolatency".to_string()]);
+            }
+            args.extend(
+                ["-bf", "0", "-force_key_frames", "expr:gte(t,n_forced)"].map(String::from),
+            );
+        }
+
+        // VA-API frames get their format as they are uploaded
+        if let (Some(pix_fmt), false) = (&self.pix_fmt, self.hwaccel == Some(HwAccel::Vaapi)) {
+            args.extend(["-pix_fmt".to_string(), pix_fmt.clone()]);
+        }
+
+        Ok(args)
+    }
+}
+
+/// The name a value is given by on the command line
+pub(crate) fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// The names of the video encoders built into ffmpeg, asked for once per process
+#[cfg(feature = "render")]
+fn available_encoders() -> Result<&'static [String], VidGridError> {
+    static ENCODERS: OnceLock<Vec<String>> = OnceLock::new();
+    if let Some(encoders) = ENCODERS.get() {
+        return Ok(encoders);
+    }
+
+    let output = Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-encoders")
+        .output()?;
+    // Encoders are listed as ` V....D libx264   libx264 H.264 ...` after a legend
+    let encoders = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let flags = words.next()?;
+            let name = words.next()?;
+            (flags.starts_with('V') && flags.len() == 6 && name != "=").then(|| name.to_string())
+        })
+        .collect();
+    Ok(ENCODERS.get_or_init(|| encoders))
+}
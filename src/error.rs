@@ -0,0 +1,83 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+#[cfg(feature = "render")]
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// Everything that can go wrong while creating a grid
+#[derive(Debug)]
+pub enum VidGridError {
+    /// ffprobe could not be run, or could not make sense of a file
+    Ffprobe { path: PathBuf, message: String },
+    /// A value could not be parsed, e.g. a frame rate reported by ffprobe or a line of a data file
+    Parse(String),
+    /// ffmpeg exited unsuccessfully while doing `task`
+    #[cfg(feature = "render")]
+    Ffmpeg { task: String, status: ExitStatus },
+    /// ffmpeg was stopped after running for `after` while doing `task`
+    TimedOut { task: String, after: Duration },
+    /// The grid cannot be built as described, e.g. because a cell refers to a missing cell
+    InvalidGrid(String),
+    /// The output would exceed the limits of the settings. Holds what it would cost to render
+    LimitExceeded(String),
+    /// Reading or writing a file failed, or ffmpeg could not be run
+    Io(io::Error),
+}
+
+impl fmt::Display for VidGridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VidGridError::Ffprobe { path, message } => {
+                write!(f, "ffprobe failed for {}: {}", path.display(), message)
+            }
+            VidGridError::Parse(message) => write!(f, "{}", message),
+            #[cfg(feature = "render")]
+            VidGridError::Ffmpeg { task, status } => {
+                write!(f, "ffmpeg failed to {} ({})", task, status)
+            }
+            VidGridError::TimedOut { task, after } => write!(
+                f,
+                "ffmpeg timed out after {} seconds trying to {}",
+                after.as_secs_f64(),
+                task
+            ),
+            VidGridError::InvalidGrid(message) => write!(f, "{}", message),
+            VidGridError::LimitExceeded(message) => write!(f, "{}", message),
+            VidGridError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl VidGridError {
+    /// Exit status of vidgrid when an input could not be probed
+    pub const EXIT_PROBE_FAILED: u8 = 3;
+
+    /// Exit status of vidgrid when ffmpeg was stopped for running too long
+    pub const EXIT_TIMED_OUT: u8 = 4;
+
+    /// The exit status of vidgrid failing with this error, so scripts running it can tell probing
+    /// failures and time-outs apart from other failures
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            VidGridError::Ffprobe { .. } => VidGridError::EXIT_PROBE_FAILED,
+            VidGridError::TimedOut { .. } => VidGridError::EXIT_TIMED_OUT,
+            _ => 1,
+        }
+    }
+}
+
+impl std::error::Error for VidGridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VidGridError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VidGridError {
+    fn from(error: io::Error) -> Self {
+        VidGridError::Io(error)
+    }
+}
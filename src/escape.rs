@@ -0,0 +1,39 @@
+/// Escapes every character in `special` with a backslash
+fn backslash_escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes a value so it can be used as a filter option inside a filtergraph.
+///
+/// ffmpeg unescapes filtergraphs twice: once when splitting the graph into filters and once
+/// when splitting a filter's arguments into options, so both levels are escaped here.
+pub fn escape_filter_option(value: &str) -> String {
+    let option = backslash_escape(value, &['\\', '\'', ':']);
+    backslash_escape(&option, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// Escapes text for the `text` option of the `drawtext` filter, which additionally expands
+/// `%{...}` sequences
+pub fn escape_drawtext(text: &str) -> String {
+    escape_filter_option(&backslash_escape(text, &['\\', '%']))
+}
+
+/// Quotes a word for a POSIX shell, unless it is safe as it is
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
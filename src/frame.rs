@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::Path;
+
+use crate::VidGridError;
+
+/// A single frame of a grid, as 8-bit RGBA pixels in rows from the top left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaFrame {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height * 4` bytes, red first
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaFrame {
+    /// The red, green, blue and alpha values of a pixel
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let at = (y as usize * self.width as usize + x as usize) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[at..at + 4]);
+        pixel
+    }
+}
+
+/// Reads an uncompressed 24 or 32-bit BMP file, which is what ffmpeg writes stills to `.bmp` as
+pub(crate) fn read_bmp(path: &Path) -> Result<RgbaFrame, VidGridError> {
+    let invalid = || VidGridError::Parse(format!("{} is not a BMP ffmpeg writes", path.display()));
+    let data = fs::read(path)?;
+    let u32_at = |at: usize| -> Result<u32, VidGridError> {
+        let bytes = data.get(at..at + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("four bytes")))
+    };
+
+    // The file and info headers take 54 bytes
+    if !data.starts_with(b"BM") || data.len() < 54 {
+        return Err(invalid());
+    }
+    let offset = u32_at(10)? as usize;
+    let width = u32_at(18)?;
+    // Rows are stored from the bottom up unless the height is negative
+    let height = u32_at(22)? as i32;
+    let bits = u16::from_le_bytes([data[28], data[29]]);
+    let compression = u32_at(30)?;
+    let bytes_per_pixel = match (bits, compression) {
+        (24, 0) => 3,
+        (32, 0 | 3) => 4,
+        _ => return Err(invalid()),
+    };
+
+    let (width_px, height_px) = (width as usize, height.unsigned_abs() as usize);
+    // Every row is padded to a multiple of four bytes
+    let stride = (width_px * bytes_per_pixel).div_ceil(4) * 4;
+    let mut pixels = Vec::with_capacity(width_px * height_px * 4);
+    for row in 0..height_px {
+        let stored_row = if height > 0 { height_px - 1 - row } else { row };
+        let start = offset + stored_row * stride;
+        let row = data
+            .get(start..start + width_px * bytes_per_pixel)
+            .ok_or_else(invalid)?;
+        for bgr in row.chunks_exact(bytes_per_pixel) {
+            let alpha = if bytes_per_pixel == 4 { bgr[3] } else { 255 };
+            pixels.extend([bgr[2], bgr[1], bgr[0], alpha]);
+        }
+    }
+
+    Ok(RgbaFrame {
+        width,
+        height: height_px as u32,
+        pixels,
+    })
+}
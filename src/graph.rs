@@ -0,0 +1,1567 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::cell::{Cell, Fit, TimecodeSource};
+use crate::input::{InputSource, Visualizer};
+use crate::probe::MediaInfo;
+use crate::settings::{
+    AnimatedFormat, AudioMode, ColorRange, ColorSpace, GridSettings, Layout, PadMode, Segments,
+    ShortInputs, StillFrame, StreamTarget,
+};
+use crate::{encoder, escape, health, loudness, timeline, Adjustment, GridInfo, VidGridError};
+
+/// Returns the number of columns and rows of the most square grid that fits `cells` cells
+fn grid_dimensions(cells: usize) -> (usize, usize) {
+    let columns = (cells as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = cells.div_ceil(columns).max(1);
+    (columns, rows)
+}
+
+/// The part of the output a cell takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Slot {
+    /// Distance of the left edge from the left of the output
+    pub x: u32,
+    /// Distance of the top edge from the top of the output
+    pub y: u32,
+    /// Width of the slot, including the cell's border
+    pub width: u32,
+    /// Height of the slot, including the cell's border
+    pub height: u32,
+    /// Width of the video inside the cell's border
+    pub video_width: u32,
+    /// Height of the video inside the cell's border
+    pub video_height: u32,
+}
+
+/// Where the cells of a grid go and how large they are
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GridGeometry {
+    /// The number of columns of a layout of equal cells, which can be stacked row by row
+    pub columns: Option<usize>,
+    /// The slot of every cell in cell order, followed by those left empty
+    pub slots: Vec<Slot>,
+}
+
+/// Lays out a grid of `cells` cells as `settings` asks, failing if the layout can't hold them
+pub fn grid_geometry(cells: usize, settings: &GridSettings) -> Result<GridGeometry, VidGridError> {
+    let (width, height, gap) = (settings.width, settings.height, settings.gap);
+    let slot = |x: u32, y: u32, width: u32, height: u32| Slot {
+        x,
+        y,
+        width,
+        height,
+        video_width: width.saturating_sub(2 * settings.border),
+        video_height: height.saturating_sub(2 * settings.border),
+    };
+
+    let (columns, slots) = match settings.layout {
+        // Both cells of a comparison fill the output, to be combined into one picture
+        _ if settings.compare.is_some() => {
+            if cells != 2 {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "A comparison needs exactly 2 cells, not {}",
+                    cells
+                )));
+            }
+            (None, vec![slot(0, 0, width, height); 2])
+        }
+        // A single cell fills the output whatever the layout
+        Layout::Pip | Layout::MainPlusThumbs if cells <= 1 => {
+            (Some(1), vec![slot(0, 0, width, height)])
+        }
+        // Overlays are a quarter of the output's size, inset from its corners by the gap
+        Layout::Pip => {
+            if cells > 5 {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "A picture-in-picture layout fits 5 cells, not {}",
+                    cells
+                )));
+            }
+            let (inset_width, inset_height) = (width / 4, height / 4);
+            let margin = gap.max(height / 40);
+            let (left, top) = (margin, margin);
+            let right = width.saturating_sub(inset_width + margin);
+            let bottom = height.saturating_sub(inset_height + margin);
+            let corners = [(right, top), (right, bottom), (left, bottom), (left, top)];
+            let mut slots = vec![slot(0, 0, width, height)];
+            slots.extend(
+                corners[..cells - 1]
+                    .iter()
+                    .map(|&(x, y)| slot(x, y, inset_width, inset_height)),
+            );
+            (None, slots)
+        }
+        // The main cell takes two thirds of the width, the others share the rest of it
+        Layout::MainPlusThumbs => {
+            let thumbs = (cells - 1) as u32;
+            let main_width = width.saturating_sub(gap) * 2 / 3;
+            let thumb_x = main_width + gap;
+            let thumb_width = width.saturating_sub(thumb_x);
+            let thumb_height = height.saturating_sub(gap.saturating_mul(thumbs - 1)) / thumbs;
+            let mut slots = vec![slot(0, 0, main_width, height)];
+            slots.extend((0..thumbs).map(|thumb| {
+                slot(
+                    thumb_x,
+                    thumb * (thumb_height + gap),
+                    thumb_width,
+                    thumb_height,
+                )
+            }));
+            (None, slots)
+        }
+        Layout::Grid | Layout::StripH | Layout::StripV => {
+            if settings.columns == Some(0) || settings.rows == Some(0) {
+                return Err(VidGridError::InvalidGrid(
+                    "A grid needs at least 1 column and 1 row".to_string(),
+                ));
+            }
+            let (columns, rows) = match (settings.layout, settings.columns, settings.rows) {
+                (Layout::StripH, _, _) => (cells.max(1), 1),
+                (Layout::StripV, _, _) => (1, cells.max(1)),
+                (_, Some(columns), Some(rows)) => (columns, rows),
+                (_, Some(columns), None) => (columns, cells.div_ceil(columns)),
+                (_, None, Some(rows)) => (cells.div_ceil(rows), rows),
+                (_, None, None) => grid_dimensions(cells),
+            };
+            if columns * rows < cells {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "A {}x{} grid cannot fit {} cells",
+                    columns, rows, cells
+                )));
+            }
+            // Each cell takes an equal share of what the gaps leave
+            let gaps = |count: usize| gap.saturating_mul(count.saturating_sub(1) as u32);
+            let slot_width = width.saturating_sub(gaps(columns)) / columns as u32;
+            let slot_height = height.saturating_sub(gaps(rows)) / rows as u32;
+            let slots = (0..columns * rows)
+                .map(|index| {
+                    slot(
+                        (index % columns) as u32 * (slot_width + gap),
+                        (index / columns) as u32 * (slot_height + gap),
+                        slot_width,
+                        slot_height,
+                    )
+                })
+                .collect();
+            (Some(columns), slots)
+        }
+    };
+
+    // The input of a cell gets the part of its slot inside the border
+    if slots
+        .iter()
+        .any(|slot| slot.width <= 2 * settings.border || slot.height <= 2 * settings.border)
+    {
+        let layout = match (settings.layout, columns) {
+            (Layout::Grid, Some(columns)) => {
+                format!("a {}x{} grid", columns, slots.len() / columns)
+            }
+            _ => format!("the {} layout", encoder::value_name(&settings.layout)),
+        };
+        return Err(VidGridError::InvalidGrid(format!(
+            "Gaps of {} and borders of {} pixels leave no room for the cells of {}",
+            gap, settings.border, layout
+        )));
+    }
+
+    Ok(GridGeometry { columns, slots })
+}
+
+/// Stacks the labeled streams with `hstack` or `vstack`, passing a lone stream through as is
+fn stack_filter(stack: &str, inputs: &[String], output: &str) -> String {
+    let pads: String = inputs.iter().map(|label| format!("[{}]", label)).collect();
+    if inputs.len() == 1 {
+        format!("{}null[{}];", pads, output)
+    } else {
+        format!("{}{}=inputs={}[{}];", pads, stack, inputs.len(), output)
+    }
+}
+
+/// The subtitle codec the container of `output` carries text tracks in
+fn subtitle_codec(output: &Path) -> Result<String, VidGridError> {
+    let extension = output
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("mp4" | "m4v" | "mov") => Ok("mov_text".to_string()),
+        Some("mkv") => Ok("srt".to_string()),
+        Some("webm") => Ok("webvtt".to_string()),
+        _ => Err(VidGridError::InvalidGrid(format!(
+            "Captions can't be added as tracks to {}, only to MP4, MOV, MKV and WebM files",
+            output.display()
+        ))),
+    }
+}
+
+/// The filters of a filtergraph, one per line as they would be printed. Filters are separated by
+/// unescaped semicolons
+pub(crate) fn filtergraph_lines(filter_complex: &str) -> Vec<String> {
+    let mut filters = vec![String::new()];
+    for part in filter_complex.split("; ") {
+        let filter = filters.last_mut().expect("there is a filter");
+        filter.push_str(part);
+        if part.ends_with('\\') {
+            filter.push_str("; ");
+        } else {
+            filters.push(String::new());
+        }
+    }
+    filters
+        .iter()
+        .filter(|filter| !filter.is_empty())
+        .map(|filter| filter.trim_end_matches(';').to_string())
+        .collect()
+}
+
+/// Output options that write the settings' metadata tags into the container
+fn metadata_args(settings: &GridSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    for (key, value) in &settings.metadata {
+        args.push("-metadata".to_string());
+        args.push(format!("{}={}", key, value));
+    }
+
+    // MP4 and MOV only keep their standard tags unless told otherwise
+    let is_mov = settings.output_path.extension().is_some_and(|ext| {
+        ["mp4", "m4v", "mov"]
+            .iter()
+            .any(|mov| ext.eq_ignore_ascii_case(mov))
+    });
+    if is_mov && !settings.metadata.is_empty() {
+        args.push("-movflags".to_string());
+        args.push("+use_metadata_tags".to_string());
+    }
+
+    args
+}
+
+/// Placement and style of the badge drawn in the top-right corner of cells that don't
+/// contribute sound
+const MUTE_ICON: &str =
+    "x=w-text_w-h/40:y=h/40:fontsize=h/24:fontcolor=white:box=1:boxcolor=red@0.6:boxborderw=6";
+
+/// Placement and style of the live stats drawn in the top-right corner of cells, under the badge
+/// of muted cells
+const HEALTH_STATS: &str =
+    "x=w-text_w-h/40:y=h/10:fontsize=h/28:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=6";
+
+/// The filters covering a cell of `width` by `height` with a "NO SIGNAL" slate once its picture
+/// has stood still for `after` seconds at `fps`, and uncovering it as soon as the picture moves
+/// again, as the start of a chain continuing with the cell's effects. Pads inside it are named
+/// after `label`.
+///
+/// How much every frame differs from the one before is amplified and averaged down to a single
+/// pixel, which `lagfun` holds on to and lets fade away over `after` seconds. Once it has faded
+/// completely, that pixel blown up to the cell makes the slate opaque.
+fn no_signal_filter(
+    label: &str,
+    width: u32,
+    height: u32,
+    after: f64,
+    fps: f64,
+    font_option: &str,
+) -> String {
+    // Decays the largest difference of 255 below 1 in `after` seconds
+    let decay = (1.0 / 255.0_f64).powf(1.0 / (after * fps).max(1.0));
+    format!(
+        "split[{label}live][{label}probe]; \
+         [{label}probe]tblend=all_mode=difference,split[{label}diff][{label}card]; \
+         [{label}diff]format=gray,lut=c0={amplify},scale=32:18:flags=area,scale=1:1:flags=area,\
+         lagfun=decay={decay},lut=c0={still},scale={width}:{height}:flags=neighbor[{label}mask]; \
+         [{label}card]drawbox=c=black:t=fill,drawtext=text=NO SIGNAL{font_option}:{NO_SIGNAL}[{label}slate]; \
+         [{label}slate][{label}mask]alphamerge[{label}cover]; \
+         [{label}live][{label}cover]overlay=eof_action=pass,",
+        amplify = escape::escape_filter_option("min(val*64,255)"),
+        still = escape::escape_filter_option("if(eq(val,0),255,0)"),
+    )
+}
+
+/// Placement and style of the text of the slate covering cells whose picture stands still
+const NO_SIGNAL: &str = "x=(w-text_w)/2:y=(h-text_h)/2:fontsize=h/8:fontcolor=white";
+
+/// Output options coding and flagging the output as interlaced, if it is
+fn interlace_args(settings: &GridSettings) -> Vec<String> {
+    match settings.interlace {
+        Some(field_order) => [
+            "-flags",
+            "+ildct+ilme",
+            "-top",
+            field_order.top_field(),
+            "-field_order",
+            field_order.flag_name(),
+        ]
+        .map(String::from)
+        .to_vec(),
+        None => Vec::new(),
+    }
+}
+
+/// The cells of a still grid, each trimmed to start at the moment the still shows. Cells ending
+/// before then show their last frame. `file_durations` are those of the files of the file cells,
+/// in order.
+fn seek_cells(
+    cells: &[Cell],
+    file_durations: &[f64],
+    still: StillFrame,
+    duration: u32,
+) -> Vec<Cell> {
+    let mut file_durations = file_durations.iter();
+    let durations: Vec<Option<f64>> = cells
+        .iter()
+        .map(|cell| {
+            cell.source.as_file()?;
+            file_durations
+                .next()
+                .map(|&duration| cell.trimmed_duration(duration))
+        })
+        .collect();
+    let at = match still {
+        StillFrame::At(at) => at,
+        StillFrame::Middle => {
+            let longest = durations.iter().flatten().fold(0.0, |a: f64, &b| a.max(b));
+            longest.min(duration as f64) / 2.0
+        }
+    };
+
+    cells
+        .iter()
+        .zip(durations)
+        .map(|(cell, duration)| {
+            let mut cell = cell.clone();
+            if let Some(duration) = duration {
+                let offset = at.min((duration - 0.1).max(0.0));
+                cell.start = Some(cell.start.unwrap_or(0.0) + offset);
+            }
+            cell
+        })
+        .collect()
+}
+
+/// What building the command of a grid depends on beyond its cells and settings
+pub(crate) struct Inputs<'a> {
+    /// What is known of the video files of the file cells, in their order
+    pub media: &'a [MediaInfo],
+    /// The directory video files are pre-scaled into, when they are
+    pub scratch: Option<&'a Path>,
+    /// The directory the health monitor writes the live stats of the video files to, when they
+    /// are drawn
+    pub health_dir: Option<&'a Path>,
+    /// The directory the loudness of the heard cells is logged to, when it is measured
+    pub loudness_dir: Option<&'a Path>,
+    /// The file the output is encoded to before it is moved into place, when writes are atomic
+    pub partial_path: Option<PathBuf>,
+}
+
+/// A video file to shrink to the cell size before the grid is rendered
+#[cfg_attr(not(feature = "render"), allow(dead_code))]
+pub(crate) struct Prescale {
+    pub input: PathBuf,
+    pub stream: usize,
+    pub input_args: Vec<String>,
+    pub width: u32,
+    pub height: u32,
+    /// Where the shrunk video is written to, and read from by the grid
+    pub intermediate: PathBuf,
+}
+
+/// How ffmpeg renders a grid
+#[cfg_attr(not(feature = "render"), allow(dead_code))]
+pub(crate) struct GridCommand {
+    /// The arguments of ffmpeg, the program left out
+    pub args: Vec<String>,
+    /// The filtergraph among the arguments
+    pub filter_complex: String,
+    pub geometry: GridGeometry,
+    pub info: GridInfo,
+    /// Seconds of the grid rendered, also for stills
+    pub duration: u32,
+    /// The video files to pre-scale before ffmpeg is run
+    pub prescales: Vec<Prescale>,
+    /// The stream the output is sent to, if it isn't written to a file
+    pub stream: Option<StreamTarget>,
+    /// Where ffmpeg writes the output
+    pub encode_path: PathBuf,
+    /// The command publishing the output, which ffmpeg writes to the standard input of
+    pub gateway: Option<Vec<String>>,
+    /// The cells heard in the output, counted from 0
+    pub audio_cells: Vec<usize>,
+    /// The cells whose feeds' health is drawn, counted from 1
+    pub health_cells: Vec<usize>,
+}
+
+/// Works out the ffmpeg command rendering `cells` into a grid as `settings` ask. Nothing is run
+/// or written, so grids are planned with the same code they are rendered with
+pub(crate) fn build(
+    cells: &[Cell],
+    settings: &GridSettings,
+    inputs: &Inputs,
+) -> Result<GridCommand, VidGridError> {
+    // A still shows every cell at the same moment, so start every cell there and keep the first
+    // frame of the grid
+    let seeked;
+    let cells = match settings.still {
+        Some(still) => {
+            let durations: Vec<f64> = inputs.media.iter().map(|media| media.duration).collect();
+            seeked = seek_cells(cells, &durations, still, settings.duration);
+            &seeked[..]
+        }
+        None => cells,
+    };
+
+    // Stills and animations are written by the encoder of their format
+    let encoder_args = match (settings.still, settings.animation) {
+        (Some(_), _) => Vec::new(),
+        (None, Some(format)) => format.output_args(),
+        (None, None) => settings.encoder.planned_args()?,
+    };
+
+    // Map each file cell to its ffmpeg input index. Virtual cells reuse the streams of
+    // the file cells they refer to.
+    let mut file_paths = Vec::new();
+    let mut file_cells = Vec::new();
+    let mut input_indices = vec![None; cells.len()];
+    for (index, cell) in cells.iter().enumerate() {
+        if let Some(path) = cell.source.as_file() {
+            input_indices[index] = Some(file_paths.len());
+            file_paths.push(path.as_path());
+            file_cells.push(cell);
+        }
+    }
+    if file_paths.is_empty() {
+        return Err(VidGridError::InvalidGrid(
+            "At least one input must be a file".to_string(),
+        ));
+    }
+
+    // The ffmpeg input index of a video file cell
+    let file_input = |cell: usize| -> Result<usize, VidGridError> {
+        match (
+            cells.get(cell - 1).map(|cell| &cell.source),
+            input_indices.get(cell - 1),
+        ) {
+            (Some(InputSource::File(_)), Some(&Some(input))) => Ok(input),
+            _ => Err(VidGridError::InvalidGrid(format!(
+                "Cell {} is not a video file and cannot be referenced",
+                cell
+            ))),
+        }
+    };
+    // Step 1: Retrieve the Frame Rates of the Videos. Audio visualizers are drawn in RGB and
+    // have no frame rate, matrix or rotation of their own.
+    if inputs.media.len() != file_paths.len() {
+        return Err(VidGridError::InvalidGrid(format!(
+            "The grid reads {} files, but {} were described",
+            file_paths.len(),
+            inputs.media.len()
+        )));
+    }
+    let media = inputs.media;
+    let mut max_input_fps: f64 = 0.0;
+    let mut input_fps = vec![None; file_paths.len()];
+    let mut color_matrices = vec!["bt709"; file_paths.len()];
+    let mut full_ranges = vec![false; file_paths.len()];
+    let mut color_primaries = vec![None; file_paths.len()];
+    let mut rotations = vec![None; file_paths.len()];
+    let mut frame_sizes = vec![None; file_paths.len()];
+    for (input, (path, cell)) in file_paths.iter().zip(&file_cells).enumerate() {
+        if let InputSource::File(_) = cell.source {
+            let video = media[input].video_stream(path, cell.stream)?;
+            frame_sizes[input] = Some((video.width, video.height));
+            input_fps[input] = Some(video.fps);
+            max_input_fps = max_input_fps.max(video.fps);
+            color_matrices[input] = video.color_matrix;
+            full_ranges[input] = video.full_range;
+            color_primaries[input] = video.color_primaries;
+            rotations[input] = video.rotation;
+        }
+    }
+
+    // Cap the frame rate at the specified max_framerate. Grids of only audio inputs have no
+    // frame rate of their own and simply run at it.
+    if max_input_fps > settings.max_framerate || max_input_fps == 0.0 {
+        max_input_fps = settings.max_framerate;
+    }
+
+    // Step 2: Retrieve Durations of All Input Videos, accounting for their trims
+    let mut input_durations = Vec::new();
+    for (media, cell) in media.iter().zip(&file_cells) {
+        input_durations.push(cell.trimmed_duration(media.duration));
+    }
+    // The grid runs as long as its longest input, or its shortest when cutting short
+    let max_input_duration = match settings.short_inputs {
+        ShortInputs::Shortest => input_durations.iter().fold(f64::INFINITY, |a, &b| a.min(b)),
+        _ => input_durations.iter().fold(0.0, |a: f64, &b| a.max(b)),
+    };
+    let max_input_duration = max_input_duration.floor() as u32;
+
+    // Calculate the output duration: min(user_duration, max_input_duration)
+    let output_duration = if settings.duration < max_input_duration {
+        settings.duration
+    } else {
+        max_input_duration
+    };
+
+    // Refuse outputs past the limits before any work is done, saying what they would cost
+    if let Some(limits) = settings.limits {
+        // The timeline strip is stacked under the grid, making the frames taller
+        let height = match settings.timeline_strip {
+            Some(_) => settings.height + timeline::strip_height(settings.height),
+            None => settings.height,
+        };
+        let pixels = u64::from(settings.width) * u64::from(height);
+        let duration = if settings.still.is_some() {
+            0.0
+        } else {
+            f64::from(output_duration)
+        };
+        let exceeded = match (pixels > limits.max_pixels, duration > limits.max_duration) {
+            (true, _) => Some(format!(
+                "{}x{} frames have more than {} pixels",
+                settings.width, height, limits.max_pixels
+            )),
+            (false, true) => Some(format!(
+                "{} seconds is longer than {} seconds",
+                duration, limits.max_duration
+            )),
+            (false, false) => None,
+        };
+        if let Some(exceeded) = exceeded {
+            // Each cell's fifo and the encoder hold frames of their own, 4:2:0 at 1.5 bytes a pixel
+            let frames = (duration * max_input_fps).ceil().max(1.0);
+            let frame_megabytes = pixels as f64 * 1.5 / 1e6;
+            return Err(VidGridError::LimitExceeded(format!(
+                "The output is too large: {}. Rendering it means compositing {:.1} gigapixels in {} \
+                 frames of {:.0} MB each",
+                exceeded,
+                pixels as f64 * frames / 1e9,
+                frames,
+                frame_megabytes
+            )));
+        }
+    }
+
+    // Step 3: Calculate Individual Video Dimensions for the Grid
+    let GridGeometry { columns, slots } = grid_geometry(cells.len(), settings)?;
+
+    // Step 3b: Optionally shrink the video inputs to the cell size up front, in parallel, so the
+    // main filtergraph only handles small frames. Magnified inputs keep their full resolution.
+    let mut input_paths: Vec<PathBuf> = file_paths.iter().map(|path| path.to_path_buf()).collect();
+    let mut prescaled = vec![false; file_paths.len()];
+    let mut prescales = Vec::new();
+    if let Some(scratch) = inputs.scratch {
+        // Inputs can be shown in several cells, so shrink them to fit the largest
+        let video_width = slots.iter().map(|slot| slot.video_width).max();
+        let video_height = slots.iter().map(|slot| slot.video_height).max();
+        for (input, cell) in file_cells.iter().enumerate() {
+            let magnified = cells.iter().any(|other| match other.source {
+                InputSource::Magnify { cell, .. } => input_indices[cell - 1] == Some(input),
+                _ => false,
+            });
+            // Magnified and redacted regions are given in source pixels, cropped or stretched
+            // cells need more of the source than fits within the cell, and planes are extracted
+            // at their native resolution
+            let needs_source = magnified
+                || !cell.redactions.is_empty()
+                || cell.fit != Fit::Pad
+                || cell.plane.is_some();
+            if !matches!(cell.source, InputSource::File(_)) || needs_source {
+                continue;
+            }
+
+            let intermediate = scratch.join(format!("input{}.mkv", input));
+            prescales.push(Prescale {
+                input: file_paths[input].to_path_buf(),
+                stream: cell.stream,
+                input_args: cell.input_args(),
+                width: video_width.expect("there is a slot"),
+                height: video_height.expect("there is a slot"),
+                intermediate: intermediate.clone(),
+            });
+            input_paths[input] = intermediate;
+            prescaled[input] = true;
+        }
+    }
+
+    // Step 3c: Find the cells the output's audio is taken from. Video cells play their first
+    // audio stream, audio cells the stream they visualize.
+    let cell_audio_stream = |index: usize| match cells[index].source {
+        InputSource::File(_) => Some(0),
+        InputSource::Audio { .. } => Some(cells[index].stream),
+        _ => None,
+    };
+    let has_audio = |index: usize| match (input_indices[index], cell_audio_stream(index)) {
+        (Some(input), Some(stream)) => media[input].has_audio(stream),
+        _ => false,
+    };
+    let cells_with_audio =
+        || -> Vec<usize> { (0..cells.len()).filter(|&index| has_audio(index)).collect() };
+    let mut audio_cells = Vec::new();
+    match settings.audio {
+        _ if settings.still.is_some() || settings.animation.is_some() => {}
+        AudioMode::None => {}
+        AudioMode::Mix => audio_cells = cells_with_audio(),
+        AudioMode::Cell(cell) => {
+            if cell > cells.len() || !has_audio(cell - 1) {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "Cell {} has no audio",
+                    cell
+                )));
+            }
+            audio_cells.push(cell - 1);
+        }
+    }
+
+    // Every cell with audio also gets its own stem
+    let stem_cells = match (&settings.audio_stems, settings.still) {
+        (Some(_), None) => cells_with_audio(),
+        _ => Vec::new(),
+    };
+
+    // The ffmpeg stream specifier of the audio of cells that are heard. Pre-scaled intermediates
+    // have no audio, so their cells read their sound from the original file, opened a second time
+    // after the other inputs.
+    let mut audio_inputs = Vec::new();
+    let mut audio_specs = vec![None; cells.len()];
+    for &index in audio_cells.iter().chain(&stem_cells) {
+        if audio_specs[index].is_some() {
+            continue;
+        }
+        let mut input = input_indices[index].expect("audible cells read a file");
+        if prescaled[input] {
+            audio_inputs.push(input);
+            input = input_paths.len() + audio_inputs.len() - 1;
+        }
+        audio_specs[index] = Some(format!(
+            "{}:a:{}",
+            input,
+            cell_audio_stream(index).expect("audible cells have audio")
+        ));
+    }
+    // Cells trimmed or frozen by the filtergraph have their audio trimmed and paused there too,
+    // and filtered as asked, split for each of its uses. Other cells' audio is read straight from
+    // their input.
+    let mut audio_filters = Vec::new();
+    let mut mix_pads = Vec::new();
+    let mut stem_maps = Vec::new();
+    for (index, spec) in audio_specs.iter().enumerate() {
+        let Some(spec) = spec else {
+            continue;
+        };
+        let mixed = audio_cells.contains(&index);
+        let stem = stem_cells.contains(&index);
+        let loudness_dir = inputs.loudness_dir.filter(|_| mixed);
+        let measured = loudness_dir.is_some();
+        let measure = |pad: &str| {
+            let dir = loudness_dir.expect("measured cells have a loudness directory");
+            loudness::measure_filter(pad, &loudness::stats_path(dir, index + 1))
+        };
+        let chain: Vec<String> = cells[index]
+            .trim_filter(true)
+            .into_iter()
+            .chain(cells[index].freeze_audio_filter(&format!("aud{}", index + 1)))
+            .chain(cells[index].audio_filter.clone())
+            .collect();
+        if chain.is_empty() {
+            if mixed {
+                mix_pads.push((index, format!("[{}]", spec)));
+            }
+            if stem {
+                stem_maps.push((index, spec.clone()));
+            }
+            if measured {
+                audio_filters.push(measure(&format!("[{}]", spec)));
+            }
+        } else {
+            let label = format!("aud{}", index + 1);
+            let pad = |use_: &str, used: bool| {
+                if used {
+                    format!("[{}{}]", label, use_)
+                } else {
+                    String::new()
+                }
+            };
+            audio_filters.push(format!(
+                "[{spec}]{chain},asplit={uses}{mix}{stem}{loud};",
+                chain = chain.join(","),
+                uses = mixed as usize + stem as usize + measured as usize,
+                mix = pad("mix", mixed),
+                stem = pad("stem", stem),
+                loud = pad("loud", measured),
+            ));
+            if mixed {
+                mix_pads.push((index, format!("[{}mix]", label)));
+            }
+            if stem {
+                stem_maps.push((index, format!("[{}stem]", label)));
+            }
+            if measured {
+                audio_filters.push(measure(&format!("[{}loud]", label)));
+            }
+        }
+    }
+    // Mixed in the order the cells were chosen in
+    mix_pads.sort_by_key(|(index, _)| audio_cells.iter().position(|cell| cell == index));
+    let audio_pads: Vec<String> = mix_pads.into_iter().map(|(_, pad)| pad).collect();
+
+    // The ffmpeg stream specifier of the video stream feeding a file cell. Pre-scaled
+    // intermediates only contain that stream.
+    let stream_spec = |cell: usize| -> Result<String, VidGridError> {
+        let input = file_input(cell)?;
+        let stream = if prescaled[input] {
+            0
+        } else {
+            cells[cell - 1].stream
+        };
+        Ok(format!("{}:v:{}", input, stream))
+    };
+
+    // Read a file cell's video stream for the consumer `label`, obscuring the cell's redacted
+    // regions. Returns the filters doing so and the pad to read the result from. Redactions follow
+    // the source into every cell showing it, such as magnifications.
+    let read_source = |cell: usize, label: &str| -> Result<(String, String), VidGridError> {
+        let mut graph = String::new();
+        let mut pad = stream_spec(cell)?;
+        // Turn the frames upright. Pre-scaled intermediates were turned by ffmpeg when decoded
+        let input = file_input(cell)?;
+        if let (Some(rotation), false) = (rotations[input], prescaled[input]) {
+            let upright = format!("{}upright", label);
+            graph.push_str(&format!("[{pad}]{}[{upright}]; ", rotation.filter()));
+            pad = upright;
+        }
+        if let Some(trim) = cells[cell - 1].trim_filter(false) {
+            let trimmed = format!("{}trim", label);
+            graph.push_str(&format!("[{pad}]{trim}[{trimmed}]; "));
+            pad = trimmed;
+        }
+        let offset = cells[cell - 1].start.unwrap_or(0.0);
+        for (index, redaction) in cells[cell - 1].redactions.iter().enumerate() {
+            let region = redaction.region;
+            let enable = match redaction.time {
+                Some((start, end)) if end.is_finite() => {
+                    format!("between(t,{},{})", start - offset, end - offset)
+                }
+                Some((start, _)) => format!("gte(t,{})", start - offset),
+                None => String::new(),
+            };
+            let enable = if enable.is_empty() {
+                enable
+            } else {
+                format!(":enable={}", escape::escape_filter_option(&enable))
+            };
+            let redacted = format!("{}redact{}", label, index + 1);
+            graph.push_str(&format!(
+                "[{pad}]split[{redacted}clear][{redacted}patch]; \
+                 [{redacted}patch]crop={w}:{h}:{x}:{y},{obscure}[{redacted}obscured]; \
+                 [{redacted}clear][{redacted}obscured]overlay={x}:{y}{enable}[{redacted}]; ",
+                w = region.width,
+                h = region.height,
+                x = region.x,
+                y = region.y,
+                obscure = settings.redact_style.filter(region.width, region.height),
+            ));
+            pad = redacted;
+        }
+        Ok((graph, pad))
+    };
+
+    // Whether the video file of a cell already has the size and colors of the cell, as when
+    // inputs were normalized for the grid beforehand. Scaling those would only cost time.
+    let cell_sized = |index: usize| {
+        let (Some(input), InputSource::File(_)) = (input_indices[index], &cells[index].source)
+        else {
+            return false;
+        };
+        let slot = &slots[index];
+        frame_sizes[input] == Some((slot.video_width, slot.video_height))
+            && rotations[input].is_none()
+            && !prescaled[input]
+            && color_matrices[input] == "bt709"
+            && !full_ranges[input]
+            && color_primaries[input].is_none()
+            && cells[index].plane.is_none()
+    };
+
+    // Scale an input to fit the cell in `slot`, converting its colors to limited range BT.709,
+    // fill the rest of the cell according to the pad mode, then reset its PTS and run it at the
+    // output frame rate. Inputs that are `sized` to the cell are only retimed. Reads from the
+    // `from` pad and writes to the `to` pad.
+    let conform = |input: usize, slot: &Slot, fit: Fit, sized: bool, from: &str, to: &str| {
+        let (video_width, video_height) = (slot.video_width, slot.video_height);
+        let range = if full_ranges[input] { "pc" } else { "tv" };
+        let scale = |aspect: &str| match color_primaries[input] {
+            // scale converts the matrix and range but not the primaries, which colorspace does
+            // along with the rest
+            Some(primaries) => format!(
+                "colorspace=all=bt709:iall={primaries}:ispace={space}:irange={range}:range=tv,\
+                 scale={vw}:{vh}{aspect}:in_color_matrix=bt709:out_color_matrix=bt709",
+                vw = video_width,
+                vh = video_height,
+                space = match color_matrices[input] {
+                    "bt601" => "smpte170m",
+                    "bt2020" => "bt2020ncl",
+                    matrix => matrix,
+                },
+            ),
+            None => format!(
+                "scale={vw}:{vh}{aspect}:in_color_matrix={matrix}:out_color_matrix=bt709:\
+                 in_range={range}:out_range=tv",
+                vw = video_width,
+                vh = video_height,
+                matrix = color_matrices[input],
+            ),
+        };
+        let fit_filter = scale(":force_original_aspect_ratio=decrease");
+        let timing = format!("setpts=PTS-STARTPTS,fps=fps={fps}", fps = max_input_fps);
+
+        match (fit, settings.pad) {
+            _ if sized => format!("[{from}]{timing}[{to}];"),
+            (Fit::Crop, _) => format!(
+                "[{from}]{scale},crop={vw}:{vh},{timing}[{to}];",
+                scale = scale(":force_original_aspect_ratio=increase"),
+                vw = video_width,
+                vh = video_height,
+            ),
+            (Fit::Stretch, _) => format!(
+                "[{from}]{scale},setsar=1,{timing}[{to}];",
+                scale = scale("")
+            ),
+            (Fit::Pad, PadMode::Black) => format!(
+                "[{from}]{fit_filter},pad={vw}:{vh}:(ow-iw)/2:(oh-ih)/2,{timing}[{to}];",
+                vw = video_width,
+                vh = video_height,
+            ),
+            // Fill the cell with a blurred copy of the input, then center the input on top
+            (Fit::Pad, PadMode::Blur) => format!(
+                "[{from}]split[{to}fg][{to}bg]; \
+                 [{to}bg]{fill},crop={vw}:{vh},boxblur=20:2[{to}blur]; \
+                 [{to}fg]{fit_filter}[{to}fit]; \
+                 [{to}blur][{to}fit]overlay=(W-w)/2:(H-h)/2,{timing}[{to}];",
+                fill = scale(":force_original_aspect_ratio=increase"),
+                vw = video_width,
+                vh = video_height,
+            ),
+        }
+    };
+
+    // Live stats of the video files are drawn from where the health monitor writes them
+    let health_dir = inputs.health_dir;
+    let health_cells: Vec<usize> = (1..=cells.len())
+        .filter(|&cell| matches!(cells[cell - 1].source, InputSource::File(_)))
+        .collect();
+
+    let labels: Vec<String> = (1..=slots.len())
+        .map(|cell| format!("vid{}", cell))
+        .collect();
+    let mut filters = Vec::new();
+
+    // Cells contributing sound to the output
+    let audible: Vec<bool> = (0..cells.len())
+        .map(|index| audio_cells.contains(&index))
+        .collect();
+
+    // Conform each cell, apply its effects and add fifo to it. The fifo buffers are unbounded, so
+    // they are left out when memory is tight and cells instead wait on each other.
+    for (index, (cell, label)) in cells.iter().zip(&labels).enumerate() {
+        let slot = &slots[index];
+        let (video_width, video_height) = (slot.video_width, slot.video_height);
+        let mut effects = cell.effect_filters();
+        if let (Some(dir), InputSource::File(_)) = (&health_dir, &cell.source) {
+            effects.push(format!(
+                "drawtext=textfile={}:reload=1{}:{}",
+                escape::escape_filter_option(&health::text_path(dir, index + 1).to_string_lossy()),
+                cell.font_option(),
+                HEALTH_STATS
+            ));
+        }
+        let silent = !audible[index] && !matches!(cell.source, InputSource::Empty);
+        if settings.show_mute_icons && silent {
+            effects.push(format!(
+                "drawtext=text=MUTED{}:{}",
+                cell.font_option(),
+                MUTE_ICON
+            ));
+        }
+        if settings.border > 0 {
+            effects.push(format!(
+                "pad={sw}:{sh}:{b}:{b}:color={color}",
+                sw = slot.width,
+                sh = slot.height,
+                b = settings.border,
+                color = escape::escape_filter_option(&settings.border_color)
+            ));
+        }
+        if settings.low_memory {
+            effects.push("null".to_string());
+        } else {
+            effects.push("fifo".to_string());
+        }
+        let effects = effects.join(",");
+        // Obscure spoilers before anything is drawn on them, so their labels stay readable
+        let effects = match &cell.spoiler {
+            Some(spoiler) => format!(
+                "{}{}",
+                spoiler.filter(&format!("{}spoiler", label), video_width, video_height),
+                effects
+            ),
+            None => effects,
+        };
+        // So does the slate of a video file whose picture stands still
+        let effects = match (settings.no_signal_after, &cell.source) {
+            (Some(after), InputSource::File(_)) => format!(
+                "{}{}",
+                no_signal_filter(
+                    &format!("{}nosignal", label),
+                    video_width,
+                    video_height,
+                    after,
+                    max_input_fps,
+                    &cell.font_option()
+                ),
+                effects
+            ),
+            _ => effects,
+        };
+
+        let mut source_filters = cell.source_filters();
+        if let (Some(_), TimecodeSource::Source, InputSource::File(path)) =
+            (cell.timestamp, cell.timecode_from, &cell.source)
+        {
+            let input = file_input(index + 1)?;
+            let timecode = media[input].timecode.as_deref().ok_or_else(|| {
+                VidGridError::InvalidGrid(format!(
+                    "{} has no timecode to show on cell {}",
+                    path.display(),
+                    index + 1
+                ))
+            })?;
+            let fps = input_fps[input].expect("file cells have a frame rate");
+            source_filters.push(
+                cell.timecode_filter(timecode, fps)
+                    .map_err(VidGridError::Parse)?,
+            );
+        }
+        if source_filters.is_empty() {
+            source_filters.push("null".to_string());
+        }
+        let source_filters = source_filters.join(",");
+
+        let filter = match &cell.source {
+            InputSource::File(_) => {
+                let (read, stream) = read_source(index + 1, label)?;
+                format!(
+                    "{read}[{stream}]{source_filters}[{label}src]; \
+                     {conform} \
+                     [{label}fit]{effects}[{label}];",
+                    conform = conform(
+                        file_input(index + 1)?,
+                        slot,
+                        cell.fit,
+                        cell_sized(index),
+                        &format!("{}src", label),
+                        &format!("{}fit", label)
+                    )
+                )
+            }
+            // Blend the overlay cell on top of the base cell at the requested opacity
+            InputSource::Onion {
+                base,
+                overlay,
+                opacity,
+            } => {
+                let (read_base, base_pad) = read_source(*base, &format!("{}base", label))?;
+                let (read_overlay, overlay_pad) = read_source(*overlay, &format!("{}over", label))?;
+                format!(
+                    "{read_base}[{base_pad}]{source_filters}[{label}basesrc]; \
+                     {base_conform} \
+                     {read_overlay}[{overlay_pad}]{source_filters}[{label}oversrc]; \
+                     {overlay_conform} \
+                     [{label}overfit]format=yuva420p,colorchannelmixer=aa={opacity}[{label}over]; \
+                     [{label}base][{label}over]overlay=eof_action=pass,{effects}[{label}];",
+                    base_conform = conform(
+                        file_input(*base)?,
+                        slot,
+                        cell.fit,
+                        false,
+                        &format!("{}basesrc", label),
+                        &format!("{}base", label)
+                    ),
+                    overlay_conform = conform(
+                        file_input(*overlay)?,
+                        slot,
+                        cell.fit,
+                        false,
+                        &format!("{}oversrc", label),
+                        &format!("{}overfit", label)
+                    ),
+                )
+            }
+            // Draw the audio with a visualizer at the cell size
+            InputSource::Audio { visualizer, .. } => {
+                let source = match visualizer {
+                    Visualizer::Waveform => format!(
+                        "showwaves=s={vw}x{vh}:mode=cline",
+                        vw = video_width,
+                        vh = video_height
+                    ),
+                    Visualizer::Spectrogram => format!(
+                        "showspectrum=s={vw}x{vh}:slide=scroll:mode=combined",
+                        vw = video_width,
+                        vh = video_height
+                    ),
+                };
+                format!(
+                    "[{input}:a:{stream}]{source},fps=fps={fps},format=yuv420p,{effects}[{label}];",
+                    input = input_indices[index].expect("audio cells read a file"),
+                    stream = cell.stream,
+                    fps = max_input_fps
+                )
+            }
+            // Crop the region out of the other cell's source and blow it up to the cell size
+            InputSource::Magnify {
+                cell: source,
+                region,
+            } => {
+                let (read, stream) = read_source(*source, label)?;
+                format!(
+                    "{read}[{stream}]crop={w}:{h}:{x}:{y}[{label}src]; \
+                     {conform} \
+                     [{label}fit]{effects}[{label}];",
+                    w = region.width,
+                    h = region.height,
+                    x = region.x,
+                    y = region.y,
+                    conform = conform(
+                        file_input(*source)?,
+                        slot,
+                        cell.fit,
+                        false,
+                        &format!("{}src", label),
+                        &format!("{}fit", label)
+                    )
+                )
+            }
+            InputSource::Empty => format!(
+                "color=c=black:s={vw}x{vh}:r={fps},{effects}[{label}];",
+                vw = video_width,
+                vh = video_height,
+                fps = max_input_fps
+            ),
+        };
+        filters.push(filter);
+    }
+
+    // Fill the positions left over in the last row with the background
+    let background = escape::escape_filter_option(&settings.background_color);
+    for (label, slot) in labels.iter().zip(&slots).skip(cells.len()) {
+        filters.push(format!(
+            "color=c={background}:s={sw}x{sh}:r={fps}[{label}];",
+            sw = slot.width,
+            sh = slot.height,
+            fps = max_input_fps
+        ));
+    }
+
+    // Cover each cell with its end card, fitted inside the cell's border, once only the card's
+    // duration is left of the cell or of the grid
+    let mut labels = labels;
+    for (index, (cell, label)) in cells.iter().zip(labels.iter_mut()).enumerate() {
+        let Some(card) = &cell.end_card else {
+            continue;
+        };
+        let slot = &slots[index];
+        let grid_duration = f64::from(output_duration);
+        let played = input_indices[index].map_or(grid_duration, |input| {
+            cell.trimmed_duration(media[input].duration)
+                .min(grid_duration)
+        });
+        filters.push(format!(
+            "movie=filename={image},scale={vw}:{vh}:force_original_aspect_ratio=decrease,format=yuva420p[{label}cardimage]; \
+             [{label}][{label}cardimage]overlay=(W-w)/2:(H-h)/2:enable={enable}[{label}card];",
+            image = escape::escape_filter_option(&card.image.to_string_lossy()),
+            vw = slot.video_width,
+            vh = slot.video_height,
+            enable = escape::escape_filter_option(&format!(
+                "gte(t,{})",
+                (played - card.duration).max(0.0)
+            )),
+        ));
+        *label = format!("{}card", label);
+    }
+
+    // Branch a thumbnail stream off every cell before it is stacked into the grid
+    let mut thumb_outputs = Vec::new();
+    if let Some(thumb_track) = &settings.thumb_track {
+        for (index, label) in labels.iter_mut().take(cells.len()).enumerate() {
+            filters.push(format!(
+                "[{label}]split[{label}grid][{label}thumb]; \
+                 [{label}thumb]fps=fps=1/{interval},scale=160:-2[thumb{cell}];",
+                interval = thumb_track.interval,
+                cell = index + 1,
+            ));
+            *label = format!("{}grid", label);
+
+            thumb_outputs.extend([
+                "-map".to_string(),
+                format!("[thumb{}]", index + 1),
+                "-t".to_string(),
+                output_duration.to_string(),
+                "-start_number".to_string(),
+                "0".to_string(),
+                "-q:v".to_string(),
+                "5".to_string(),
+                thumb_track
+                    .dir
+                    .join(format!("cell{}_%05d.jpg", index + 1))
+                    .to_string_lossy()
+                    .into_owned(),
+            ]);
+        }
+    }
+
+    // Write the audio of every cell as a WAV file on the grid's timeline
+    let mut stem_outputs = Vec::new();
+    if let Some(dir) = &settings.audio_stems {
+        for (index, stream) in &stem_maps {
+            stem_outputs.extend([
+                "-map".to_string(),
+                stream.clone(),
+                "-t".to_string(),
+                output_duration.to_string(),
+                "-c:a".to_string(),
+                "pcm_s16le".to_string(),
+                dir.join(format!("cell{}.wav", index + 1))
+                    .to_string_lossy()
+                    .into_owned(),
+            ]);
+        }
+    }
+
+    match (settings.compare, columns) {
+        (Some(comparison), _) => {
+            filters.push(comparison.filter(
+                &labels[0],
+                &labels[1],
+                "stacked",
+                f64::from(output_duration),
+            ));
+        }
+        (None, Some(columns)) if settings.gap == 0 || labels.len() == 1 => {
+            // Stack the videos into rows, then stack the rows into the grid
+            let mut row_labels = Vec::new();
+            for (row, row_cells) in labels.chunks(columns).enumerate() {
+                let row_label = format!("row{}", row + 1);
+                filters.push(stack_filter("hstack", row_cells, &row_label));
+                row_labels.push(row_label);
+            }
+            filters.push(stack_filter("vstack", &row_labels, "stacked"));
+        }
+        // Draw every inset on top of the main cell in turn
+        _ if settings.layout == Layout::Pip => {
+            let mut base = labels[0].clone();
+            for (index, (label, slot)) in labels.iter().zip(&slots).enumerate().skip(1) {
+                let output = if index + 1 == labels.len() {
+                    "stacked".to_string()
+                } else {
+                    format!("pip{}", index + 1)
+                };
+                filters.push(format!(
+                    "[{base}][{label}]overlay={x}:{y}:eof_action=pass[{output}];",
+                    x = slot.x,
+                    y = slot.y,
+                ));
+                base = output;
+            }
+        }
+        _ => {
+            // Place every video at its own position, with the background showing through the
+            // gaps, then pad the grid to the exact output size the cells couldn't divide evenly
+            let pads: String = labels.iter().map(|label| format!("[{}]", label)).collect();
+            let layout: Vec<String> = slots
+                .iter()
+                .map(|slot| format!("{}_{}", slot.x, slot.y))
+                .collect();
+            filters.push(format!(
+                "{pads}xstack=inputs={count}:layout={layout}:fill={background},\
+                 pad={w}:{h}:0:0:color={background}[stacked];",
+                count = labels.len(),
+                layout = layout.join("|"),
+                w = settings.width,
+                h = settings.height,
+            ));
+        }
+    }
+
+    let mut output_filters = Vec::new();
+    if let Some(rotation) = settings.rotate {
+        output_filters.push(rotation.filter());
+    }
+    // Cells are normalized to limited range BT.709, so only convert when something else is asked for
+    if settings.colorspace != ColorSpace::Bt709 || settings.color_range != ColorRange::Limited {
+        output_filters.push(format!(
+            "colorspace=all={space}:iall=bt709:irange=tv:range={range}",
+            space = settings.colorspace.filter_name(),
+            range = settings.color_range.ffmpeg_name()
+        ));
+    }
+    // Weave pairs of frames into the two fields of one interlaced frame, halving the frame rate
+    if let Some(field_order) = settings.interlace {
+        output_filters.push(format!(
+            "interlace=scan={}:lowpass=complex",
+            field_order.scan_name()
+        ));
+    }
+    // Fingerprint the frames as they are before encoding, so a different encoder build doesn't
+    // change them
+    if settings.frame_hashes.is_some() {
+        output_filters.push("split[hashed][hashes]; [hashed]null".to_string());
+    }
+    // The replay buffer is encoded apart from the output, so it plays whatever the output is
+    if settings.replay_buffer.is_some() {
+        output_filters.push("split[replayed][replay]; [replayed]null".to_string());
+    }
+    if let (Some(upload), None, None) = (
+        settings.encoder.upload_filter(),
+        settings.still,
+        settings.animation,
+    ) {
+        output_filters.push(upload);
+    }
+    // A GIF holds 256 colors, so pick the ones the grid needs most from all of its frames, then
+    // map every frame onto them. Dithering only what changes between frames keeps them small.
+    if settings.animation == Some(AnimatedFormat::Gif) && settings.still.is_none() {
+        output_filters.push(
+            "split[frames][palette_frames]; [palette_frames]palettegen=stats_mode=diff[palette]; \
+             [frames][palette]paletteuse=dither=bayer:bayer_scale=5:diff_mode=rectangle"
+                .to_string(),
+        );
+    }
+    if output_filters.is_empty() {
+        output_filters.push("null".to_string());
+    }
+    // Mix the audible cells into one track. Normalizing sums the cells at full volume and brings
+    // the loudness of the sum to a broadcast level, limiting its peaks; otherwise amix scales the
+    // cells down so their sum can't clip. A single cell's audio is passed through as is.
+    let mut audio_outputs = Vec::new();
+    filters.extend(audio_filters);
+    if !audio_pads.is_empty() {
+        let mix = match settings.audio {
+            AudioMode::Mix if settings.normalize_audio => format!(
+                "amix=inputs={}:duration=longest:normalize=0,loudnorm=I=-16:TP=-1.5:LRA=11,aresample=48000",
+                audio_pads.len()
+            ),
+            AudioMode::Mix => format!("amix=inputs={}:duration=longest", audio_pads.len()),
+            _ => "anull".to_string(),
+        };
+        let replay_split = match settings.replay_buffer {
+            Some(_) => ",asplit[aout][replayaudio]",
+            None => "[aout]",
+        };
+        filters.push(format!("{}{}{};", audio_pads.join(""), mix, replay_split));
+        audio_outputs.extend(["-map", "[aout]"]);
+    }
+
+    let mut grid = "stacked";
+    if let Some(events) = &settings.timeline_strip {
+        filters.push(timeline::strip_filter(
+            events,
+            cells.len(),
+            settings.width,
+            timeline::strip_height(settings.height),
+            f64::from(output_duration),
+            max_input_fps,
+            "timeline",
+        )?);
+        filters.push("[stacked][timeline]vstack=inputs=2[timelined];".to_string());
+        grid = "timelined";
+    }
+    filters.push(format!("[{}]{}[final]", grid, output_filters.join(",")));
+
+    let filter_complex = filters.join(" ");
+
+    // Record how every video file was changed to fit it into its cell
+    let mut adjustments = vec![Vec::new(); cells.len()];
+    for (index, cell_adjustments) in adjustments.iter_mut().enumerate() {
+        let (InputSource::File(_), Some(input)) = (&cells[index].source, input_indices[index])
+        else {
+            continue;
+        };
+        if let Some(fps) = input_fps[input].filter(|&fps| fps != max_input_fps) {
+            cell_adjustments.push(Adjustment::FrameRate {
+                from: fps,
+                to: max_input_fps,
+            });
+        }
+        if color_matrices[input] != "bt709" {
+            cell_adjustments.push(Adjustment::ColorMatrix {
+                from: color_matrices[input].to_string(),
+                to: "bt709".to_string(),
+            });
+        }
+        if full_ranges[input] {
+            cell_adjustments.push(Adjustment::ColorRange {
+                from: "full".to_string(),
+                to: "limited".to_string(),
+            });
+        }
+        if let Some(primaries) = color_primaries[input] {
+            cell_adjustments.push(Adjustment::ColorPrimaries {
+                from: primaries.to_string(),
+                to: "bt709".to_string(),
+            });
+        }
+        if let Some(rotation) = rotations[input] {
+            cell_adjustments.push(Adjustment::Rotate {
+                degrees: rotation.degrees(),
+            });
+        }
+        if prescaled[input] {
+            cell_adjustments.push(Adjustment::Prescale);
+        }
+        if cell_sized(index) {
+            continue;
+        }
+        cell_adjustments.push(Adjustment::Scale {
+            width: slots[index].video_width,
+            height: slots[index].video_height,
+            fit: cells[index].fit,
+            pad: settings.pad,
+        });
+    }
+
+    let info = GridInfo {
+        adjustments,
+        loudness: vec![None; cells.len()],
+        duration: if settings.still.is_some() {
+            0
+        } else {
+            output_duration
+        },
+        framerate: if settings.interlace.is_some() {
+            max_input_fps / 2.0
+        } else {
+            max_input_fps
+        },
+    };
+
+    // Step 4: Assemble the ffmpeg Command with the New Parameters
+    // Encode next to the output and only move it into place once complete, so nothing watching
+    // the destination picks up a half-written file
+    let stream = StreamTarget::from_path(&settings.output_path);
+    let encode_path = match (stream, &inputs.partial_path) {
+        // Segments are complete files once ffmpeg moves on to the next one
+        (None, _) if settings.segments.is_some() => settings.output_path.clone(),
+        (None, Some(partial_path)) => partial_path.clone(),
+        // ffmpeg has no WHIP muxer, so the stream is written to the gateway's standard input
+        (Some(StreamTarget::Whip), _) => PathBuf::from("pipe:1"),
+        _ => settings.output_path.clone(),
+    };
+    let gateway = match (stream, &settings.stream_gateway) {
+        (Some(StreamTarget::Whip), Some(gateway)) => Some(gateway.clone()),
+        (Some(StreamTarget::Whip), None) => {
+            return Err(VidGridError::InvalidGrid(
+                "A WHIP stream needs a gateway command to publish it".to_string(),
+            ))
+        }
+        _ => None,
+    };
+    // Progress is reported on standard output, which a stream to it can't share
+    let to_stdout = matches!(stream, Some(StreamTarget::Stdout | StreamTarget::Whip));
+    let (colorspace_tag, primaries_tag, trc_tag) = settings.colorspace.tags();
+    let mut args: Vec<String> = Vec::new();
+    // Standard input is left to whatever triggers replays, instead of ffmpeg's own keys
+    if settings.replay_buffer.is_some() {
+        args.push("-nostdin".to_string());
+    }
+    if settings.quiet || to_stdout {
+        args.extend(["-v", "error", "-nostats"].map(String::from));
+    } else if settings.progress.is_some() {
+        args.extend(["-hide_banner", "-nostats", "-progress", "pipe:1"].map(String::from));
+    }
+    if settings.still.is_none() {
+        args.extend(settings.encoder.global_args());
+    }
+    if settings.low_memory {
+        // Every filter and frame thread holds frames of its own, which adds up quickly at 4K+
+        args.extend(["-filter_complex_threads", "1", "-filter_threads", "1"].map(String::from));
+    } else if let Some(threads) = settings.threads {
+        args.extend(["-filter_complex_threads".to_string(), threads.to_string()]);
+    }
+    // Inputs ending before the grid are looped from the start. Trimmed inputs would loop from the
+    // start of the file rather than of their trim, so they hold their last frame instead.
+    let looped: Vec<bool> = file_cells
+        .iter()
+        .zip(&input_durations)
+        .map(|(cell, &duration)| {
+            settings.short_inputs == ShortInputs::Loop
+                && cell.start.is_none()
+                && cell.end.is_none()
+                && duration < output_duration as f64
+        })
+        .collect();
+    let loop_args = |input: usize| {
+        if looped[input] {
+            vec!["-stream_loop", "-1"]
+        } else {
+            Vec::new()
+        }
+    };
+    // Servers expect a live stream to arrive as fast as it plays, not as fast as it encodes
+    let realtime_args = match stream {
+        Some(
+            StreamTarget::Rtmp | StreamTarget::Network | StreamTarget::Srt | StreamTarget::Whip,
+        ) => vec!["-re"],
+        _ => Vec::new(),
+    };
+    let path_arg = |path: &Path| path.to_string_lossy().into_owned();
+    for (input, (path, cell)) in input_paths.iter().zip(&file_cells).enumerate() {
+        args.extend(loop_args(input).into_iter().map(String::from));
+        args.extend(realtime_args.iter().map(|arg| arg.to_string()));
+        // Pre-scaled intermediates have already been decrypted, trimmed and turned upright
+        if !prescaled[input] {
+            args.extend(cell.input_args());
+            if rotations[input].is_some() {
+                args.push("-noautorotate".to_string());
+            }
+        }
+        if settings.low_memory {
+            args.extend(["-threads", "2"].map(String::from));
+        }
+        args.extend(["-i".to_string(), path_arg(path)]);
+    }
+    for &input in &audio_inputs {
+        args.extend(loop_args(input).into_iter().map(String::from));
+        args.extend(realtime_args.iter().map(|arg| arg.to_string()));
+        args.extend(file_cells[input].input_args());
+        args.extend(["-i".to_string(), path_arg(file_paths[input])]);
+    }
+    // Captions are read from the start of their cell's source, like its video
+    let mut caption_outputs: Vec<String> = Vec::new();
+    let caption_cells: Vec<usize> = (0..cells.len())
+        .filter(|&index| cells[index].captions.is_some())
+        .collect();
+    for (track, &index) in caption_cells.iter().enumerate() {
+        let cell = &cells[index];
+        if let Some(start) = cell.start {
+            args.extend(["-itsoffset".to_string(), (-start).to_string()]);
+        }
+        args.extend([
+            "-i".to_string(),
+            path_arg(cell.captions.as_ref().expect("the cell has captions")),
+        ]);
+        caption_outputs.extend([
+            "-map".to_string(),
+            format!("{}:s:0", input_paths.len() + audio_inputs.len() + track),
+            format!("-metadata:s:s:{}", track),
+            format!(
+                "title={}",
+                cell.label
+                    .clone()
+                    .unwrap_or_else(|| format!("Cell {}", index + 1))
+            ),
+        ]);
+    }
+    if !caption_cells.is_empty() {
+        caption_outputs.extend(["-c:s".to_string(), subtitle_codec(&settings.output_path)?]);
+    }
+    if settings.low_memory {
+        args.extend(["-threads", "2", "-max_muxing_queue_size", "64"].map(String::from));
+    } else if let Some(threads) = settings.threads {
+        args.extend(["-threads".to_string(), threads.to_string()]);
+    }
+    let length_args = match settings.still {
+        Some(_) => ["-frames:v", "1", "-update", "1"]
+            .map(String::from)
+            .to_vec(),
+        None => vec!["-t".to_string(), output_duration.to_string()],
+    };
+    let mut hash_outputs = Vec::new();
+    if let Some(path) = &settings.frame_hashes {
+        hash_outputs.extend(["-map".to_string(), "[hashes]".to_string()]);
+        hash_outputs.extend(length_args.iter().take(2).cloned());
+        hash_outputs.extend(["-f".to_string(), "framemd5".to_string(), path_arg(path)]);
+    }
+    let mut health_outputs = Vec::new();
+    if let Some(dir) = health_dir {
+        for &cell in &health_cells {
+            health_outputs.extend(health::output_args(
+                dir,
+                cell,
+                &stream_spec(cell)?,
+                &length_args,
+            ));
+        }
+    }
+    let replay_outputs = match &settings.replay_buffer {
+        Some(buffer) => buffer.output_args(
+            "[replay]",
+            (!audio_outputs.is_empty()).then_some("[replayaudio]"),
+            output_duration,
+        ),
+        None => Vec::new(),
+    };
+    // Streams have no extension to pick their muxer by. AAC is the audio codec every muxer of a
+    // stream carries
+    let mut stream_args = Vec::new();
+    if let Some(stream) = stream {
+        if !audio_outputs.is_empty() {
+            stream_args.extend(["-c:a", "aac"]);
+        }
+        stream_args.extend(["-f", stream.muxer()]);
+        // Packets are sent as soon as they are muxed rather than gathered into larger writes
+        if stream.is_low_latency() {
+            stream_args.extend(["-flush_packets", "1", "-muxdelay", "0", "-muxpreload", "0"]);
+        }
+    }
+    args.extend(["-filter_complex".to_string(), filter_complex.clone()]);
+    args.extend(["-map", "[final]"].map(String::from));
+    args.extend(audio_outputs.into_iter().map(String::from));
+    args.extend(caption_outputs);
+    args.extend(length_args);
+    args.extend(encoder_args);
+    args.extend(
+        [
+            "-colorspace",
+            colorspace_tag,
+            "-color_primaries",
+            primaries_tag,
+            "-color_trc",
+            trc_tag,
+            "-color_range",
+            settings.color_range.ffmpeg_name(),
+        ]
+        .map(String::from),
+    );
+    args.extend(metadata_args(settings));
+    args.extend(interlace_args(settings));
+    // Ensure frame duplication is handled correctly, and overwrite the output if it exists
+    args.extend(["-vsync", "2", "-y"].map(String::from));
+    args.extend(stream_args.into_iter().map(String::from));
+    match &settings.segments {
+        Some(segments) => {
+            args.extend(segments.output_args());
+            args.push(path_arg(&Segments::pattern(&encode_path)));
+        }
+        None => args.push(path_arg(&encode_path)),
+    }
+    args.extend(thumb_outputs);
+    args.extend(stem_outputs);
+    args.extend(hash_outputs);
+    args.extend(replay_outputs);
+    args.extend(health_outputs);
+
+    Ok(GridCommand {
+        args,
+        filter_complex,
+        geometry: GridGeometry { columns, slots },
+        info,
+        duration: output_duration,
+        prescales,
+        stream,
+        encode_path,
+        gateway,
+        audio_cells,
+        health_cells,
+    })
+}
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+#[cfg(feature = "render")]
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Where the stats of `cell`, counted from 1, are written to for drawing
+pub(crate) fn text_path(dir: &Path, cell: usize) -> PathBuf {
+    dir.join(format!("cell{}.txt", cell))
+}
+
+/// Where ffmpeg lists the packets of `cell`
+pub(crate) fn packets_path(dir: &Path, cell: usize) -> PathBuf {
+    dir.join(format!("cell{}.crc", cell))
+}
+
+/// The ffmpeg output options listing the packets of the input stream `stream_spec` that
+/// feeds `cell` for as long as `length_args` let the grid run, flushed as they arrive
+pub(crate) fn output_args(
+    dir: &Path,
+    cell: usize,
+    stream_spec: &str,
+    length_args: &[String],
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-map", stream_spec, "-c", "copy"]
+        .into_iter()
+        .chain(length_args.iter().map(String::as_str))
+        .chain(["-flush_packets", "1", "-f", "framecrc"])
+        .map(String::from)
+        .collect();
+    args.push(packets_path(dir, cell).to_string_lossy().into_owned());
+    args
+}
+
+/// How often the stats of the cells are brought up to date. Frames are seen arriving in batches
+/// of this interval, so the time since the last frame is only this accurate
+#[cfg(feature = "render")]
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How far back the frame rate and bitrate of a cell are averaged over
+#[cfg(feature = "render")]
+const WINDOW: Duration = Duration::from_secs(2);
+
+/// Shown on a cell until its first frame arrives
+#[cfg(feature = "render")]
+const WAITING: &str = "waiting for frames";
+
+/// Watches the packets ffmpeg receives for every cell as it renders the grid, writing each cell's
+/// frame rate, bitrate and time since its last frame to a text file drawn on the cell.
+///
+/// ffmpeg copies the video packets of every watched input into a `framecrc` listing, one line
+/// per packet with its size, which is read as it grows. Stats are measured against the wall
+/// clock, so they show how a live feed is arriving rather than how fast it is encoded.
+#[cfg(feature = "render")]
+pub(crate) struct HealthMonitor {
+    dir: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The packets of one cell seen so far
+#[cfg(feature = "render")]
+struct CellFeed {
+    cell: usize,
+    /// How far into its packet listing has been read
+    offset: u64,
+    /// What is left of a line ffmpeg hasn't finished writing yet
+    partial: String,
+    /// When the packets within the last `WINDOW` arrived, and their sizes in bytes
+    recent: VecDeque<(Instant, u64)>,
+    last_arrival: Option<Instant>,
+}
+
+#[cfg(feature = "render")]
+impl HealthMonitor {
+    /// Starts watching `cells` in `dir`. drawtext fails on a missing text file, so every cell
+    /// waits for its first frame with a placeholder
+    pub fn start(dir: PathBuf, cells: Vec<usize>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        for &cell in &cells {
+            write_stats(&dir, cell, WAITING)?;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (dir, stop) = (dir.clone(), Arc::clone(&stop));
+            let mut feeds: Vec<CellFeed> = cells
+                .into_iter()
+                .map(|cell| CellFeed {
+                    cell,
+                    offset: 0,
+                    partial: String::new(),
+                    recent: VecDeque::new(),
+                    last_arrival: None,
+                })
+                .collect();
+            thread::spawn(move || {
+                let started = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(UPDATE_INTERVAL);
+                    for feed in &mut feeds {
+                        // A missed update is made up for by the next one
+                        let _ = feed.update(&dir, started);
+                    }
+                }
+            })
+        };
+        Ok(HealthMonitor {
+            dir,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(feature = "render")]
+impl Drop for HealthMonitor {
+    /// Stops watching and removes the listings and stats
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(feature = "render")]
+impl CellFeed {
+    /// Reads the packets listed since the last update and rewrites the cell's stats
+    fn update(&mut self, dir: &Path, started: Instant) -> io::Result<()> {
+        let now = Instant::now();
+        let mut file = match File::open(packets_path(dir, self.cell)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut read = String::new();
+        self.offset += file.read_to_string(&mut read)? as u64;
+        self.partial.push_str(&read);
+
+        // Packet lines are `<stream>, <dts>, <pts>, <duration>, <size>, <crc>`, after `#` headers
+        let complete = self.partial.rfind('\n').map_or(0, |end| end + 1);
+        let lines: String = self.partial.drain(..complete).collect();
+        for line in lines.lines().filter(|line| !line.starts_with('#')) {
+            if let Some(size) = line
+                .split(',')
+                .nth(4)
+                .and_then(|size| size.trim().parse().ok())
+            {
+                self.recent.push_back((now, size));
+                self.last_arrival = Some(now);
+            }
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|(arrived, _)| now.duration_since(*arrived) > WINDOW)
+        {
+            self.recent.pop_front();
+        }
+
+        let Some(last_arrival) = self.last_arrival else {
+            return Ok(());
+        };
+        // Right after the start there is less than a window to average over
+        let window = WINDOW.min(now.duration_since(started)).as_secs_f64();
+        let bytes: u64 = self.recent.iter().map(|(_, size)| size).sum();
+        let stats = format!(
+            "{:.1} fps  {:.2} Mb/s  last frame {:.1} s ago",
+            self.recent.len() as f64 / window,
+            bytes as f64 * 8.0 / window / 1e6,
+            now.duration_since(last_arrival).as_secs_f64()
+        );
+        write_stats(dir, self.cell, &stats)
+    }
+}
+
+/// Replaces the stats of `cell` in one step, so drawtext never reads a half-written file
+#[cfg(feature = "render")]
+fn write_stats(dir: &Path, cell: usize, stats: &str) -> io::Result<()> {
+    let path = text_path(dir, cell);
+    let partial = path.with_extension("txt.partial");
+    fs::write(&partial, stats)?;
+    fs::rename(&partial, &path)
+}
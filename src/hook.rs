@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs a user-provided command template for an input file and returns what it printed.
+///
+/// The template is split into words like a shell would (with single and double quotes, but no
+/// other expansions), and `{input}` is replaced with the input's path in every word, as is
+/// `{output}` with `output` when a file is expected of the command. No shell is involved, so
+/// paths never need quoting.
+pub fn run_hook(
+    template: &str,
+    input: &Path,
+    output: Option<&Path>,
+) -> Result<String, Box<dyn Error>> {
+    let input = input.to_string_lossy();
+    let output = output.map(Path::to_string_lossy);
+    let words: Vec<String> = split_words(template)?
+        .into_iter()
+        .map(|word| match &output {
+            Some(output) => word.replace("{input}", &input).replace("{output}", output),
+            None => word.replace("{input}", &input),
+        })
+        .collect();
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| format!("Empty command '{}'", template))?;
+
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Cannot run '{}': {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'{}' failed for {}: {}",
+            program,
+            input,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Splits a command line into words at unquoted whitespace
+pub fn split_words(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in '{}'", command));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The source feeding a single cell of the grid.
+///
+/// Most cells are plain video files, but a cell can also be a "virtual" input that is
+/// composed from other cells of the same grid, or generated from an audio file.
+#[derive(Debug, Clone)]
+pub enum InputSource {
+    /// A video file on disk
+    File(PathBuf),
+    /// An audio file on disk, drawn by an audio visualizer
+    Audio {
+        path: PathBuf,
+        visualizer: Visualizer,
+    },
+    /// Overlays the `overlay` cell at `opacity` on top of the `base` cell (`onion:1,2:0.5`).
+    /// Cell numbers are 1-based, in reading order.
+    Onion {
+        base: usize,
+        overlay: usize,
+        opacity: f64,
+    },
+    /// A magnified region of another cell's source (`--magnify 2:x:y:w:h`)
+    Magnify { cell: usize, region: Region },
+    /// A cell left black, keeping its position in the grid (`empty`)
+    Empty,
+}
+
+/// A rectangle within a video frame, in source pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses a region of the form `x:y:w:h`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<u32> = s
+            .split(':')
+            .map(u32::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid region '{}', expected x:y:w:h", s))?;
+        let [x, y, width, height] = values[..] else {
+            return Err(format!("Invalid region '{}', expected x:y:w:h", s));
+        };
+        if width == 0 || height == 0 {
+            return Err(format!("Region '{}' is empty", s));
+        }
+        Ok(Region {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// How an audio input is drawn in its cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Visualizer {
+    /// The audio's waveform
+    Waveform,
+    /// A scrolling spectrogram of the audio
+    Spectrogram,
+}
+
+/// Container metadata cells can be labeled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LabelSource {
+    /// The title of the file
+    Title,
+    /// When the file was recorded
+    CreationTime,
+    /// The codec of the cell's video stream
+    Codec,
+}
+
+impl InputSource {
+    /// Returns the path of the file this input reads, if it reads one
+    pub fn as_file(&self) -> Option<&PathBuf> {
+        match self {
+            InputSource::File(path) | InputSource::Audio { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSource::File(path) | InputSource::Audio { path, .. } => {
+                write!(f, "{}", path.display())
+            }
+            InputSource::Onion {
+                base,
+                overlay,
+                opacity,
+            } => write!(f, "onion:{},{}:{}", base, overlay, opacity),
+            InputSource::Magnify { cell, region } => write!(f, "magnify:{}:{}", cell, region),
+            InputSource::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+impl FromStr for InputSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "empty" {
+            return Ok(InputSource::Empty);
+        }
+        let Some(spec) = s.strip_prefix("onion:") else {
+            return Ok(InputSource::File(PathBuf::from(s)));
+        };
+
+        // onion:<base>,<overlay>[:<opacity>]
+        let (cells, opacity) = match spec.split_once(':') {
+            Some((cells, opacity)) => (cells, opacity),
+            None => (spec, "0.5"),
+        };
+        let (base, overlay) = cells
+            .split_once(',')
+            .ok_or_else(|| format!("Invalid onion input '{}', expected onion:1,2:0.5", s))?;
+
+        let base = parse_cell_number(base)?;
+        let overlay = parse_cell_number(overlay)?;
+        if base == overlay {
+            return Err(format!("Onion input '{}' overlays a cell on itself", s));
+        }
+
+        let opacity =
+            f64::from_str(opacity).map_err(|_| format!("Invalid onion opacity '{}'", opacity))?;
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(format!(
+                "Onion opacity must be between 0 and 1, got {}",
+                opacity
+            ));
+        }
+
+        Ok(InputSource::Onion {
+            base,
+            overlay,
+            opacity,
+        })
+    }
+}
+
+/// Parses a 1-based cell number
+pub fn parse_cell_number(s: &str) -> Result<usize, String> {
+    match usize::from_str(s.trim()) {
+        Ok(n) if n >= 1 => Ok(n),
+        _ => Err(format!("Invalid cell number '{}', expected 1 or more", s)),
+    }
+}
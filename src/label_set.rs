@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::config_file;
+
+/// The labels of a grid in one language, read from a file named after the language, e.g.
+/// `labels.de.json` holding
+///
+/// ```json
+/// ["Vorher", "Nachher", null, "Referenz"]
+/// ```
+///
+/// with the labels in cell order and `null` for cells left unlabeled. TOML has no `null`, so
+/// `labels.de.toml` leaves cells unlabeled with empty labels:
+///
+/// ```toml
+/// labels = ["Vorher", "Nachher", "", "Referenz"]
+/// ```
+#[derive(Debug)]
+pub struct LabelSet {
+    /// The language, taken from the file name
+    pub language: String,
+    pub labels: Vec<Option<String>>,
+}
+
+/// The labels of a `.toml` label set
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlLabels {
+    labels: Vec<String>,
+}
+
+impl LabelSet {
+    /// Reads a label set, naming its language after the last part of the file stem: `de` for
+    /// both `labels.de.json` and `de.toml`
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let language = stem.rsplit('.').next().unwrap_or_default().to_string();
+        if language.is_empty() {
+            return Err(format!("Cannot tell the language of {}", path.display()).into());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let invalid = |e: String| format!("Invalid labels {}: {}", path.display(), e);
+        let labels = if config_file::is_toml(path) {
+            let file: TomlLabels = config_file::parse(path, &text).map_err(invalid)?;
+            file.labels
+                .into_iter()
+                .map(|label| Some(label).filter(|label| !label.is_empty()))
+                .collect()
+        } else {
+            config_file::parse(path, &text).map_err(invalid)?
+        };
+        Ok(LabelSet { language, labels })
+    }
+
+    /// Where the grid in this language is written: next to `output`, with the language before its
+    /// extension, e.g. `grid.de.mp4`
+    pub fn output_path(&self, output: &Path) -> PathBuf {
+        let mut name = output.file_stem().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(&self.language);
+        if let Some(extension) = output.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        output.with_file_name(name)
+    }
+
+    /// The `--label` options labeling the cells
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (index, label) in self.labels.iter().enumerate() {
+            if let Some(label) = label {
+                args.push("--label".to_string());
+                args.push(format!("{}:{}", index + 1, label));
+            }
+        }
+        args
+    }
+}
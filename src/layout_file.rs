@@ -0,0 +1,129 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::options::Args;
+
+/// The arrangement of a grid without its inputs, saved with `--save-layout` and applied to other
+/// grids with `--layout-file`, e.g.
+///
+/// ```json
+/// {
+///   "width": 1920,
+///   "height": 1080,
+///   "layout": "grid",
+///   "columns": 3,
+///   "gap": 8,
+///   "background_color": "#202020",
+///   "fit_cells": ["1:crop"],
+///   "label_position": "bottom"
+/// }
+/// ```
+///
+/// Anything left out keeps the default of its command line option. Enumerations are spelled as
+/// on the command line.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutFile {
+    width: Option<u32>,
+    height: Option<u32>,
+    layout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows: Option<u16>,
+    gap: Option<u32>,
+    border: Option<u32>,
+    border_color: Option<String>,
+    background_color: Option<String>,
+    pad: Option<String>,
+    fit: Option<String>,
+    /// Fits of single cells, as for `--fit-cell`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fit_cells: Vec<String>,
+    label_position: Option<String>,
+    label_scale: Option<f64>,
+    label_color: Option<String>,
+}
+
+/// The name of an enumeration value on the command line
+fn name(value: &impl ValueEnum) -> Option<String> {
+    Some(value.to_possible_value()?.get_name().to_string())
+}
+
+impl LayoutFile {
+    /// The arrangement of the grid `args` describe
+    pub fn from_args(args: &Args) -> Self {
+        LayoutFile {
+            width: Some(args.width),
+            height: Some(args.height),
+            layout: name(&args.layout),
+            columns: args.cols,
+            rows: args.rows,
+            gap: Some(args.gap),
+            border: Some(args.border),
+            border_color: Some(args.border_color.clone()),
+            background_color: Some(args.background_color.clone()),
+            pad: name(&args.pad),
+            fit: name(&args.fit),
+            fit_cells: args
+                .fit_cell
+                .iter()
+                .filter_map(|(cell, fit)| Some(format!("{}:{}", cell, name(fit)?)))
+                .collect(),
+            label_position: name(&args.label_position),
+            label_scale: Some(args.label_scale),
+            label_color: Some(args.label_color.clone()),
+        }
+    }
+
+    /// Reads a layout from a JSON file
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid layout {}: {}", path.display(), e))?)
+    }
+
+    /// Writes the layout as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        Ok(())
+    }
+
+    /// The command line options that arrange a grid like this
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut option = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{}", name));
+                args.push(value);
+            }
+        };
+        option("width", self.width.map(|width| width.to_string()));
+        option("height", self.height.map(|height| height.to_string()));
+        option("layout", self.layout.clone());
+        option("cols", self.columns.map(|columns| columns.to_string()));
+        option("rows", self.rows.map(|rows| rows.to_string()));
+        option("gap", self.gap.map(|gap| gap.to_string()));
+        option("border", self.border.map(|border| border.to_string()));
+        option("border-color", self.border_color.clone());
+        option("background-color", self.background_color.clone());
+        option("pad", self.pad.clone());
+        option("fit", self.fit.clone());
+        for fit in &self.fit_cells {
+            option("fit-cell", Some(fit.clone()));
+        }
+        option("label-position", self.label_position.clone());
+        option(
+            "label-scale",
+            self.label_scale.map(|scale| scale.to_string()),
+        );
+        option("label-color", self.label_color.clone());
+        args
+    }
+}
@@ -0,0 +1,615 @@
+//! Tiles videos into a grid using ffmpeg.
+//!
+//! [`VideoGrid`] builds a grid from a list of inputs and renders it. For full control, describe
+//! every [`Cell`] and the [`GridSettings`] yourself and pass them to [`create_video_grid`].
+//!
+//! Running ffmpeg and ffprobe, and everything else touching processes or files, is behind the
+//! default `render` feature. Without it the crate still lays out grids and plans their
+//! filtergraphs with [`plan`], e.g. in a browser where there is neither.
+use std::path::PathBuf;
+#[cfg(feature = "render")]
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+pub mod annotation;
+pub mod cell;
+#[cfg(feature = "render")]
+pub mod describe;
+pub mod encoder;
+mod error;
+mod escape;
+#[cfg(feature = "render")]
+mod frame;
+mod graph;
+mod health;
+pub mod input;
+pub mod locale;
+pub mod loudness;
+pub mod overlay_data;
+pub mod plan;
+#[cfg(feature = "render")]
+mod prescale;
+pub mod probe;
+#[cfg(feature = "render")]
+pub mod process;
+#[cfg(feature = "render")]
+mod progress;
+pub mod replay;
+pub mod settings;
+#[cfg(feature = "render")]
+pub mod sync;
+pub mod time;
+pub mod timeline;
+
+pub use cell::Cell;
+pub use error::VidGridError;
+pub use escape::shell_quote;
+#[cfg(feature = "render")]
+pub use frame::RgbaFrame;
+pub use graph::{grid_geometry, GridGeometry, Slot};
+pub use input::InputSource;
+pub use settings::GridSettings;
+
+use cell::Fit;
+#[cfg(feature = "render")]
+use health::HealthMonitor;
+#[cfg(feature = "render")]
+use input::LabelSource;
+use loudness::Loudness;
+#[cfg(feature = "render")]
+use probe::MediaInfo;
+#[cfg(feature = "render")]
+use settings::StreamTarget;
+use settings::{AudioMode, Layout, PadMode};
+
+/// Runs ffprobe on a file with `args` and returns what it printed, giving up once it has run for
+/// `timeout`
+#[cfg(feature = "render")]
+fn ffprobe(
+    video_path: &Path,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> Result<String, VidGridError> {
+    let failed = |message: String| VidGridError::Ffprobe {
+        path: video_path.to_path_buf(),
+        message,
+    };
+
+    let mut command = Command::new("ffprobe");
+    command.arg("-v").arg("error").args(args).arg(video_path);
+    let output = process::output_within(&mut command, timeout)
+        .map_err(|e| failed(e.to_string()))?
+        .ok_or_else(|| {
+            failed(format!(
+                "timed out after {} seconds",
+                timeout.unwrap_or_default().as_secs_f64()
+            ))
+        })?;
+
+    if !output.status.success() {
+        return Err(failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| failed("output is not UTF-8".to_string()))
+}
+
+/// Helper function to retrieve the frame rate of a video stream using ffprobe
+#[cfg(feature = "render")]
+pub fn get_video_framerate(video_path: &Path, stream: usize) -> Result<f64, VidGridError> {
+    let media = MediaInfo::probe(video_path)?;
+    Ok(media.video_stream(video_path, stream)?.fps)
+}
+
+/// Helper function to retrieve the duration of a video using ffprobe
+#[cfg(feature = "render")]
+pub fn get_video_duration(video_path: &Path) -> Result<f64, VidGridError> {
+    Ok(MediaInfo::probe(video_path)?.duration)
+}
+
+/// Helper function to read a label for a video stream from its container metadata using
+/// ffprobe, giving up once ffprobe has run for `timeout`. Returns `None` when the metadata is
+/// missing.
+#[cfg(feature = "render")]
+pub fn get_video_label(
+    video_path: &Path,
+    stream: usize,
+    source: LabelSource,
+    timeout: Option<Duration>,
+) -> Result<Option<String>, VidGridError> {
+    let output = ffprobe(
+        video_path,
+        &[
+            "-select_streams",
+            &format!("v:{}", stream),
+            "-show_entries",
+            "format_tags=title,creation_time:stream=codec_name",
+            "-of",
+            "json",
+        ],
+        timeout,
+    )?;
+
+    let probe: serde_json::Value = serde_json::from_str(&output).map_err(|e| {
+        VidGridError::Parse(format!(
+            "Invalid ffprobe output for {}: {}",
+            video_path.display(),
+            e
+        ))
+    })?;
+    let label = match source {
+        LabelSource::Title => &probe["format"]["tags"]["title"],
+        LabelSource::CreationTime => &probe["format"]["tags"]["creation_time"],
+        LabelSource::Codec => &probe["streams"][0]["codec_name"],
+    };
+
+    Ok(label
+        .as_str()
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string))
+}
+
+/// A hidden temporary path in the same directory as `output`, with the same extension so
+/// ffmpeg picks the same container
+#[cfg(feature = "render")]
+fn partial_output_path(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!(".{}.{}.partial", stem, std::process::id());
+    if let Some(extension) = output.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    output.with_file_name(name)
+}
+
+/// A command as it would be typed into a POSIX shell
+#[cfg(feature = "render")]
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|word| shell_quote(&word.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A change made to an input file on its own to fit it into its cell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Adjustment {
+    /// Frames were dropped or repeated to run the input at the frame rate of the grid
+    FrameRate { from: f64, to: f64 },
+    /// Colors were converted from another matrix to that of the grid
+    ColorMatrix { from: String, to: String },
+    /// Full range colors were squeezed into the limited range of the grid
+    ColorRange { from: String, to: String },
+    /// Colors were converted from other primaries to those of the grid
+    ColorPrimaries { from: String, to: String },
+    /// The input was turned clockwise by this many degrees to stand upright
+    Rotate { degrees: u32 },
+    /// The input was transcoded to an intermediate at the cell size before compositing
+    Prescale,
+    /// Frames were scaled to the cell, keeping their aspect ratio and padded to fill it unless
+    /// cropped or stretched
+    Scale {
+        width: u32,
+        height: u32,
+        fit: Fit,
+        pad: PadMode,
+    },
+}
+
+/// Values computed while creating a grid
+#[derive(Debug, Clone)]
+pub struct GridInfo {
+    /// The changes made to the video file of each cell, in cell order. Empty for other cells
+    pub adjustments: Vec<Vec<Adjustment>>,
+    /// Duration of the output in seconds
+    pub duration: u32,
+    /// Frame rate of the output
+    pub framerate: f64,
+    /// How loud the audio of each cell that is heard was, in cell order, when measured
+    pub loudness: Vec<Option<Loudness>>,
+}
+
+/// Creates a video grid from a list of cells.
+///
+/// This function takes the cells' inputs, adjusts their frame rates and durations as specified,
+/// and combines them into a single output video arranged in a grid layout. Four cells make a
+/// 2x2 grid; other counts are laid out in as square a grid as possible, with leftover positions
+/// left black. The output video
+/// will have a resolution defined by `settings.width` and `settings.height`, and its duration
+/// will be the lesser of the longest input video or `settings.duration`.
+///
+/// Each input's color matrix is probed and converted to BT.709 while scaling, so mixing SD and
+/// HD sources does not shift colors. The composite is then converted to the requested output
+/// colorspace and range, and tagged accordingly.
+///
+/// Inputs are usually video files, but a cell may also be a virtual input such as
+/// `onion:1,2:0.5`, which overlays cell 2 at 50% opacity on top of cell 1, or a magnified
+/// region of another cell. Each cell may
+/// additionally carry effects such as a motion trail.
+///
+/// # Arguments
+///
+/// * `cells` - The cells, in reading order (left to right, then top to bottom).
+/// * `settings` - Output resolution, duration, frame rate, color and path.
+///
+/// # Returns
+///
+/// * `Result<GridInfo, VidGridError>` - The computed duration and frame rate on success, Err otherwise.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Any of the input video paths are invalid or inaccessible.
+/// - A virtual input refers to a cell that is not a video file.
+/// - `ffprobe` or `ffmpeg` commands fail to execute.
+/// - There is an issue with processing the video streams.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use vidgrid::settings::{
+///     AudioMode, ColorRange, ColorSpace, Layout, PadMode, RedactStyle, ShortInputs,
+/// };
+/// use vidgrid::{create_video_grid, Cell, GridSettings, InputSource};
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     create_video_grid(
+///         &[
+///             Cell::new(InputSource::File(PathBuf::from("video1.mp4"))),
+///             Cell::new(InputSource::File(PathBuf::from("video2.mp4"))),
+///             Cell::new(InputSource::File(PathBuf::from("video3.mp4"))),
+///             Cell::new("onion:1,2:0.5".parse()?),
+///         ],
+///         &GridSettings {
+///             width: 1920,
+///             height: 1080,
+///             duration: 60,
+///             max_framerate: 60.0,
+///             color_range: ColorRange::Limited,
+///             colorspace: ColorSpace::Bt709,
+///             pad: PadMode::Black,
+///             gap: 0,
+///             border: 0,
+///             border_color: "white".to_string(),
+///             background_color: "black".to_string(),
+///             low_memory: false,
+///             threads: None,
+///             input_timeout: None,
+///             stream_gateway: None,
+///             limits: None,
+///             prescale: false,
+///             metadata: Vec::new(),
+///             columns: None,
+///             rows: None,
+///             layout: Layout::Grid,
+///             compare: None,
+///             timeline_strip: None,
+///             short_inputs: ShortInputs::Hold,
+///             audio: AudioMode::Mix,
+///             normalize_audio: true,
+///             show_mute_icons: false,
+///             redact_style: RedactStyle::Blur,
+///             rotate: None,
+///             interlace: None,
+///             atomic: true,
+///             still: None,
+///             animation: None,
+///             thumb_track: None,
+///             audio_stems: None,
+///             frame_hashes: None,
+///             measure_loudness: false,
+///             health_overlay: false,
+///             no_signal_after: None,
+///             replay_buffer: None,
+///             segments: None,
+///             encoder: Default::default(),
+///             print_commands: false,
+///             dry_run: false,
+///             progress: None,
+///             quiet: false,
+///             output_path: PathBuf::from("output.mp4"),
+///         },
+///     )?;
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "render")]
+pub fn create_video_grid(
+    cells: &[Cell],
+    settings: &GridSettings,
+) -> Result<GridInfo, VidGridError> {
+    // Check the encoder before any work is done. Stills and animations are written by the
+    // encoder of their format.
+    if settings.still.is_none() && settings.animation.is_none() {
+        settings.encoder.check_available()?;
+    }
+
+    // Step 1: Probe All Inputs
+    let mut media = Vec::new();
+    for path in cells.iter().filter_map(|cell| cell.source.as_file()) {
+        media.push(MediaInfo::probe_within(path, settings.input_timeout)?);
+    }
+    let scratch = if settings.prescale {
+        Some(prescale::ScratchDir::create("prescale")?)
+    } else {
+        None
+    };
+    // Live stats of the video files are drawn from where the health monitor writes them
+    let health_dir = settings
+        .health_overlay
+        .then(|| std::env::temp_dir().join(format!("vidgrid_health_{}", std::process::id())));
+    // So is the loudness of the cells heard, when it is measured
+    let loudness_dir = settings
+        .measure_loudness
+        .then(|| std::env::temp_dir().join(format!("vidgrid_loudness_{}", std::process::id())));
+    let grid = graph::build(
+        cells,
+        settings,
+        &graph::Inputs {
+            media: &media,
+            scratch: scratch.as_ref().map(prescale::ScratchDir::path),
+            health_dir: health_dir.as_deref(),
+            loudness_dir: loudness_dir.as_deref(),
+            partial_path: settings
+                .atomic
+                .then(|| partial_output_path(&settings.output_path)),
+        },
+    )?;
+    let mut info = grid.info;
+
+    // Optionally shrink the video inputs to the cell size up front, in parallel, so the main
+    // filtergraph only handles small frames
+    let prescales: Vec<Command> = grid
+        .prescales
+        .iter()
+        .map(|prescale| {
+            prescale::prescale_command(
+                &prescale.input,
+                prescale.stream,
+                &prescale.input_args,
+                prescale.width,
+                prescale.height,
+                &prescale.intermediate,
+            )
+        })
+        .collect();
+    let mut command = Command::new("ffmpeg");
+    command.args(&grid.args);
+    // Printed to stderr, as stdout may be carrying the grid itself
+    if settings.print_commands || settings.dry_run {
+        for prescale in &prescales {
+            eprintln!("{}\n", command_line(prescale));
+        }
+        match &grid.gateway {
+            Some(gateway) => eprintln!(
+                "{} | {}\n",
+                command_line(&command),
+                command_line(Command::new(&gateway[0]).args(&gateway[1..]))
+            ),
+            None => eprintln!("{}\n", command_line(&command)),
+        }
+        eprintln!("Filtergraph:");
+        for filter in graph::filtergraph_lines(&grid.filter_complex) {
+            eprintln!("  {}", filter);
+        }
+    }
+    if settings.dry_run {
+        return Ok(info);
+    }
+    if !prescales.is_empty() {
+        prescale::run_parallel(prescales, settings.threads, settings.input_timeout)?;
+    }
+
+    // Step 2: Execute the ffmpeg Command
+    // Watched until the grid is done, when dropping the monitor stops it
+    let _health = match health_dir {
+        Some(dir) => Some(HealthMonitor::start(dir, grid.health_cells)?),
+        None => None,
+    };
+    if let Some(dir) = &loudness_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut gateway = match &grid.gateway {
+        Some(gateway) => {
+            let mut child = Command::new(&gateway[0])
+                .args(&gateway[1..])
+                .stdin(Stdio::piped())
+                .spawn()?;
+            command.stdout(child.stdin.take().expect("the gateway's input is piped"));
+            Some(child)
+        }
+        None => None,
+    };
+    // Progress is reported on standard output, which a stream to it can't share
+    let to_stdout = matches!(grid.stream, Some(StreamTarget::Stdout | StreamTarget::Whip));
+    let status = match settings.progress {
+        Some(format) if !settings.quiet && !to_stdout => {
+            progress::run(&mut command, grid.duration as f64, format)
+        }
+        _ => command.status(),
+    };
+    // The gateway sees the stream end once ffmpeg's end of the pipe is closed with the command
+    drop(command);
+    if let Some(mut gateway) = gateway.take() {
+        let gateway_status = gateway.wait()?;
+        // A gateway failing because ffmpeg did is no news
+        if !gateway_status.success() && matches!(status, Ok(status) if status.success()) {
+            return Err(VidGridError::Io(std::io::Error::other(format!(
+                "The WHIP gateway failed ({})",
+                gateway_status
+            ))));
+        }
+    }
+
+    let encoded = matches!(status, Ok(status) if status.success());
+    if grid.stream.is_none() && grid.encode_path != settings.output_path {
+        if encoded {
+            std::fs::rename(&grid.encode_path, &settings.output_path)?;
+        } else {
+            let _ = std::fs::remove_file(&grid.encode_path);
+        }
+    }
+    // Read even after a failure, which would leave the logs behind
+    if let Some(dir) = &loudness_dir {
+        for &index in &grid.audio_cells {
+            info.loudness[index] = loudness::read_stats(&loudness::stats_path(dir, index + 1));
+        }
+        let _ = std::fs::remove_dir(dir);
+    }
+    let status = status?;
+    if !status.success() {
+        return Err(VidGridError::Ffmpeg {
+            task: "create the grid".to_string(),
+            status,
+        });
+    }
+    Ok(info)
+}
+
+/// Builds and renders a grid of videos.
+///
+/// Inputs are laid out in reading order in as square a grid as possible, unless a layout is
+/// given. Settings not set on the builder keep the defaults of [`GridSettings`].
+///
+/// ```no_run
+/// let info = vidgrid::VideoGrid::new()
+///     .input("video1.mp4")
+///     .input("video2.mp4")
+///     .input("video3.mp4")
+///     .dimensions(1280, 720)
+///     .duration(30)
+///     .max_framerate(30.0)
+///     .output("grid.mp4")
+///     .render()?;
+/// println!("Rendered {} seconds at {} fps", info.duration, info.framerate);
+/// # Ok::<(), vidgrid::VidGridError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VideoGrid {
+    cells: Vec<Cell>,
+    settings: GridSettings,
+}
+
+impl VideoGrid {
+    pub fn new() -> Self {
+        VideoGrid::default()
+    }
+
+    /// Adds a cell playing a video file
+    pub fn input(self, path: impl Into<PathBuf>) -> Self {
+        self.cell(Cell::new(InputSource::File(path.into())))
+    }
+
+    /// Adds a cell, e.g. a virtual input or a video file with effects
+    pub fn cell(mut self, cell: Cell) -> Self {
+        self.cells.push(cell);
+        self
+    }
+
+    /// Sets the resolution of the output
+    pub fn dimensions(mut self, width: u32, height: u32) -> Self {
+        self.settings.width = width;
+        self.settings.height = height;
+        self
+    }
+
+    /// Sets the number of columns and rows of the grid
+    pub fn layout(mut self, columns: usize, rows: usize) -> Self {
+        self.settings.columns = Some(columns);
+        self.settings.rows = Some(rows);
+        self
+    }
+
+    /// Arranges the cells in a preset other than a grid, e.g. picture-in-picture
+    pub fn preset(mut self, layout: Layout) -> Self {
+        self.settings.layout = layout;
+        self
+    }
+
+    /// Sets the maximum duration of the output, in seconds
+    pub fn duration(mut self, seconds: u32) -> Self {
+        self.settings.duration = seconds;
+        self
+    }
+
+    /// Sets the maximum frame rate of the output
+    pub fn max_framerate(mut self, framerate: f64) -> Self {
+        self.settings.max_framerate = framerate;
+        self
+    }
+
+    /// Sets where the audio of the output is taken from
+    pub fn audio(mut self, audio: AudioMode) -> Self {
+        self.settings.audio = audio;
+        self
+    }
+
+    /// Sets the path the output is written to
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.output_path = path.into();
+        self
+    }
+
+    /// Replaces all settings at once, e.g. to change ones the builder has no method for
+    pub fn settings(mut self, settings: GridSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Renders the grid with ffmpeg
+    #[cfg(feature = "render")]
+    pub fn render(&self) -> Result<GridInfo, VidGridError> {
+        create_video_grid(&self.cells, &self.settings)
+    }
+
+    /// Renders only the frame of the grid `at` into it and returns its pixels, e.g. for a
+    /// preview of the layout while it is edited. Nothing is encoded, so this takes about as long
+    /// as seeking the inputs.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let frame = vidgrid::VideoGrid::new()
+    ///     .input("video1.mp4")
+    ///     .input("video2.mp4")
+    ///     .dimensions(640, 360)
+    ///     .render_frame(Duration::from_secs(5))?;
+    /// assert_eq!(frame.pixels.len(), 640 * 360 * 4);
+    /// # Ok::<(), vidgrid::VidGridError>(())
+    /// ```
+    #[cfg(feature = "render")]
+    pub fn render_frame(&self, at: Duration) -> Result<RgbaFrame, VidGridError> {
+        // ffmpeg writes BMPs uncompressed, so they are read back without an image decoder
+        let path = std::env::temp_dir().join(format!(
+            "vidgrid_frame_{}_{}.bmp",
+            std::process::id(),
+            FRAME_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let rendered = self
+            .render_frame_to(at, &path)
+            .and_then(|_| frame::read_bmp(&path));
+        let _ = std::fs::remove_file(&path);
+        rendered
+    }
+
+    /// Renders only the frame of the grid `at` into it to an image file, e.g. a PNG
+    #[cfg(feature = "render")]
+    pub fn render_frame_to(&self, at: Duration, path: &Path) -> Result<GridInfo, VidGridError> {
+        let settings = self.settings.for_still(at.as_secs_f64(), path);
+        create_video_grid(&self.cells, &settings)
+    }
+}
+
+/// Tells apart the temporary files of frames rendered at the same time
+#[cfg(feature = "render")]
+static FRAME_COUNTER: AtomicUsize = AtomicUsize::new(0);
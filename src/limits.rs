@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Limits above this are how cgroup v1 spells "unlimited"
+const UNLIMITED: u64 = 1 << 60;
+
+/// Output frames the memory of a container must hold for a grid to be rendered normally. The
+/// cells' buffers and the encoder's lookahead hold about this many between them.
+const FRAMES_IN_FLIGHT: u64 = 256;
+
+/// CPU time and memory a container (or any other cgroup) allows vidgrid, as found in
+/// `/sys/fs/cgroup`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ResourceLimits {
+    /// How many CPUs' worth of time the container gets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<f64>,
+    /// Most memory the container may use, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Finds the limits of the cgroup vidgrid runs in and of every cgroup above it, keeping the
+    /// tightest. Limits that can't be read are treated as absent.
+    pub fn detect() -> Self {
+        let mut limits = ResourceLimits::default();
+        // cgroup v2 keeps every controller in one hierarchy, cgroup v1 each in its own
+        let v2 = cgroup_dirs(None);
+        for dir in v2.iter().chain(&cgroup_dirs(Some("cpu"))) {
+            let cpu_max = read(&dir.join("cpu.max")).or_else(|| {
+                let quota = read(&dir.join("cpu.cfs_quota_us"))?;
+                let period = read(&dir.join("cpu.cfs_period_us"))?;
+                Some(format!("{} {}", quota, period))
+            });
+            if let Some(cpus) = cpu_max.and_then(|max| parse_cpu_max(&max)) {
+                limits.cpus = Some(limits.cpus.map_or(cpus, |limit| limit.min(cpus)));
+            }
+        }
+        for dir in v2.iter().chain(&cgroup_dirs(Some("memory"))) {
+            let memory_max =
+                read(&dir.join("memory.max")).or_else(|| read(&dir.join("memory.limit_in_bytes")));
+            if let Some(memory) = memory_max.and_then(|max| parse_bytes(&max)) {
+                limits.memory = Some(limits.memory.map_or(memory, |limit| limit.min(memory)));
+            }
+        }
+        limits
+    }
+
+    /// Whether any limit was found
+    pub fn is_limited(&self) -> bool {
+        self.cpus.is_some() || self.memory.is_some()
+    }
+
+    /// How many threads keep the CPUs of the container busy without oversubscribing them
+    pub fn threads(&self) -> Option<usize> {
+        self.cpus.map(|cpus| (cpus.ceil() as usize).max(1))
+    }
+
+    /// Whether a grid of `width` x `height` must be rendered in low-memory mode to fit the
+    /// container's memory
+    pub fn needs_low_memory(&self, width: u32, height: u32) -> bool {
+        // YUV 4:2:0 takes a byte and a half per pixel
+        let frame = u64::from(width) * u64::from(height) * 3 / 2;
+        self.memory
+            .is_some_and(|memory| memory < frame * FRAMES_IN_FLIGHT)
+    }
+}
+
+/// The directories of the cgroup this process is in and of its ancestors, in the cgroup v1
+/// hierarchy of `controller` or in the cgroup v2 hierarchy. Containers see their own group at the
+/// root of each hierarchy.
+fn cgroup_dirs(controller: Option<&str>) -> Vec<PathBuf> {
+    let Some(groups) = read(Path::new("/proc/self/cgroup")) else {
+        return Vec::new();
+    };
+    // Each line is `<id>:<controllers>:<path>`, with no controllers for cgroup v2
+    let group = groups.lines().find_map(|line| {
+        let mut fields = line.splitn(3, ':').skip(1);
+        let (controllers, path) = (fields.next()?, fields.next()?);
+        let matches = match controller {
+            Some(controller) => controllers.split(',').any(|name| name == controller),
+            None => controllers.is_empty(),
+        };
+        matches.then_some(path)
+    });
+    let Some(group) = group else {
+        return Vec::new();
+    };
+
+    let mut dir = Path::new("/sys/fs/cgroup").join(controller.unwrap_or(""));
+    let mut dirs = vec![dir.clone()];
+    for part in group.split('/').filter(|part| !part.is_empty()) {
+        dir.push(part);
+        dirs.push(dir.clone());
+    }
+    dirs
+}
+
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+}
+
+/// Parses a CPU quota of the form `<quota> <period>`, where a quota of `max` or -1 is no limit
+fn parse_cpu_max(max: &str) -> Option<f64> {
+    let (quota, period) = max.split_once(' ')?;
+    let quota: f64 = quota.parse().ok().filter(|&quota: &f64| quota > 0.0)?;
+    let period: f64 = period
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&period: &f64| period > 0.0)?;
+    Some(quota / period)
+}
+
+/// Parses a memory limit in bytes, where `max` or a huge number is no limit
+fn parse_bytes(limit: &str) -> Option<u64> {
+    limit.parse().ok().filter(|&bytes| bytes < UNLIMITED)
+}
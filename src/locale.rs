@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+/// Conventions for numbers, units, dates and times in burned-in text, e.g. telemetry readouts
+/// and clocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    /// Separates the integer and fractional parts of numbers
+    pub decimal_separator: char,
+    /// Whether speeds are given in miles rather than kilometres per hour
+    pub miles: bool,
+    /// How dates are written, as a strftime format, e.g. `%d.%m.%Y`
+    pub date_format: &'static str,
+    /// Whether times of day are given on a 12-hour clock with AM and PM
+    pub twelve_hour: bool,
+}
+
+/// Languages writing `12,5` rather than `12.5`
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "is", "it", "lt",
+    "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk", "vi",
+];
+
+/// Regions whose road speeds are signposted in miles per hour
+const MILES_REGIONS: &[&str] = &["GB", "LR", "MM", "US"];
+
+/// Languages writing dates as `07.05.2024`
+const DOTTED_DATE_LANGUAGES: &[&str] = &[
+    "bg", "cs", "da", "de", "et", "fi", "hr", "is", "lv", "nb", "nn", "no", "pl", "ro", "ru", "sk",
+    "sl", "sr", "tr", "uk",
+];
+
+/// Languages writing dates as `2024-05-07`
+const ISO_DATE_LANGUAGES: &[&str] = &["ja", "ko", "lt", "sv", "zh"];
+
+/// Regions writing dates month first, as `05/07/2024`
+const MONTH_FIRST_REGIONS: &[&str] = &["PH", "US"];
+
+/// Regions telling the time on a 12-hour clock
+const TWELVE_HOUR_REGIONS: &[&str] = &["AU", "CA", "EG", "IN", "NZ", "PH", "PK", "US"];
+
+impl Default for Locale {
+    /// The conventions used when no locale is given: `12.5 km/h` and `2024-05-07 13:15:02`
+    fn default() -> Self {
+        Locale {
+            decimal_separator: '.',
+            miles: false,
+            date_format: "%Y-%m-%d",
+            twelve_hour: false,
+        }
+    }
+}
+
+impl Locale {
+    /// Formats a number with a fixed number of decimals
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value);
+        formatted.replace('.', &self.decimal_separator.to_string())
+    }
+
+    /// The time of day as a strftime format ending in the seconds, so fractions can follow them.
+    /// AM and PM go after the fractions
+    pub fn time_format(&self) -> &'static str {
+        if self.twelve_hour {
+            "%I:%M:%S"
+        } else {
+            "%H:%M:%S"
+        }
+    }
+
+    /// Formats a speed given in km/h in the locale's unit
+    pub fn speed(&self, kmh: f64) -> String {
+        if self.miles {
+            format!("{} mph", self.number(kmh / 1.609344, 1))
+        } else {
+            format!("{} km/h", self.number(kmh, 1))
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parses a language tag such as `de-DE`, `en_US.UTF-8` or `fr`. `C` and `POSIX` give the
+    /// default conventions.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Drop the encoding and modifier of POSIX locale names
+        let tag = s.split(['.', '@']).next().unwrap_or(s);
+        if tag.eq_ignore_ascii_case("C") || tag.eq_ignore_ascii_case("POSIX") {
+            return Ok(Locale::default());
+        }
+
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next().unwrap_or("").to_ascii_lowercase();
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return Err(format!("Invalid locale '{}', expected e.g. de-DE", s));
+        }
+        // The region is the first two letter subtag, skipping scripts such as `Latn`
+        let region = subtags
+            .find(|subtag| subtag.len() == 2)
+            .map(|region| region.to_ascii_uppercase());
+
+        Ok(Locale {
+            decimal_separator: if DECIMAL_COMMA_LANGUAGES.contains(&language.as_str()) {
+                ','
+            } else {
+                '.'
+            },
+            miles: region
+                .as_deref()
+                .is_some_and(|region| MILES_REGIONS.contains(&region)),
+            date_format: match region.as_deref() {
+                Some(region) if MONTH_FIRST_REGIONS.contains(&region) => "%m/%d/%Y",
+                _ if DOTTED_DATE_LANGUAGES.contains(&language.as_str()) => "%d.%m.%Y",
+                _ if ISO_DATE_LANGUAGES.contains(&language.as_str()) => "%Y-%m-%d",
+                _ => "%d/%m/%Y",
+            },
+            twelve_hour: region
+                .as_deref()
+                .is_some_and(|region| TWELVE_HOUR_REGIONS.contains(&region)),
+        })
+    }
+}
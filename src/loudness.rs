@@ -0,0 +1,60 @@
+#[cfg(feature = "render")]
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::escape::escape_filter_option;
+
+/// How loud a cell's audio was over the whole grid, measured per EBU R 128
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// Highest true peak of any channel in dBTP
+    pub true_peak: f64,
+}
+
+/// Where the loudness of cell `cell`, counted from 1, is logged in `dir` while the grid renders
+pub(crate) fn stats_path(dir: &Path, cell: usize) -> PathBuf {
+    dir.join(format!("cell{}.txt", cell))
+}
+
+/// The filters measuring the audio read from `pad` and logging the measurements to `path`, for
+/// each frame of audio, without passing the audio on
+pub(crate) fn measure_filter(pad: &str, path: &Path) -> String {
+    format!(
+        "{pad}ebur128=metadata=1:peak=true,ametadata=mode=print:file={file},anullsink;",
+        file = escape_filter_option(&path.to_string_lossy())
+    )
+}
+
+/// Reads the loudness logged to `path` and removes the log. The integrated loudness is the last
+/// one logged, as it is measured over everything before it. `None` if nothing loud enough to
+/// measure was heard
+#[cfg(feature = "render")]
+pub(crate) fn read_stats(path: &Path) -> Option<Loudness> {
+    let stats = fs::read_to_string(path).ok();
+    let _ = fs::remove_file(path);
+    let mut integrated = None;
+    let mut true_peak: Option<f64> = None;
+    for line in stats?.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse::<f64>() else {
+            continue;
+        };
+        match key.strip_prefix("lavfi.r128.") {
+            Some("I") => integrated = Some(value),
+            Some(peak) if peak.starts_with("true_peaks_ch") => {
+                true_peak = Some(true_peak.map_or(value, |highest| highest.max(value)));
+            }
+            _ => {}
+        }
+    }
+    Some(Loudness {
+        integrated: integrated.filter(|value| value.is_finite())?,
+        true_peak: true_peak.filter(|value| value.is_finite())?,
+    })
+}
@@ -1,254 +1,1223 @@
 // main.rs
 use std::error::Error;
-use std::path::Path;
-use std::process::Command;
-use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
+mod assertion;
+mod audio_grid;
+mod batch;
+mod blind;
+mod chapters;
+mod checksum;
+mod config_file;
+mod diff;
+mod discover;
+mod edl;
+mod hook;
+mod label_set;
+mod layout_file;
+mod limits;
+mod manifest;
 mod options;
+mod pick;
+mod prompt;
+mod provenance;
+mod publish;
+mod shuffle;
+mod summary;
+mod sweep;
+mod telemetry;
+mod validate;
+mod wizard;
 
-/// Helper function to retrieve the frame rate of a video using ffprobe
-fn get_video_framerate(video_path: &Path) -> Result<f64, Box<dyn Error>> {
-    let output = Command::new("ffprobe")
-        .arg("-v")
-        .arg("error")
-        .arg("-select_streams")
-        .arg("v:0")
-        .arg("-show_entries")
-        .arg("stream=r_frame_rate")
-        .arg("-of")
-        .arg("default=noprint_wrappers=1:nokey=1")
-        .arg(video_path)
-        .output()?;
-
-    if !output.status.success() {
-        return Err(format!("ffprobe failed for {}", video_path.display()).into());
-    }
-
-    let fps_str = String::from_utf8(output.stdout)?.trim().to_string();
-
-    // Parse the frame rate string, which might be in the form "30000/1001"
-    let fps = if fps_str.contains('/') {
-        let parts: Vec<&str> = fps_str.split('/').collect();
-        if parts.len() == 2 {
-            let numerator = f64::from_str(parts[0])?;
-            let denominator = f64::from_str(parts[1])?;
-            if denominator == 0.0 {
-                return Err(
-                    format!("Invalid frame rate denominator in {}", video_path.display()).into(),
-                );
-            }
-            numerator / denominator
-        } else {
-            return Err(format!("Invalid frame rate format: {}", fps_str).into());
+use vidgrid::annotation;
+use vidgrid::cell::{self, Cell};
+use vidgrid::describe;
+use vidgrid::encoder;
+use vidgrid::input::InputSource;
+use vidgrid::replay::ReplayBuffer;
+use vidgrid::settings::{self, GridSettings};
+use vidgrid::sync::SyncMode;
+use vidgrid::timeline;
+use vidgrid::{create_video_grid, get_video_label, overlay_data, VidGridError};
+
+/// An output path in the directory of the first input file, named after it
+fn default_output_next_to(inputs: &[InputSource]) -> PathBuf {
+    match inputs.iter().find_map(InputSource::as_file) {
+        Some(first) => {
+            let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+            first.with_file_name(format!("{}_grid.mp4", stem))
+        }
+        None => PathBuf::from("output.mp4"),
+    }
+}
+
+/// A fresh output path in `dir` named after the current local date and time
+fn timestamped_output_path(dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+
+    let stamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let mut path = dir.join(format!("vidgrid_{}.mp4", stamp));
+
+    // Two runs within the same second still get their own files
+    let mut attempt = 2;
+    while path.exists() {
+        path = dir.join(format!("vidgrid_{}_{}.mp4", stamp, attempt));
+        attempt += 1;
+    }
+
+    Ok(path)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(
+                e.downcast_ref::<vidgrid::VidGridError>()
+                    .map_or(1, vidgrid::VidGridError::exit_code),
+            )
         }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let argv: Vec<String> = std::env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let args: options::Args = clap::Parser::parse();
+
+    match &args.command {
+        Some(options::Command::AudioGrid(audio_args)) => audio_grid::run(audio_args),
+        Some(options::Command::Reveal { key }) => blind::reveal(key),
+        Some(options::Command::Append {
+            grid,
+            input,
+            output_path,
+            overrides,
+        }) => append(grid, input, output_path.as_deref(), overrides),
+        Some(options::Command::Batch(batch_args)) => batch::run(batch_args),
+        Some(options::Command::Pick(pick_args)) => pick::run(pick_args),
+        Some(options::Command::Replay { output, overrides }) => replay(output, overrides),
+        Some(options::Command::Diff(diff_args)) => diff::run(diff_args),
+        Some(options::Command::Sweep(sweep_args)) => sweep::run(sweep_args),
+        Some(options::Command::Plan { spec }) => {
+            let spec = std::fs::read_to_string(spec)
+                .map_err(|e| format!("Cannot read {}: {}", spec.display(), e))?;
+            println!("{}", vidgrid::plan::plan_json(&spec)?);
+            Ok(())
+        }
+        Some(options::Command::Validate { job }) => validate::run(job),
+        Some(options::Command::Wizard) => wizard::run(),
+        None if args.layout_file.is_some() => run_layout_file(&args, &argv),
+        None if !args.labels_file.is_empty() => run_languages(&args, &argv),
+        None if args.manifest.is_some() || args.template.is_some() => run_manifest(&args, &argv),
+        None if args.input_dir.is_some() => run_batches(&args, &argv),
+        None => run_grid(&args, &argv),
+    }
+}
+
+/// `path` with `_<number>` appended to its file stem, e.g. `output_001.mp4`
+fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}_{:03}", stem, number);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// Tiles the videos of `--input-dir` into as many grids as it takes. Each grid is rendered as if
+/// its videos had been given with `--input`, so its embedded job replays just that grid.
+fn run_batches(args: &options::Args, argv: &[String]) -> Result<(), Box<dyn Error>> {
+    let dir = args.input_dir.as_ref().expect("checked by the caller");
+    let videos = discover::find_videos(dir, args.glob.as_deref())?;
+    let per_grid = match (args.cols, args.rows) {
+        (Some(cols), Some(rows)) => usize::from(cols) * usize::from(rows),
+        _ => 4,
+    };
+
+    let output_path = match &args.output_path {
+        Some(path) => path.clone(),
+        None if args.timestamped_output => timestamped_output_path(&args.output_dir)?,
+        None if args.image => PathBuf::from("output.png"),
+        None => PathBuf::from("output.mp4"),
+    };
+
+    // Drop the options that differ between grids, then give them again per grid
+    let mut shared = Vec::new();
+    let mut words = argv.iter();
+    while let Some(word) = words.next() {
+        let name = word.split_once('=').map_or(word.as_str(), |(name, _)| name);
+        match name {
+            "--timestamped-output" => {}
+            "--input-dir" | "--glob" | "--output-path" | "-o" | "--summary" | "--key-path" => {
+                if !word.contains('=') {
+                    words.next();
+                }
+            }
+            _ if word.starts_with("-o") && !word.starts_with("--") => {}
+            _ => shared.push(word.clone()),
+        }
+    }
+
+    let batches: Vec<&[PathBuf]> = videos.chunks(per_grid).collect();
+    for (index, batch) in batches.iter().enumerate() {
+        let numbered = |path: &Path| match batches.len() {
+            1 => path.to_path_buf(),
+            _ => numbered_path(path, index + 1),
+        };
+
+        let mut batch_argv = shared.clone();
+        for video in batch.iter() {
+            batch_argv.push("--input".to_string());
+            batch_argv.push(video.to_string_lossy().into_owned());
+        }
+        batch_argv.push("--output-path".to_string());
+        batch_argv.push(numbered(&output_path).to_string_lossy().into_owned());
+        if let Some(summary) = &args.summary {
+            batch_argv.push("--summary".to_string());
+            batch_argv.push(numbered(summary).to_string_lossy().into_owned());
+        }
+        if let Some(key_path) = &args.key_path {
+            batch_argv.push("--key-path".to_string());
+            batch_argv.push(numbered(key_path).to_string_lossy().into_owned());
+        }
+
+        let batch_args: options::Args = clap::Parser::try_parse_from(
+            std::iter::once("vidgrid".to_string()).chain(batch_argv.iter().cloned()),
+        )?;
+        run_grid(&batch_args, &batch_argv)?;
+    }
+
+    Ok(())
+}
+
+/// Renders the grid once per label set of `--labels-file`, each labeled in its language and
+/// written to an output named after it
+fn run_languages(args: &options::Args, argv: &[String]) -> Result<(), Box<dyn Error>> {
+    // Every set is read before any grid is rendered, so a mistake in the last one shows up front
+    let mut sets = Vec::new();
+    for path in &args.labels_file {
+        sets.push(label_set::LabelSet::read(path)?);
+    }
+    let output_path = output_path(args, argv)?;
+
+    let mut shared = Vec::new();
+    let mut words = argv.iter();
+    while let Some(word) = words.next() {
+        match word.split_once('=').map_or(word.as_str(), |(name, _)| name) {
+            "--labels-file" if !word.contains('=') => {
+                words.next();
+            }
+            "--labels-file" | "--timestamped-output" => {}
+            _ => shared.push(word.clone()),
+        }
+    }
+
+    for set in &sets {
+        let mut language_argv = shared.clone();
+        language_argv.extend(set.to_args());
+        language_argv.push("--output-path".to_string());
+        language_argv.push(set.output_path(&output_path).to_string_lossy().into_owned());
+        if let Some(summary) = &args.summary {
+            language_argv.push("--summary".to_string());
+            language_argv.push(set.output_path(summary).to_string_lossy().into_owned());
+        }
+
+        let language_args: options::Args = clap::Parser::try_parse_from(
+            std::iter::once("vidgrid".to_string()).chain(language_argv.iter().cloned()),
+        )?;
+        run_grid(&language_args, &language_argv)?;
+    }
+
+    Ok(())
+}
+
+/// Renders the grid of a manifest or template, with the options of `argv` overriding the
+/// manifest's
+fn run_manifest(args: &options::Args, argv: &[String]) -> Result<(), Box<dyn Error>> {
+    let merged = manifest_args(args, argv, Path::new(""))?;
+    let merged_args: options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(merged.iter().cloned()),
+    )?;
+    run_grid(&merged_args, &merged)
+}
+
+/// The arguments a `--manifest` or `--template` stands for, followed by the other arguments of
+/// `argv`, which override them. The manifest's path is relative to `cwd`.
+fn manifest_args(
+    args: &options::Args,
+    argv: &[String],
+    cwd: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let (path, manifest) = match (&args.manifest, &args.template) {
+        (Some(path), _) => {
+            let path = cwd.join(path);
+            let manifest = manifest::Manifest::read(&path)?;
+            (path, manifest)
+        }
+        (None, Some(path)) => {
+            let path = cwd.join(path);
+            let manifest = manifest::Manifest::read_template(&path, &args.variables)?;
+            (path, manifest)
+        }
+        (None, None) => unreachable!("checked by the caller"),
+    };
+    let mut merged = manifest.to_args(path.parent().unwrap_or(Path::new("")))?;
+
+    let mut words = argv.iter();
+    while let Some(word) = words.next() {
+        match word.split_once('=').map_or(word.as_str(), |(name, _)| name) {
+            "--manifest" | "--template" | "--set" if !word.contains('=') => {
+                words.next();
+            }
+            "--manifest" | "--template" | "--set" => {}
+            _ => merged.push(word.clone()),
+        }
+    }
+    Ok(merged)
+}
+
+/// The arguments a `--layout-file` stands for, followed by the other arguments of `argv`, which
+/// override them. The layout file's path is relative to `cwd`.
+fn layout_file_args(
+    args: &options::Args,
+    argv: &[String],
+    cwd: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = args.layout_file.as_ref().expect("checked by the caller");
+    let mut merged = layout_file::LayoutFile::read(&cwd.join(path))?.to_args();
+
+    let mut words = argv.iter();
+    while let Some(word) = words.next() {
+        match word.as_str() {
+            "--layout-file" => {
+                words.next();
+            }
+            _ if word.starts_with("--layout-file=") => {}
+            _ => merged.push(word.clone()),
+        }
+    }
+    Ok(merged)
+}
+
+/// Applies a `--layout-file` by putting the options it stands for before the given ones, which
+/// override them. The grid's embedded job holds the options rather than the file.
+fn run_layout_file(args: &options::Args, argv: &[String]) -> Result<(), Box<dyn Error>> {
+    let merged = layout_file_args(args, argv, Path::new(""))?;
+    let merged_args: options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(merged.iter().cloned()),
+    )?;
+    if !merged_args.labels_file.is_empty() {
+        run_languages(&merged_args, &merged)
+    } else if merged_args.manifest.is_some() || merged_args.template.is_some() {
+        run_manifest(&merged_args, &merged)
+    } else if merged_args.input_dir.is_some() {
+        run_batches(&merged_args, &merged)
     } else {
-        f64::from_str(&fps_str)?
-    };
-
-    Ok(fps)
-}
-
-/// Helper function to retrieve the duration of a video using ffprobe
-fn get_video_duration(video_path: &Path) -> Result<u32, Box<dyn Error>> {
-    let output = Command::new("ffprobe")
-        .arg("-v")
-        .arg("error")
-        .arg("-show_entries")
-        .arg("format=duration")
-        .arg("-of")
-        .arg("default=noprint_wrappers=1:nokey=1")
-        .arg(video_path)
-        .output()?;
-
-    if !output.status.success() {
-        return Err(format!("ffprobe failed for {}", video_path.display()).into());
-    }
-
-    let dur_str = String::from_utf8(output.stdout)?.trim().to_string();
-
-    // Parse the duration string to f64 and then convert to u32 (seconds)
-    let dur_f64 = f64::from_str(&dur_str)?;
-    let dur_u32 = dur_f64.floor() as u32;
-
-    Ok(dur_u32)
-}
-
-/// Creates a 2x2 video grid from four input videos.
-///
-/// This function takes four input video files, adjusts their frame rates and durations as specified,
-/// and combines them into a single output video arranged in a 2x2 grid layout. The output video
-/// will have a resolution defined by `output_width` and `output_height`, and its duration will
-/// be the lesser of the longest input video or the specified `duration`.
-///
-/// # Arguments
-///
-/// * `vid1_path` - Path to the first video (top-left).
-/// * `vid2_path` - Path to the second video (top-right).
-/// * `vid3_path` - Path to the third video (bottom-left).
-/// * `vid4_path` - Path to the fourth video (bottom-right).
-/// * `duration` - Maximum duration of the output video in seconds.
-/// * `output_width` - Width of the output video.
-/// * `output_height` - Height of the output video.
-/// * `max_framerate` - Maximum frame rate for the output video.
-/// * `output_path` - Path to save the output video.
-///
-/// # Returns
-///
-/// * `Result<(), Box<dyn Error>>` - Ok on success, Err otherwise.
-///
-/// # Errors
-///
-/// Returns an error if:
-/// - Any of the input video paths are invalid or inaccessible.
-/// - `ffprobe` or `ffmpeg` commands fail to execute.
-/// - There is an issue with processing the video streams.
-///
-/// # Examples
-///
-/// ```rust
-/// use std::path::Path;
-/// use your_crate::create_video_grid;
-///
-/// fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     create_video_grid(
-///         Path::new("video1.mp4"),
-///         Path::new("video2.mp4"),
-///         Path::new("video3.mp4"),
-///         Path::new("video4.mp4"),
-///         60,
-///         1920,
-///         1080,
-///         60.0,
-///         Path::new("output.mp4"),
-///     )?;
-///     Ok(())
-/// }
-/// ```
-fn create_video_grid(
-    vid1_path: &Path,
-    vid2_path: &Path,
-    vid3_path: &Path,
-    vid4_path: &Path,
-    duration: u32,
-    output_width: u32,
-    output_height: u32,
-    max_framerate: f64,
-    output_path: &Path,
+        run_grid(&merged_args, &merged)
+    }
+}
+
+/// Re-runs the job embedded in an output, with `overrides` appended to its arguments. A grid
+/// that would replace the replayed output is written next to it as `<name>_replay.<ext>`.
+fn replay(output: &Path, overrides: &[String]) -> Result<(), Box<dyn Error>> {
+    let spec = provenance::JobSpec::read_from(output)?;
+    let replayed = std::fs::canonicalize(output)?;
+    std::env::set_current_dir(&spec.cwd)
+        .map_err(|e| format!("Cannot enter {}: {}", spec.cwd.display(), e))?;
+
+    let mut argv = spec.args;
+    argv.extend(overrides.iter().cloned());
+    let parse = |argv: &[String]| -> Result<options::Args, clap::Error> {
+        clap::Parser::try_parse_from(std::iter::once("vidgrid".to_string()).chain(argv.to_vec()))
+    };
+    let mut args = parse(&argv)?;
+    if args.command.is_some() {
+        return Err("The embedded job is not a grid job".into());
+    }
+
+    // ffmpeg can't write the output it is reading from
+    let resolved = std::fs::canonicalize(output_path(&args, &argv)?);
+    if resolved.is_ok_and(|path| path == replayed) {
+        let stem = replayed.file_stem().unwrap_or_default().to_string_lossy();
+        let mut sibling = replayed.with_file_name(format!("{}_replay", stem));
+        if let Some(ext) = replayed.extension() {
+            sibling.set_extension(ext);
+        }
+        argv.extend(["--output-path".to_string(), sibling.display().to_string()]);
+        args = parse(&argv)?;
+    }
+
+    run_grid(&args, &argv)
+}
+
+/// Explains how to get past a refused render
+fn grid_error(error: VidGridError) -> Box<dyn Error> {
+    match error {
+        VidGridError::LimitExceeded(message) => {
+            format!("{}. Pass --force to render it anyway", message).into()
+        }
+        e => e.into(),
+    }
+}
+
+/// Exports the replay buffer to a timestamped file in its directory every time Enter is pressed,
+/// while the grid renders
+fn watch_replay_trigger(buffer: ReplayBuffer) {
+    eprintln!(
+        "Keeping the last {} seconds in {}. Press Enter to export a replay",
+        buffer.length,
+        buffer.dir.display()
+    );
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        // Messages go to standard error, as the grid may be streamed to standard output
+        while std::io::stdin()
+            .read_line(&mut line)
+            .is_ok_and(|read| read > 0)
+        {
+            line.clear();
+            let stamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+            let mut path = buffer.dir.join(format!("replay_{}.mp4", stamp));
+            let mut attempt = 2;
+            while path.exists() {
+                path = buffer.dir.join(format!("replay_{}_{}.mp4", stamp, attempt));
+                attempt += 1;
+            }
+            match buffer.export(&path) {
+                Ok(()) => eprintln!("Replay written to {}", path.display()),
+                Err(e) => eprintln!("Cannot export the replay: {}", e),
+            }
+        }
+    });
+}
+
+/// Renders the first frame of the grid to a temporary image, opens it and asks whether to render
+/// the whole grid
+fn confirm_first_frame(cells: &[Cell], settings: &GridSettings) -> Result<bool, Box<dyn Error>> {
+    let preview_path =
+        std::env::temp_dir().join(format!("vidgrid_{}_first_frame.png", std::process::id()));
+    let preview = settings.for_still(0.0, &preview_path);
+    create_video_grid(cells, &preview).map_err(grid_error)?;
+    open::that(&preview_path)?;
+
+    let answer = prompt::prompt("Render the whole grid? [y/N]");
+    let _ = std::fs::remove_file(&preview_path);
+    let answer = answer?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Re-renders a grid from the job embedded in it with `new_inputs` as additional cells. The new
+/// cells are given the same way as the job's own inputs, so the grown job can be appended to again
+fn append(
+    grid: &Path,
+    new_inputs: &[PathBuf],
+    output_path: Option<&Path>,
+    overrides: &[String],
 ) -> Result<(), Box<dyn Error>> {
-    // Step 1: Retrieve Frame Rates of All Input Videos
-    let fps1 = get_video_framerate(vid1_path)?;
-    let fps2 = get_video_framerate(vid2_path)?;
-    let fps3 = get_video_framerate(vid3_path)?;
-    let fps4 = get_video_framerate(vid4_path)?;
+    let spec = provenance::JobSpec::read_from(grid)?;
+    let args: options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(spec.args.iter().cloned()),
+    )?;
+    if args.command.is_some() {
+        return Err("The embedded job is not a grid job".into());
+    }
+    if args.edl.is_some() || args.from_chapters.is_some() {
+        return Err(format!(
+            "{} takes its cells from {}, so cells can't be appended to it",
+            grid.display(),
+            if args.edl.is_some() {
+                "--edl"
+            } else {
+                "--from-chapters"
+            }
+        )
+        .into());
+    }
+
+    // The job is run from its own working directory, where the new inputs must still be found
+    let new_inputs: Vec<String> = new_inputs
+        .iter()
+        .map(|path| {
+            std::fs::canonicalize(path)
+                .map_err(|e| format!("Cannot find {}: {}", path.display(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let output_path = match output_path {
+        Some(path) => std::env::current_dir()?.join(path),
+        None => std::fs::canonicalize(grid)?,
+    }
+    .to_string_lossy()
+    .into_owned();
+
+    let cells = [&args.in1, &args.in2, &args.in3, &args.in4]
+        .into_iter()
+        .flatten()
+        .count()
+        + args.inputs.len()
+        + args.input.len()
+        + new_inputs.len();
 
-    // Determine the maximum frame rate among the inputs
-    let mut max_input_fps = fps1.max(fps2).max(fps3).max(fps4);
+    // A grid whose columns and rows can't hold the new cells is laid out anew
+    let fits = match (args.cols, args.rows) {
+        (Some(cols), Some(rows)) => usize::from(cols) * usize::from(rows) >= cells,
+        _ => true,
+    };
+    // `--in1`..`--in4` hold no more than four cells, so they are given as `--input` instead
+    let mut fixed = Vec::new();
+    let mut argv = Vec::new();
+    let mut words = spec.args.iter();
+    while let Some(word) = words.next() {
+        let (name, value) = match word.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (word.as_str(), None),
+        };
+        match name {
+            "--cols" | "--rows" if !fits => {
+                if value.is_none() {
+                    words.next();
+                }
+            }
+            "--in1" | "--in2" | "--in3" | "--in4" => {
+                let value = value.or_else(|| words.next().cloned()).unwrap_or_default();
+                fixed.push((name.to_string(), value));
+            }
+            _ => argv.push(word.clone()),
+        }
+    }
+    fixed.sort();
 
-    // Cap the frame rate at the specified max_framerate
-    if max_input_fps > max_framerate {
-        max_input_fps = max_framerate;
+    if args.inputs.is_empty() {
+        let inputs = fixed.into_iter().map(|(_, input)| input).chain(new_inputs);
+        argv.extend(inputs.flat_map(|input| ["--input".to_string(), input]));
+    } else {
+        argv.extend(new_inputs);
     }
+    argv.extend(["--output-path".to_string(), output_path]);
+    argv.extend(overrides.iter().cloned());
 
-    // Step 2: Retrieve Durations of All Input Videos
-    let dur1 = get_video_duration(vid1_path)?;
-    let dur2 = get_video_duration(vid2_path)?;
-    let dur3 = get_video_duration(vid3_path)?;
-    let dur4 = get_video_duration(vid4_path)?;
+    std::env::set_current_dir(&spec.cwd)
+        .map_err(|e| format!("Cannot enter {}: {}", spec.cwd.display(), e))?;
+    let args: options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(argv.iter().cloned()),
+    )?;
+    run_grid(&args, &argv)
+}
 
-    // Determine the maximum duration among the inputs
-    let max_input_duration = dur1.max(dur2).max(dur3).max(dur4);
+/// Whether the files of `args` were dropped onto the executable or sent to it from a file
+/// manager, which then needs defaults asking for no further interaction. Those pass nothing but
+/// absolute paths, never an option, unlike a command typed in a shell
+fn is_drag_and_drop(args: &options::Args, argv: &[String]) -> bool {
+    !args.inputs.is_empty()
+        && !argv.iter().any(|arg| arg.starts_with('-'))
+        && argv.iter().all(|arg| Path::new(arg).is_absolute())
+}
+
+/// The output of the grid of `args`, parsed from `argv`: `--output-path`, or else a default
+/// that suits how vidgrid was started and whether a still is asked for
+fn output_path(args: &options::Args, argv: &[String]) -> Result<PathBuf, Box<dyn Error>> {
+    let output_path = match &args.output_path {
+        Some(path) => return Ok(path.clone()),
+        None if args.timestamped_output => timestamped_output_path(&args.output_dir)?,
+        None if is_drag_and_drop(args, argv) => default_output_next_to(&args.inputs),
+        None => PathBuf::from("output.mp4"),
+    };
+    if args.image {
+        return Ok(output_path.with_extension("png"));
+    }
+    Ok(output_path)
+}
+
+/// Renders the grid described by the command line arguments `args`, parsed from `argv`
+fn run_grid(args: &options::Args, argv: &[String]) -> Result<(), Box<dyn Error>> {
+    let drag_and_drop = is_drag_and_drop(args, argv);
+    let output_path = output_path(args, argv)?;
+    let is_image = |path: &Path| {
+        path.extension().is_some_and(|ext| {
+            ["png", "jpg", "jpeg"]
+                .iter()
+                .any(|image| ext.eq_ignore_ascii_case(image))
+        })
+    };
+    let still = (args.image || is_image(&output_path)).then(|| {
+        args.at
+            .map_or(settings::StillFrame::Middle, settings::StillFrame::At)
+    });
+    let animation = settings::AnimatedFormat::from_path(&output_path).filter(|_| still.is_none());
+    let stream = settings::StreamTarget::from_path(&output_path);
 
-    // Calculate the output duration: min(user_duration, max_input_duration)
-    let output_duration = if duration < max_input_duration {
-        duration
+    let timeline_events = match &args.timeline_strip {
+        Some(_) if still.is_some() => {
+            return Err("A still has no timeline to draw a strip of".into());
+        }
+        Some(path) => Some(timeline::read_events(path)?),
+        None => None,
+    };
+    if let Some(path) = &args.save_layout {
+        layout_file::LayoutFile::from_args(args).save(path)?;
+    }
+    // The gateway is told the endpoint over HTTP(S), which WHIP is spoken over
+    let stream_gateway = match (stream, &args.whip_gateway) {
+        (Some(settings::StreamTarget::Whip), Some(template)) => {
+            let url = output_path.to_string_lossy();
+            let url = match url.split_once("://") {
+                Some(("whips", rest)) => format!("https://{}", rest),
+                Some((_, rest)) => format!("http://{}", rest),
+                None => url.into_owned(),
+            };
+            Some(
+                hook::split_words(template)?
+                    .into_iter()
+                    .map(|word| word.replace("{url}", &url))
+                    .collect::<Vec<_>>(),
+            )
+            .filter(|words| !words.is_empty())
+        }
+        (Some(settings::StreamTarget::Whip), None) => {
+            return Err("A WHIP stream is published through --whip-gateway".into());
+        }
+        _ => None,
+    };
+    if !args.publish.is_empty() && stream.is_some() {
+        return Err("A stream is sent as it is rendered and leaves nothing to publish".into());
+    }
+    if args.segment.is_some() && (still.is_some() || animation.is_some() || stream.is_some()) {
+        return Err("Only videos written to disk can be recorded in segments".into());
+    }
+    if args.segment.is_some() && (args.audio_describe.is_some() || !args.publish.is_empty()) {
+        return Err(
+            "A recording in segments has no single file to audio describe or publish".into(),
+        );
+    }
+    if args.captions_mux && (still.is_some() || animation.is_some() || stream.is_some()) {
+        return Err("Captions can only be added as tracks to video files written to disk".into());
+    }
+
+    // The script is read up front so a mistake in it shows before the grid is rendered
+    let narration = match &args.audio_describe {
+        Some(_) if still.is_some() || animation.is_some() || stream.is_some() => {
+            return Err("Only video files written to disk can be audio described".into());
+        }
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("srt")) =>
+        {
+            Some(describe::Narration::Script {
+                cues: describe::read_script(path)?,
+                voice: args.tts_voice.clone(),
+            })
+        }
+        Some(path) => Some(describe::Narration::Recording(path.clone())),
+        None => None,
+    };
+
+    // Animated images are shared inline, where full-size frames at full frame rate would make
+    // them far too heavy to load
+    let (mut width, mut height, mut max_framerate) = (args.width, args.height, args.max_framerate);
+    if animation.is_some() {
+        if width > args.animation_width {
+            height =
+                (u64::from(height) * u64::from(args.animation_width) / u64::from(width)) as u32;
+            width = args.animation_width;
+        }
+        max_framerate = max_framerate.min(args.animation_fps);
+    }
+
+    let mut cells = match &args.edl {
+        Some(edl_path) => {
+            let clips = edl::read_edl(edl_path)?;
+            if clips.is_empty() {
+                return Err("The edit decision list contains no video clips".into());
+            }
+            clips
+                .into_iter()
+                .map(|clip| Cell {
+                    start: Some(clip.start),
+                    end: Some(clip.end),
+                    ..Cell::new(InputSource::File(clip.path))
+                })
+                .collect()
+        }
+        None if args.from_chapters.is_some() => {
+            let path = args.from_chapters.clone().expect("checked above");
+            chapters::read_chapters(&path)?
+                .into_iter()
+                .map(|chapter| Cell {
+                    start: Some(chapter.start),
+                    end: Some(chapter.end),
+                    label: chapter.title,
+                    ..Cell::new(InputSource::File(path.clone()))
+                })
+                .collect()
+        }
+        None if !args.inputs.is_empty() || !args.input.is_empty() => args
+            .inputs
+            .iter()
+            .chain(&args.input)
+            .cloned()
+            .map(Cell::new)
+            .collect(),
+        // clap guarantees all four inputs are present when there is no other source of cells
+        None => [&args.in1, &args.in2, &args.in3, &args.in4]
+            .map(|input| Cell::new(input.clone().expect("input is required")))
+            .to_vec(),
+    };
+    if let Some(cell) = args.referenced_cells().find(|&cell| cell > cells.len()) {
+        return Err(format!(
+            "Cell {} is referenced, but there are {} cells",
+            cell,
+            cells.len()
+        )
+        .into());
+    }
+    for &(cell, stream) in &args.stream {
+        cells[cell - 1].stream = stream;
+    }
+    for &(cell, start) in &args.start {
+        cells[cell - 1].start = Some(start);
+    }
+    for &(cell, end) in &args.end {
+        cells[cell - 1].end = Some(end);
+    }
+    // Synchronizing moves each cell's start and end points by the same amount, keeping its length
+    let offsets = match args.sync {
+        Some(SyncMode::Audio) => Some(vidgrid::sync::audio_offsets(
+            &cells,
+            args.sync_reference - 1,
+            args.sync_window,
+        )?),
+        None if !args.sync_point.is_empty() => {
+            Some(vidgrid::sync::point_offsets(cells.len(), &args.sync_point))
+        }
+        None => None,
+    };
+    for (cell, offset) in cells.iter_mut().zip(offsets.iter().flatten()) {
+        if *offset > 0.0 {
+            cell.start = Some(cell.start.unwrap_or(0.0) + offset);
+            cell.end = cell.end.map(|end| end + offset);
+        }
+    }
+    if let Some(cell) = cells
+        .iter()
+        .position(|cell| matches!((cell.start, cell.end), (Some(start), Some(end)) if end <= start))
+    {
+        return Err(format!("Cell {} ends before it starts", cell + 1).into());
+    }
+    for cell in &mut cells {
+        cell.fit = args.fit;
+    }
+    for &(cell, fit) in &args.fit_cell {
+        cells[cell - 1].fit = fit;
+    }
+    for &(cell, plane) in &args.plane {
+        cells[cell - 1].plane = Some(plane);
+    }
+    for &(cell, frames) in &args.trail {
+        cells[cell - 1].trail = Some(frames);
+    }
+    if let Some(source) = args.label_from {
+        for cell in &mut cells {
+            if let (InputSource::File(path), None) = (&cell.source, &cell.label) {
+                cell.label = get_video_label(
+                    path,
+                    cell.stream,
+                    source,
+                    args.input_timeout.map(Duration::from_secs_f64),
+                )?;
+            }
+        }
+    }
+    for (cell, path) in &args.label_file {
+        let label = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read label {}: {}", path.display(), e))?;
+        cells[cell - 1].label = Some(label.trim_end().to_string());
+    }
+    for (cell, label) in &args.label {
+        cells[cell - 1].label = Some(label.clone());
+    }
+    for (cell, subtitles) in &args.subs {
+        if let (cell::Subtitles::Track(_), None) = (subtitles, cells[cell - 1].source.as_file()) {
+            return Err(format!("Cell {} has no input file to take subtitles from", cell).into());
+        }
+        cells[cell - 1].subtitles = Some(subtitles.clone());
+    }
+    for (cell, card) in &args.endcard {
+        cells[cell - 1].end_card = Some(card.clone());
+    }
+    for (cell, filter) in &args.audio_filter {
+        cells[cell - 1].audio_filter = Some(filter.clone());
+    }
+    if args.timecode_from == cell::TimecodeSource::Source
+        && args.timestamp_format != cell::TimestampFormat::Time
+    {
+        return Err(
+            "--timecode-from source shows timecodes, which count frames themselves, so it takes no --timestamp-format".into(),
+        );
+    }
+    for cell in &mut cells {
+        if args.input_retry > 0 && cell.is_rtsp_source() {
+            return Err(
+                "--input-retry can't retry RTSP inputs, which ffmpeg can't reconnect to".into(),
+            );
+        }
+        cell.network_retries = args.input_retry;
+        cell.accurate_seek = args.accurate_seek;
+        if args.timestamp && !matches!(cell.source, InputSource::Empty) {
+            cell.timestamp = Some(args.timestamp_format);
+            if let InputSource::File(_) = cell.source {
+                cell.timecode_from = args.timecode_from;
+            }
+        }
+        cell.font = args.font.clone();
+        cell.locale = args.locale.unwrap_or_default();
+        cell.label_style = cell::LabelStyle {
+            scale: args.label_scale,
+            color: args.label_color.clone(),
+            position: args.label_position,
+        };
+    }
+    // Measured before rendering, so the scores can be burned into the cells they were measured on
+    let metrics = if args.metrics {
+        diff::measure_cells(
+            &cells,
+            args.metrics_reference,
+            (args.duration != u32::MAX).then_some(f64::from(args.duration)),
+        )?
     } else {
-        max_input_duration
+        vec![None; cells.len()]
+    };
+    if args.metrics_overlay {
+        for (cell, metrics) in cells.iter_mut().zip(&metrics) {
+            cell.quality_score = metrics.as_ref().map(|metrics| metrics.score(&cell.locale));
+        }
+    }
+    for &(cell, redaction) in &args.blur_region {
+        cells[cell - 1].redactions.push(redaction);
+    }
+    if let Some(detector) = &args.redact_cmd {
+        for cell in &mut cells {
+            if let InputSource::File(path) = &cell.source {
+                let detections = hook::run_hook(detector, path, None)?;
+                let redactions = cell::Redaction::list_from_json(&detections).map_err(|e| {
+                    format!(
+                        "Invalid regions from detector for {}: {}",
+                        path.display(),
+                        e
+                    )
+                })?;
+                cell.redactions.extend(redactions);
+            }
+        }
+    }
+    for &(cell, clock) in &args.wallclock {
+        cells[cell - 1].wall_clock = Some(clock);
+    }
+    for &(cell, freeze) in &args.freeze {
+        cells[cell - 1].freezes.push(freeze);
+    }
+    for &(cell, spoiler) in &args.spoiler {
+        cells[cell - 1].spoiler = Some(spoiler);
+    }
+    for (cell, key) in &args.decryption_key {
+        cells[cell - 1].decryption_key = Some(key.clone());
+    }
+    for &(cell, decoder) in &args.hwaccel_decode {
+        cells[cell - 1].hw_decoder = Some(decoder);
+    }
+    for (cell, path) in &args.overlay_data {
+        cells[cell - 1]
+            .events
+            .extend(overlay_data::read_events(path)?);
+    }
+    // drawtext fails on a file missing when the grid starts, though it may vanish for a while later
+    for (cell, path) in &args.live_text {
+        if !path.is_file() {
+            return Err(format!("Live text {} does not exist", path.display()).into());
+        }
+        cells[cell - 1].live_text = Some(path.clone());
+    }
+    if let Some(path) = &args.annotations {
+        for annotation in annotation::read_annotations(path)? {
+            let count = cells.len();
+            let cell = cells.get_mut(annotation.cell - 1).ok_or_else(|| {
+                format!(
+                    "{} annotates cell {}, but the grid has {} cells",
+                    path.display(),
+                    annotation.cell,
+                    count
+                )
+            })?;
+            cell.annotations.push(annotation);
+        }
+    }
+
+    // Telemetry readouts are rendered from temporary subtitle files, removed once the grid is done
+    let mut temporary_files = Vec::new();
+    for (cell, path) in &args.telemetry {
+        let samples = telemetry::read_telemetry(path)?;
+        let offset = args
+            .telemetry_offset
+            .iter()
+            .filter(|(offset_cell, _)| offset_cell == cell)
+            .map(|(_, offset)| offset)
+            .sum::<f64>();
+        let readouts = std::env::temp_dir().join(format!(
+            "vidgrid_telemetry_{}_{}.srt",
+            std::process::id(),
+            cell
+        ));
+        telemetry::write_readout_subtitles(
+            &samples,
+            offset + cells[cell - 1].start.unwrap_or(0.0),
+            &args.locale.unwrap_or_default(),
+            &readouts,
+        )?;
+        cells[cell - 1].telemetry = Some(readouts.clone());
+        temporary_files.push(readouts);
+    }
+    if let Some(transcriber) = &args.captions_cmd {
+        for (index, cell) in cells.iter_mut().enumerate() {
+            let InputSource::File(path) = &cell.source else {
+                continue;
+            };
+            let captions = std::env::temp_dir().join(format!(
+                "vidgrid_captions_{}_{}.srt",
+                std::process::id(),
+                index + 1
+            ));
+            let _ = std::fs::remove_file(&captions);
+            let printed = hook::run_hook(transcriber, path, Some(&captions))?;
+            // Commands that don't write to `{output}` print their captions instead
+            if !captions.exists() {
+                std::fs::write(&captions, printed)?;
+            }
+            temporary_files.push(captions.clone());
+            if args.captions_mux {
+                cell.captions = Some(captions);
+            } else {
+                cell.subtitles = Some(cell::Subtitles::File(captions));
+            }
+        }
+    }
+
+    // Magnified regions get cells of their own after the inputs, growing the grid as needed
+    for &(cell, region) in &args.magnify {
+        cells.push(Cell::new(InputSource::Magnify { cell, region }));
+    }
+
+    // Shuffle last, so per-input options travel with their input
+    let mut shuffle_seed = None;
+    let mut input_numbers: Vec<usize> = (1..=cells.len()).collect();
+    if args.shuffle || args.blind {
+        let seed = args.seed.unwrap_or_else(shuffle::random_seed);
+        input_numbers = shuffle::shuffle_cells(&mut cells, seed);
+        shuffle_seed = Some(seed);
+    }
+
+    // The embedded job reproduces a random shuffle by recording the seed drawn
+    let mut recorded_argv = argv.to_vec();
+    if let (Some(seed), None) = (shuffle_seed, args.seed) {
+        recorded_argv.extend(["--seed".to_string(), seed.to_string()]);
+    }
+    let mut job = Some(provenance::JobSpec::current(&recorded_argv)?);
+
+    // Hide the inputs behind letters, sealing the answer key away from the evaluators. The job
+    // would give the shuffle away, so it is sealed with the key rather than embedded in the grid.
+    if args.blind {
+        let mut entries = Vec::new();
+        for (index, cell) in cells.iter_mut().enumerate() {
+            let label = blind::blind_label(index);
+            cell.label = Some(label.clone());
+            entries.push(blind::KeyEntry {
+                label,
+                cell: index + 1,
+                input: input_numbers[index],
+                source: cell.source.to_string(),
+            });
+        }
+        let key = blind::AnswerKey {
+            seed: shuffle_seed.expect("blind tests are shuffled"),
+            entries,
+            job: job.take(),
+        };
+        let key_path = args
+            .key_path
+            .clone()
+            .unwrap_or_else(|| output_path.with_extension("key.json"));
+        if !args.dry_run {
+            blind::write_key(&key, &key_path)?;
+        }
+    }
+
+    let thumb_track = match &args.thumb_track {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            Some(settings::ThumbTrack {
+                dir: dir.clone(),
+                interval: args.thumb_interval,
+            })
+        }
+        None => None,
     };
 
-    // Step 3: Calculate Individual Video Dimensions for the 2x2 Grid
-    let video_width = output_width / 2;
-    let video_height = output_height / 2;
+    if let Some(dir) = &args.export_audio_stems {
+        std::fs::create_dir_all(dir)?;
+    }
 
-    // Construct the scaling and padding filter with the new resolution
-    let scale_pad = format!(
-        "scale={vw}:{vh}:force_original_aspect_ratio=decrease,pad={vw}:{vh}:(ow-iw)/2:(oh-ih)/2",
-        vw = video_width,
-        vh = video_height
-    );
+    if args.health_overlay && (still.is_some() || animation.is_some()) {
+        return Err("Feed health is only measured while rendering a video or stream".into());
+    }
+    if args.health_overlay && args.prescale {
+        return Err("Feed health is measured on the inputs, which --prescale replaces".into());
+    }
+    if args.no_signal_after.is_some() && (still.is_some() || animation.is_some()) {
+        return Err("Frozen pictures are only covered while rendering a video or stream".into());
+    }
+    let replay_buffer = match args.replay_buffer {
+        Some(_) if still.is_some() || animation.is_some() => {
+            return Err("Only videos and streams can keep a replay buffer".into());
+        }
+        Some(length) => {
+            std::fs::create_dir_all(&args.replay_dir)?;
+            let buffer = ReplayBuffer {
+                dir: args.replay_dir.clone(),
+                length,
+            };
+            if !args.dry_run {
+                buffer.clear()?;
+            }
+            Some(buffer)
+        }
+        None => None,
+    };
 
-    let videos = vec![
-        ("0:v", "vid1"),
-        ("1:v", "vid2"),
-        ("2:v", "vid3"),
-        ("3:v", "vid4"),
-    ];
-    let mut filters = Vec::new();
-
-    // Apply scaling, reset PTS, set dynamic frame rate, and add fifo to each video input
-    for (input, label) in &videos {
-        let filter = format!(
-            "[{input}]{scale_pad},setpts=PTS-STARTPTS,fps=fps={fps},fifo[{label}];",
-            input = input,
-            scale_pad = scale_pad,
-            fps = max_input_fps,
-            label = label
+    // Containers kill processes going over their memory limit rather than letting them swap
+    let container = limits::ResourceLimits::detect();
+    let low_memory = args.low_memory || container.needs_low_memory(width, height);
+    if low_memory && !args.low_memory && !args.quiet {
+        eprintln!(
+            "Rendering with --low-memory to fit the {} MiB memory limit of the container",
+            container.memory.unwrap_or(0) >> 20
         );
-        filters.push(filter);
-    }
-
-    // Stack the videos into a 2x2 grid
-    filters.push("[vid1][vid2]hstack=inputs=2[top];".to_string());
-    filters.push("[vid3][vid4]hstack=inputs=2[bottom];".to_string());
-    filters.push("[top][bottom]vstack=inputs=2[final]".to_string());
-
-    let filter_complex = filters.join(" ");
-
-    // Step 4: Execute the ffmpeg Command with the New Parameters
-    let status = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(vid1_path)
-        .arg("-i")
-        .arg(vid2_path)
-        .arg("-i")
-        .arg(vid3_path)
-        .arg("-i")
-        .arg(vid4_path)
-        .arg("-filter_complex")
-        .arg(&filter_complex)
-        .arg("-map")
-        .arg("[final]")
-        .arg("-t")
-        .arg(&output_duration.to_string())
-        .arg("-vsync")
-        .arg("2") // Ensure frame duplication is handled correctly
-        .arg("-y") // Overwrite output file if it exists
-        .arg(output_path)
-        .status()?;
-
-    if !status.success() {
-        return Err("ffmpeg command failed".into());
     }
 
-    Ok(())
-}
+    let settings = GridSettings {
+        width,
+        height,
+        duration: args.duration,
+        max_framerate,
+        color_range: args.color_range,
+        colorspace: args.colorspace,
+        pad: args.pad,
+        gap: args.gap,
+        border: args.border,
+        border_color: args.border_color.clone(),
+        background_color: args.background_color.clone(),
+        short_inputs: if args.loop_short {
+            settings::ShortInputs::Loop
+        } else if args.shortest {
+            settings::ShortInputs::Shortest
+        } else {
+            settings::ShortInputs::Hold
+        },
+        low_memory,
+        threads: args.threads.map(usize::from).or(container.threads()),
+        input_timeout: args.input_timeout.map(Duration::from_secs_f64),
+        stream_gateway,
+        limits: (!args.force).then_some(settings::OutputLimits {
+            max_pixels: args.max_output_pixels,
+            max_duration: args.max_output_duration,
+        }),
+        prescale: args.prescale,
+        metadata: match &job {
+            Some(job) => vec![(
+                provenance::METADATA_KEY.to_string(),
+                serde_json::to_string(job)?,
+            )],
+            None => Vec::new(),
+        },
+        columns: args.cols.map(usize::from),
+        rows: args.rows.map(usize::from),
+        layout: args.layout,
+        compare: args.compare.map(|mode| settings::Comparison {
+            mode,
+            split_position: args.split_position,
+            animate_split: args.animate_split,
+        }),
+        timeline_strip: timeline_events,
+        audio: args.audio,
+        normalize_audio: !args.no_audio_normalize,
+        show_mute_icons: args.show_mute_icons,
+        redact_style: args.redact_style,
+        rotate: args.output_rotate,
+        interlace: args.interlace,
+        atomic: !args.no_atomic,
+        still,
+        animation,
+        thumb_track,
+        audio_stems: args.export_audio_stems.clone(),
+        frame_hashes: args.frame_hashes.clone(),
+        replay_buffer: replay_buffer.clone(),
+        segments: args.segment.map(|length| settings::Segments {
+            length,
+            keep_last: args.keep_last,
+        }),
+        health_overlay: args.health_overlay,
+        no_signal_after: args.no_signal_after,
+        // Only the summary reports it
+        measure_loudness: args.summary.is_some() || !args.fail_on.is_empty(),
+        // ffmpeg's own codecs for streams are dated, so streams are H.264 fast enough to be live
+        encoder: encoder::EncoderOptions {
+            codec: args.codec.or(stream.map(|_| encoder::VideoCodec::H264)),
+            crf: args.crf,
+            preset: args.preset.clone().or_else(|| {
+                stream
+                    .filter(|_| args.codec.is_none())
+                    .map(|_| "veryfast".to_string())
+            }),
+            pix_fmt: args.pix_fmt.clone(),
+            hwaccel: args.hwaccel,
+            low_latency: stream.is_some_and(|stream| stream.is_low_latency()),
+        },
+        print_commands: args.print_command,
+        dry_run: args.dry_run,
+        progress: Some(args.progress),
+        quiet: args.quiet,
+        output_path: output_path.clone(),
+    };
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: options::Args = clap::Parser::parse();
+    let confirmed = match args.confirm && !args.dry_run && still.is_none() {
+        true => confirm_first_frame(&cells, &settings),
+        false => Ok(true),
+    };
+    if let (Ok(true), Some(buffer), false) = (&confirmed, replay_buffer, args.dry_run) {
+        watch_replay_trigger(buffer);
+    }
+    let result = match confirmed {
+        Ok(true) => create_video_grid(&cells, &settings)
+            .map(Some)
+            .map_err(grid_error),
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    for path in &temporary_files {
+        let _ = std::fs::remove_file(path);
+    }
+    let Some(info) = result? else {
+        println!("The grid was not rendered");
+        return Ok(());
+    };
+    if args.dry_run {
+        return Ok(());
+    }
 
-    create_video_grid(
-        &args.in1,
-        &args.in2,
-        &args.in3,
-        &args.in4,
-        args.duration,
-        args.width,
-        args.height,
-        args.max_framerate,
-        &args.output_path,
-    )?;
+    let mut finished = vec![output_path.clone()];
+    if let Some(narration) = &narration {
+        let described = describe::described_path(&output_path);
+        describe::render_described(&output_path, narration, &described, args.quiet)?;
+        if !args.quiet {
+            println!("Audio-described grid written to {}", described.display());
+        }
+        finished.push(described);
+    }
+
+    if args.summary.is_some() || !args.fail_on.is_empty() {
+        let mut checksums = Vec::new();
+        if let Some(algorithm) = args.checksums {
+            // Listed in input order so blind tests don't give away which cell shows which input
+            let mut files: Vec<(usize, &PathBuf)> = cells
+                .iter()
+                .zip(&input_numbers)
+                .filter_map(|(cell, input)| Some((*input, cell.source.as_file()?)))
+                .collect();
+            files.sort();
+            let mut paths: Vec<&PathBuf> = Vec::new();
+            for (_, path) in files {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+            // A stream is gone once sent, and segments may be, so there is no output to hash
+            let output = (stream.is_none() && args.segment.is_none()).then_some(&output_path);
+            for path in paths.into_iter().chain(output) {
+                checksums.push(summary::FileChecksum {
+                    path: path.clone(),
+                    algorithm,
+                    hash: algorithm
+                        .hash_file(path)
+                        .map_err(|e| format!("Cannot hash {}: {}", path.display(), e))?,
+                });
+            }
+        }
+
+        let summary = summary::Summary {
+            output: settings.output_path.clone(),
+            width: settings.width,
+            height: settings.height,
+            duration: info.duration,
+            framerate: info.framerate,
+            cells: cells
+                .iter()
+                .zip(&input_numbers)
+                .enumerate()
+                .map(|(index, (cell, input))| summary::CellSummary {
+                    cell: index + 1,
+                    input: (!args.blind).then_some(*input),
+                    source: (!args.blind).then(|| cell.source.to_string()),
+                    label: cell.label.clone(),
+                    adjustments: if args.blind {
+                        Vec::new()
+                    } else {
+                        info.adjustments[index].clone()
+                    },
+                    loudness: info.loudness[index],
+                    metrics: metrics[index].clone(),
+                })
+                .collect(),
+            shuffle: shuffle_seed
+                .filter(|_| !args.blind)
+                .map(|seed| summary::ShuffleSummary { seed }),
+            checksums,
+            container: container.is_limited().then_some(container),
+            assertions: Vec::new(),
+        };
+        let summary = summary::Summary {
+            assertions: assertion::check_all(&args.fail_on, &summary)?,
+            ..summary
+        };
+        if let Some(summary_path) = &args.summary {
+            summary.write(summary_path)?;
+        }
+        // A grid failing its assertions is neither published nor opened
+        assertion::verdict(&summary.assertions)?;
+    }
+
+    for target in &args.publish {
+        let uploader = target.uploader();
+        for file in &finished {
+            let destination = uploader.upload(file)?;
+            if !args.quiet {
+                println!("Published {} to {}", file.display(), destination);
+            }
+        }
+    }
 
-    if args.open {
-        open::that(&args.output_path)?;
+    // A stream leaves no file behind to open, and segments no single one
+    match (&args.open_with, stream) {
+        (_, Some(_)) => {}
+        _ if args.segment.is_some() => {}
+        (Some(app), None) => open::with(&output_path, app)?,
+        (None, None) if args.open || drag_and_drop => open::that(&output_path)?,
+        (None, None) => {}
     }
 
     Ok(())
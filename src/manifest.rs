@@ -0,0 +1,232 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use vidgrid::time::Time;
+
+/// A grid described in a JSON or TOML file instead of on the command line, e.g.
+///
+/// ```json
+/// {
+///   "output": { "path": "grid.mp4", "width": 1280, "height": 720 },
+///   "cells": [
+///     { "path": "front.mp4", "label": "Front", "audio": true },
+///     { "path": "rear.mp4", "label": "Rear", "start": "1:30" },
+///     { "label": "Side (offline)" },
+///     { "path": "top.mp4" }
+///   ]
+/// }
+/// ```
+///
+/// or, in a `.toml` file,
+///
+/// ```toml
+/// [output]
+/// path = "grid.mp4"
+/// width = 1280
+///
+/// [[cells]]
+/// path = "front.mp4"
+/// label = "Front"
+/// audio = true
+///
+/// [[cells]]
+/// path = "rear.mp4"
+/// start = "1:30"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    output: OutputEntry,
+    cells: Vec<CellEntry>,
+}
+
+/// Settings of the whole grid. Anything left out keeps the default of its command line option
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputEntry {
+    path: Option<PathBuf>,
+    width: Option<u32>,
+    height: Option<u32>,
+    duration: Option<u32>,
+    max_framerate: Option<f64>,
+    columns: Option<u16>,
+    rows: Option<u16>,
+    layout: Option<String>,
+    /// `none`, `mix` or a cell number, as for `--audio`
+    audio: Option<String>,
+}
+
+/// A cell of the grid
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CellEntry {
+    /// The input, as for `--input`. Relative paths are relative to the manifest. A cell without
+    /// one is left empty, keeping the cells after it in their positions
+    #[serde(default = "empty_path")]
+    path: String,
+    label: Option<String>,
+    start: Option<Time>,
+    end: Option<Time>,
+    /// Whether the output's audio is taken from this cell
+    #[serde(default)]
+    audio: bool,
+}
+
+fn empty_path() -> String {
+    "empty".to_string()
+}
+
+impl Manifest {
+    /// Reads a manifest from a JSON or TOML file
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::read_template(path, &[])
+    }
+
+    /// Reads a manifest from a JSON or TOML file in which every `${name}` is replaced by the value
+    /// of the variable `name` of `variables`. Values are inserted as the contents of a JSON string,
+    /// which reads the same in a TOML basic string, so they may hold quotes and backslashes and
+    /// also stand for numbers outside of a string.
+    pub fn read_template(
+        path: &Path,
+        variables: &[(String, String)],
+    ) -> Result<Self, Box<dyn Error>> {
+        let template = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read manifest {}: {}", path.display(), e))?;
+
+        let mut json = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find("${") {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed ${{ in {}", path.display()))?;
+            let name = &rest[start + 2..start + end];
+            let (_, value) = variables
+                .iter()
+                .rev()
+                .find(|(variable, _)| variable == name)
+                .ok_or_else(|| {
+                    format!(
+                        "{} uses ${{{}}}, which is not given with --set",
+                        path.display(),
+                        name
+                    )
+                })?;
+            let quoted = serde_json::to_string(value)?;
+            json.push_str(&rest[..start]);
+            json.push_str(&quoted[1..quoted.len() - 1]);
+            rest = &rest[start + end + 1..];
+        }
+        json.push_str(rest);
+
+        crate::config_file::parse(path, &json)
+            .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e).into())
+    }
+
+    /// The command line arguments describing the same grid. Options given after them override
+    /// the manifest's. `dir` is the directory of the manifest.
+    pub fn to_args(&self, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut args = Vec::new();
+        let mut option = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{}", name));
+                args.push(value);
+            }
+        };
+
+        let output = &self.output;
+        option(
+            "output-path",
+            output
+                .path
+                .as_ref()
+                .map(|path| path.to_string_lossy().into_owned()),
+        );
+        option("width", output.width.map(|width| width.to_string()));
+        option("height", output.height.map(|height| height.to_string()));
+        option(
+            "duration",
+            output.duration.map(|duration| duration.to_string()),
+        );
+        option(
+            "max-framerate",
+            output.max_framerate.map(|fps| fps.to_string()),
+        );
+        option("cols", output.columns.map(|columns| columns.to_string()));
+        option("rows", output.rows.map(|rows| rows.to_string()));
+        option("layout", output.layout.clone());
+
+        // The output's audio comes from every cell, a single cell or none of them
+        let audio_cells: Vec<usize> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.audio)
+            .map(|(index, _)| index + 1)
+            .collect();
+        match (&output.audio, audio_cells.as_slice()) {
+            (audio, []) => option("audio", audio.clone()),
+            (None, &[cell]) => option("audio", Some(cell.to_string())),
+            (Some(_), _) => {
+                return Err("Choose the audio either for the output or for a cell, not both".into())
+            }
+            (None, _) => {
+                return Err(
+                    "Only one cell can have `\"audio\": true`. To hear all cells, set \
+                            `\"audio\": \"mix\"` for the output"
+                        .into(),
+                )
+            }
+        }
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let number = index + 1;
+            option("input", Some(cell.resolved_path(dir)));
+            option(
+                "label",
+                cell.label
+                    .as_ref()
+                    .map(|label| format!("{}:{}", number, label)),
+            );
+            option(
+                "start",
+                cell.start
+                    .as_ref()
+                    .map(|start| format!("{}:{}", number, start)),
+            );
+            option(
+                "end",
+                cell.end.as_ref().map(|end| format!("{}:{}", number, end)),
+            );
+        }
+
+        Ok(args)
+    }
+
+    /// The files on disk the cells read, with relative paths resolved against `dir`, the
+    /// directory of the manifest. URLs and virtual inputs are left out.
+    pub fn input_files(&self, dir: &Path) -> Vec<PathBuf> {
+        self.cells
+            .iter()
+            .filter(|cell| cell.is_file())
+            .map(|cell| PathBuf::from(cell.resolved_path(dir)))
+            .collect()
+    }
+}
+
+impl CellEntry {
+    /// Whether the cell reads a file, rather than a URL or a virtual input such as `onion:1,2`
+    fn is_file(&self) -> bool {
+        !self.path.contains("://") && !self.path.starts_with("onion:") && self.path != "empty"
+    }
+
+    /// The input of the cell, as for `--input`. URLs and virtual inputs are left as they are
+    fn resolved_path(&self, dir: &Path) -> String {
+        if self.is_file() && Path::new(&self.path).is_relative() {
+            dir.join(&self.path).to_string_lossy().into_owned()
+        } else {
+            self.path.clone()
+        }
+    }
+}
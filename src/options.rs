@@ -1,23 +1,231 @@
+use std::fmt::Display;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use vidgrid::cell::{
+    EndCard, Fit, Freeze, HwDecoder, LabelPosition, Plane, Redaction, Spoiler, Subtitles,
+    TimecodeSource, TimestampFormat,
+};
+use vidgrid::encoder::{HwAccel, VideoCodec};
+use vidgrid::input::{parse_cell_number, InputSource, LabelSource, Region, Visualizer};
+use vidgrid::settings::{
+    AudioMode, ColorRange, ColorSpace, CompareMode, FieldOrder, Layout, PadMode, ProgressFormat,
+    RedactStyle, Rotation,
+};
+use vidgrid::sync::SyncMode;
+use vidgrid::time::{parse_seconds, parse_wall_clock};
+
+use crate::assertion::Assertion;
+use crate::checksum::ChecksumAlgorithm;
+use crate::publish::PublishTarget;
+use crate::sweep::SweepParam;
+use vidgrid::locale::Locale;
 
 #[derive(Debug, clap::Parser)]
-#[clap(version)]
+#[clap(
+    version,
+    args_conflicts_with_subcommands = true,
+    args_override_self = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Args {
-    /// The path to the first video file. This will be the top-left video in the output grid
-    #[clap(long, help_heading = "INPUT")]
-    pub in1: PathBuf,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// The path to the first video file. This will be the top-left video in the output grid.
+    ///
+    /// Any input may instead be a virtual input: `onion:1,2:0.5` overlays cell 2 at 50%
+    /// opacity on top of cell 1 within this cell, and `empty` leaves the cell black while keeping
+    /// the cells after it in their positions
+    #[clap(long, required_unless_present_any = ["edl", "inputs", "input", "from_chapters", "input_dir", "manifest", "template"], help_heading = "INPUT")]
+    pub in1: Option<InputSource>,
 
     /// The path to the second video file. This will be the top-right video in the output grid
-    #[clap(long, help_heading = "INPUT")]
-    pub in2: PathBuf,
+    #[clap(long, required_unless_present_any = ["edl", "inputs", "input", "from_chapters", "input_dir", "manifest", "template"], help_heading = "INPUT")]
+    pub in2: Option<InputSource>,
 
     /// The path to the third video file. This will be the bottom-left video in the output grid
-    #[clap(long, help_heading = "INPUT")]
-    pub in3: PathBuf,
+    #[clap(long, required_unless_present_any = ["edl", "inputs", "input", "from_chapters", "input_dir", "manifest", "template"], help_heading = "INPUT")]
+    pub in3: Option<InputSource>,
 
     /// The path to the fourth video file. This will be the bottom-right video in the output grid
+    #[clap(long, required_unless_present_any = ["edl", "inputs", "input", "from_chapters", "input_dir", "manifest", "template"], help_heading = "INPUT")]
+    pub in4: Option<InputSource>,
+
+    /// Video files to tile, in reading order, as an alternative to `--in1`..`--in4`. When vidgrid
+    /// is given only absolute paths to files and no options, as when they are dropped onto the
+    /// executable, the grid is written next to the first file and opened once done
+    #[clap(conflicts_with_all = ["in1", "in2", "in3", "in4", "edl", "from_chapters"], help_heading = "INPUT")]
+    pub inputs: Vec<InputSource>,
+
+    /// A video file to tile, in reading order. May be given any number of times, as an alternative
+    /// to positional inputs
+    #[clap(long, short = 'i', conflicts_with_all = ["in1", "in2", "in3", "in4", "inputs", "edl", "from_chapters"], help_heading = "INPUT")]
+    pub input: Vec<InputSource>,
+
+    /// An edit decision list (CMX3600 `.edl` or OpenTimelineIO `.otio`) whose video clips, with
+    /// their in and out points, are used as the cells in reading order instead of `--in1`..`--in4`
+    #[clap(long, conflicts_with_all = ["in1", "in2", "in3", "in4"], help_heading = "INPUT")]
+    pub edl: Option<PathBuf>,
+
+    /// Play every chapter of a single file at once, each in its own cell labeled with the
+    /// chapter's title, as an overview of multi-scene recordings
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["in1", "in2", "in3", "in4", "edl"], help_heading = "INPUT")]
+    pub from_chapters: Option<PathBuf>,
+
+    /// Tile every video in a directory, in order of their names. When there are more videos than
+    /// cells, several grids are rendered, numbered like `output_001.mp4`
+    #[clap(long, value_name = "DIR", conflicts_with_all = ["in1", "in2", "in3", "in4", "inputs", "input", "edl", "from_chapters"], help_heading = "INPUT")]
+    pub input_dir: Option<PathBuf>,
+
+    /// A JSON or TOML (`.toml`) file describing the grid's output settings and its cells with
+    /// their labels, trims and audio. Options given on the command line override the manifest's
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["in1", "in2", "in3", "in4", "inputs", "input", "edl", "from_chapters", "input_dir"], help_heading = "INPUT")]
+    pub manifest: Option<PathBuf>,
+
+    /// A manifest in which `${name}` placeholders are filled in with the values given with
+    /// `--set`, so one file can describe many similar grids
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["in1", "in2", "in3", "in4", "inputs", "input", "edl", "from_chapters", "input_dir", "manifest"], help_heading = "INPUT")]
+    pub template: Option<PathBuf>,
+
+    /// Give a placeholder of `--template` its value, as `<name>=<value>`, e.g.
+    /// `--set inputs_dir=/data/run42`. May be given any number of times
+    #[clap(long = "set", value_name = "NAME=VALUE", value_parser = parse_variable, requires = "template", help_heading = "INPUT")]
+    pub variables: Vec<(String, String)>,
+
+    /// Only tile the files of `--input-dir` whose name matches this pattern, e.g. `cam*.mov`, in
+    /// which `*` matches anything and `?` any single character [default: common video
+    /// extensions]
+    #[clap(long, requires = "input_dir", help_heading = "INPUT")]
+    pub glob: Option<String>,
+
+    /// Select which video stream of a cell's input file feeds the cell, as `<cell>:v:<index>`,
+    /// e.g. `2:v:1` for the second video stream of the second input. Defaults to the first
+    #[clap(long, value_name = "CELL:v:INDEX", value_parser = parse_stream_option, help_heading = "INPUT")]
+    pub stream: Vec<(usize, usize)>,
+
+    /// Start a cell this far into its input, as `<cell>:<time>` in seconds or `[hh:]mm:ss`, e.g.
+    /// `2:1.5` to line up a clip that started recording early. May be given once per cell
+    #[clap(long, value_name = "CELL:TIME", value_parser = parse_time_option, help_heading = "INPUT")]
+    pub start: Vec<(usize, f64)>,
+
+    /// Stop a cell at this time of its input, as `<cell>:<time>`. May be given once per cell
+    #[clap(long, value_name = "CELL:TIME", value_parser = parse_time_option, help_heading = "INPUT")]
+    pub end: Vec<(usize, f64)>,
+
+    /// Line up inputs that started recording at different moments by trimming their starts.
+    /// `audio` matches the sound of every cell against that of `--sync-reference`
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with = "sync_point",
+        help_heading = "INPUT"
+    )]
+    pub sync: Option<SyncMode>,
+
+    /// The cell the others are synchronized to
+    #[clap(long, value_name = "CELL", default_value_t = 1, value_parser = parse_cell_number, requires = "sync", help_heading = "INPUT")]
+    pub sync_reference: usize,
+
+    /// How many seconds from the start of each cell are listened to when synchronizing. The
+    /// cells must overlap within them
+    #[clap(long, value_name = "TIME", default_value = "60", value_parser = parse_seconds, requires = "sync", help_heading = "INPUT")]
+    pub sync_window: f64,
+
+    /// The moment of an event seen by every cell, such as a clap, as `<cell>:<time>`. Cells are
+    /// trimmed so that these moments line up. May be given once per cell
+    #[clap(long, value_name = "CELL:TIME", value_parser = parse_time_option, help_heading = "INPUT")]
+    pub sync_point: Vec<(usize, f64)>,
+
+    /// Trim inputs given `--start` or `--end` by decoding them from their beginning instead of
+    /// seeking, so they start on exactly the intended frame. Slower, most of all on long inputs
     #[clap(long, help_heading = "INPUT")]
-    pub in4: PathBuf,
+    pub accurate_seek: bool,
+
+    /// Play inputs shorter than the grid again from their start until the grid ends, instead of
+    /// holding their last frame. Inputs trimmed with `--start` or `--end` are not looped
+    #[clap(long, conflicts_with = "shortest", help_heading = "INPUT")]
+    pub loop_short: bool,
+
+    /// End the grid with its shortest input instead of its longest
+    #[clap(long, help_heading = "INPUT")]
+    pub shortest: bool,
+
+    /// Retry reading an HTTP(S) input this many times when its connection drops, waiting longer
+    /// before each attempt, instead of failing the grid on the first network error. RTSP inputs
+    /// can't reconnect, so are refused
+    #[clap(long, value_name = "N", default_value_t = 0, help_heading = "INPUT")]
+    pub input_retry: u32,
+
+    /// Decrypt a cell's encrypted (CENC) MP4 input with a hex key, as `<cell>:<key>`. Keys are
+    /// not embedded in the output, so give them again to `vidgrid replay`
+    #[clap(long, value_name = "CELL:KEY", value_parser = parse_decryption_key_option, help_heading = "INPUT")]
+    pub decryption_key: Vec<(usize, String)>,
+
+    /// Decode a cell's input on the GPU, as `<cell>:<decoder>`, e.g. `2:cuda`. Cells not given
+    /// are decoded on the CPU, so a source the GPU can't decode can be left to it
+    #[clap(long, value_name = "CELL:DECODER", value_parser = parse_cell_option::<HwDecoder>, help_heading = "INPUT")]
+    pub hwaccel_decode: Vec<(usize, HwDecoder)>,
+
+    /// The number of columns of the grid. Chosen to fit the cells when not given
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub cols: Option<u16>,
+
+    /// The number of rows of the grid. Chosen to fit the cells when not given. Cells left over are
+    /// filled with the background color
+    #[clap(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub rows: Option<u16>,
+
+    /// How the cells are arranged. `--cols` and `--rows` only apply to the grid layout
+    #[clap(long, value_enum, default_value_t = Layout::Grid)]
+    pub layout: Layout,
+
+    /// Arrange the grid as saved with `--save-layout`: its size, layout, gaps, borders, colors,
+    /// fits and label placement. Options given on the command line take precedence
+    #[clap(long, value_name = "FILE")]
+    pub layout_file: Option<PathBuf>,
+
+    /// Save the arrangement of the grid, without its inputs, to a file `--layout-file` reads
+    #[clap(long, value_name = "FILE")]
+    pub save_layout: Option<PathBuf>,
+
+    /// Compare exactly two inputs in a single picture instead of side by side: `diff` shows where
+    /// they differ, `blend` overlays them at half opacity and `split` shows the first left of a
+    /// line and the second right of it
+    #[clap(long, value_enum, conflicts_with_all = ["layout", "cols", "rows"])]
+    pub compare: Option<CompareMode>,
+
+    /// Where the line of `--compare split` is, as a fraction of the width from the left
+    #[clap(long, value_name = "FRACTION", default_value_t = 0.5, value_parser = parse_fraction)]
+    pub split_position: f64,
+
+    /// Move the line of `--compare split` from the left edge to the right one over the duration
+    /// of the output
+    #[clap(long, conflicts_with = "split_position")]
+    pub animate_split: bool,
+
+    /// Space between neighboring cells, in pixels. The cells shrink so the grid keeps its size
+    #[clap(long, default_value_t = 0)]
+    pub gap: u32,
+
+    /// Draw a border of this many pixels around every cell, inside the cell
+    #[clap(long, default_value_t = 0)]
+    pub border: u32,
+
+    /// The color of cell borders, e.g. `white` or `#202020`
+    #[clap(long, default_value = "white")]
+    pub border_color: String,
+
+    /// The color showing through gaps and unused cells
+    #[clap(long, default_value = "black")]
+    pub background_color: String,
+
+    /// Draw a strip under the grid marking events from a JSON file, with a playhead advancing
+    /// over it, e.g. detections per camera. The file is an array of
+    /// `{"start", "end", "cell", "color"}` objects, where events without an end are drawn as
+    /// markers and events with a cell in a lane of their cell
+    #[clap(long, value_name = "FILE")]
+    pub timeline_strip: Option<PathBuf>,
 
     /// The resolution width of the output video file
     #[clap(long, default_value_t = 1920)]
@@ -35,7 +243,770 @@ pub struct Args {
     #[clap(long, default_value_t = 60.0)]
     pub max_framerate: f64,
 
-    /// The path to which to write the output png file
+    /// How to fill the parts of a cell its input doesn't cover
+    #[clap(long, value_enum, default_value_t = PadMode::Black)]
+    pub pad: PadMode,
+
+    /// How inputs are fitted to cells of another aspect ratio: `pad` shows all of the input,
+    /// `crop` fills the cell by cutting off the input's edges and `stretch` distorts the input to
+    /// fill the cell
+    #[clap(long, value_enum, default_value_t = Fit::Pad)]
+    pub fit: Fit,
+
+    /// Fit a single cell's input differently from `--fit`, as `<cell>:<pad|crop|stretch>`
+    #[clap(long, value_name = "CELL:FIT", value_parser = parse_cell_option::<Fit>)]
+    pub fit_cell: Vec<(usize, Fit)>,
+
+    /// The color range the output is converted to and tagged with
+    #[clap(long, value_enum, default_value_t = ColorRange::Limited, help_heading = "OUTPUT")]
+    pub color_range: ColorRange,
+
+    /// The colorspace the output is converted to and tagged with
+    #[clap(long, value_enum, default_value_t = ColorSpace::Bt709, help_heading = "OUTPUT")]
+    pub colorspace: ColorSpace,
+
+    /// The audio of the output: `none`, `mix` to mix every cell that has audio, or a cell number to
+    /// take the audio of that cell only
+    #[clap(long, default_value_t = AudioMode::None, help_heading = "OUTPUT")]
+    pub audio: AudioMode,
+
+    /// Filter a cell's audio before it is mixed or exported, as `<cell>:<filters>` in ffmpeg's
+    /// syntax, e.g. `2:highpass=f=80,agate` to tame wind rumble on an outdoor camera. May be given
+    /// once per cell
+    #[clap(long, value_name = "CELL:FILTERS", value_parser = parse_cell_option::<String>, help_heading = "OUTPUT")]
+    pub audio_filter: Vec<(usize, String)>,
+
+    /// Also write the audio of every cell that has some to this directory, as `cell<n>.wav`,
+    /// trimmed and timed like the grid, e.g. for remixing a multicam session
+    #[clap(long, value_name = "DIR", help_heading = "OUTPUT")]
+    pub export_audio_stems: Option<PathBuf>,
+
+    /// Also write an audio-described variant of the grid next to it, as `<name>.described.<ext>`,
+    /// with a narration mixed over its audio. An `.srt` script is read out by text-to-speech,
+    /// which needs an ffmpeg built with libflite; any other file is a recorded narration
+    #[clap(long, value_name = "FILE", help_heading = "OUTPUT")]
+    pub audio_describe: Option<PathBuf>,
+
+    /// Also write an MD5 hash of every frame of the grid to this file, in ffmpeg's framemd5
+    /// format, while it is rendered. Frames are hashed before they are encoded, so the file
+    /// only changes when what the grid shows does, e.g. to compare vidgrid versions
+    #[clap(long, value_name = "FILE", help_heading = "OUTPUT")]
+    pub frame_hashes: Option<PathBuf>,
+
+    /// Keep this much of the grid, in seconds or `[hh:]mm:ss`, in a rolling buffer while it
+    /// renders, e.g. on a live stream. Pressing Enter exports the buffer as an instant replay,
+    /// `replay_<date>_<time>.mp4` in `--replay-dir`
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds, help_heading = "OUTPUT")]
+    pub replay_buffer: Option<f64>,
+
+    /// The directory the replay buffer and its exported replays are written to. Segments an
+    /// earlier grid left there are removed when the grid starts
+    #[clap(
+        long,
+        value_name = "DIR",
+        default_value = "replay",
+        requires = "replay_buffer",
+        help_heading = "OUTPUT"
+    )]
+    pub replay_dir: PathBuf,
+
+    /// Record the output in files of this length, in seconds or `[hh:]mm:ss`, numbered after it
+    /// as `<name>_000.<ext>`, `<name>_001.<ext>` and so on, e.g. `--segment 600` to keep a
+    /// monitoring session in 10-minute files
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds, help_heading = "OUTPUT")]
+    pub segment: Option<f64>,
+
+    /// Keep only the last this many `--segment` files, overwriting the oldest, so recording for
+    /// days doesn't fill the disk
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), requires = "segment", help_heading = "OUTPUT")]
+    pub keep_last: Option<u32>,
+
+    /// Draw how every video file is arriving on its cell while the grid renders: the frame rate
+    /// and bitrate it is received at and how long ago its last frame came, so degraded live
+    /// feeds stand out. Inputs are measured as they are read, so --prescale is refused
+    #[clap(long, help_heading = "OUTPUT")]
+    pub health_overlay: bool,
+
+    /// Cover the cell of a video file with a "NO SIGNAL" slate once its picture has stood still
+    /// for this long, and uncover it as soon as it moves again, so frozen feeds on a wall of
+    /// live cameras stand out
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds, help_heading = "OUTPUT")]
+    pub no_signal_after: Option<f64>,
+
+    /// The text-to-speech voice narration scripts are read in: `kal`, `kal16`, `awb`, `rms` or
+    /// `slt`
+    #[clap(
+        long,
+        value_name = "VOICE",
+        default_value = "kal16",
+        help_heading = "OUTPUT"
+    )]
+    pub tts_voice: String,
+
+    /// Mix cells at their original volume instead of normalizing the loudness of the mix. amix
+    /// then scales every cell down so the mix can't clip
+    #[clap(long, help_heading = "OUTPUT")]
+    pub no_audio_normalize: bool,
+
+    /// Rotate the finished grid clockwise by this many degrees, for portrait-mounted displays.
+    /// `--width` and `--height` give the size of the grid before it is rotated
+    #[clap(long, value_enum, value_name = "DEGREES", help_heading = "OUTPUT")]
+    pub output_rotate: Option<Rotation>,
+
+    /// Interlace the output with this field order, e.g. for 1080i deliverables. Every two frames
+    /// of the grid become the two fields of one frame, so a 50 fps grid is delivered as 25i
+    #[clap(long, value_enum, value_name = "FIELD_ORDER", help_heading = "OUTPUT")]
+    pub interlace: Option<FieldOrder>,
+
+    /// The path to which to write the output file [default: output.mp4, or next to the first
+    /// input when only input files are given]. Outputs ending in .png, .jpg or .jpeg are stills
+    /// like with `--image`, and outputs ending in .gif or .webp are animated images. `-` streams
+    /// MPEG-TS to standard output, e.g. to pipe into `mpv -`, and `rtmp://`, `udp://` or `srt://`
+    /// URLs push a live stream to a server. `srt://` and `whip://` (or `whips://`) streams are
+    /// encoded for sub-second delay, the latter through `--whip-gateway`
+    #[clap(long, short = 'o', help_heading = "OUTPUT")]
+    pub output_path: Option<PathBuf>,
+
+    /// The WebRTC gateway publishing a `whip://` or `whips://` output, which reads the stream as
+    /// MPEG-TS on its standard input. `{url}` is replaced by the endpoint's http(s):// URL, e.g.
+    /// `--whip-gateway 'gst-launch-1.0 fdsrc ! tsdemux ! h264parse ! whipclientsink signaller::whip-endpoint={url}'`
+    #[clap(long, value_name = "COMMAND", help_heading = "OUTPUT")]
+    pub whip_gateway: Option<String>,
+
+    /// Render a single frame of the grid to an image instead of a video, e.g. a preview thumbnail
+    /// or contact sheet. The output defaults to a .png file
+    #[clap(long, help_heading = "OUTPUT")]
+    pub image: bool,
+
+    /// The moment of the grid a still image shows, in seconds or `[hh:]mm:ss`. Cells shorter than
+    /// that show their last frame [default: halfway through the grid]
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds, help_heading = "OUTPUT")]
+    pub at: Option<f64>,
+
+    /// The maximum frame rate of GIF and WebP outputs, which are chosen by the extension of the
+    /// output path
+    #[clap(
+        long,
+        value_name = "FPS",
+        default_value_t = 15.0,
+        help_heading = "OUTPUT"
+    )]
+    pub animation_fps: f64,
+
+    /// The maximum width of GIF and WebP outputs. Larger grids are shrunk to it, keeping their
+    /// aspect ratio
+    #[clap(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 640,
+        help_heading = "OUTPUT"
+    )]
+    pub animation_width: u32,
+
+    /// Name the output after the current date and time, e.g. `vidgrid_2024-05-07_13-15-02.mp4`, so
+    /// repeated runs never overwrite each other
+    #[clap(long, conflicts_with = "output_path", help_heading = "OUTPUT")]
+    pub timestamped_output: bool,
+
+    /// The directory timestamped outputs are written to
+    #[clap(
+        long,
+        env = "VIDGRID_OUTPUT_DIR",
+        default_value = ".",
+        help_heading = "OUTPUT"
+    )]
+    pub output_dir: PathBuf,
+
+    /// Encode straight to the output path instead of to a temporary file that is renamed into
+    /// place once complete
+    #[clap(long, help_heading = "OUTPUT")]
+    pub no_atomic: bool,
+
+    /// Keep memory use bounded when compositing very high resolution grids (e.g. four 4K inputs
+    /// into 8K), at the cost of speed, by limiting threading and buffering
+    #[clap(long)]
+    pub low_memory: bool,
+
+    /// Most threads ffmpeg filters and encodes with, and most inputs pre-scaled at once
+    /// [default: the CPUs of the container vidgrid runs in, if it is limited to some]
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Give up on an input, e.g. a corrupted or truncated file, once ffprobe or its pre-scaling
+    /// ffmpeg has run on it for this long [default: no limit]
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds)]
+    pub input_timeout: Option<f64>,
+
+    /// Refuse outputs whose frames have more pixels than this [default: 8K UHD]
+    #[clap(long, value_name = "PIXELS", default_value_t = 7680 * 4320, hide_default_value = true)]
+    pub max_output_pixels: u64,
+
+    /// Refuse outputs longer than this, in seconds or `[hh:]mm:ss`
+    #[clap(long, value_name = "TIME", default_value = "3:00:00", value_parser = parse_seconds)]
+    pub max_output_duration: f64,
+
+    /// Render outputs larger than `--max-output-pixels` and `--max-output-duration` allow
+    #[clap(long)]
+    pub force: bool,
+
+    /// Shrink each input to the cell size in a separate ffmpeg process (one per CPU core) before
+    /// compositing, which is much faster for large sources such as 4K
+    #[clap(long)]
+    pub prescale: bool,
+
+    /// Show the first frame of the grid and ask for confirmation before rendering all of it, to
+    /// catch a wrong order of inputs or broken labels early
+    #[clap(long)]
+    pub confirm: bool,
+
+    /// Whether to open the output file after processing
+    #[clap(long)]
+    pub open: bool,
+
+    /// Open the output file with this application instead of the default one
+    #[clap(long, value_name = "APP")]
+    pub open_with: Option<String>,
+
+    /// Send the finished output to a destination: a directory it is copied into,
+    /// `s3://bucket/prefix` (with the AWS CLI), `sftp://user@host/path` (with scp), `youtube` or
+    /// `youtube:<command>` (with youtubeuploader or the command), or `cmd:<command>`. Commands
+    /// get the output's path in place of `{output}`. May be given several times
+    #[clap(long, value_name = "TARGET")]
+    pub publish: Vec<PublishTarget>,
+
+    /// The video codec of the output [default: ffmpeg's choice for the output format, or libx264
+    /// when other encoder options are given]
+    #[clap(long, value_enum, help_heading = "ENCODING")]
+    pub codec: Option<VideoCodec>,
+
+    /// Encode at this constant quality instead of a bitrate. Lower is better; 23 is a typical
+    /// value for libx264. Hardware encoders use their own quality scale
+    #[clap(long, help_heading = "ENCODING")]
+    pub crf: Option<u32>,
+
+    /// The encoder's speed/quality preset, e.g. `slow` for libx264 or `p7` for NVENC
+    #[clap(long, help_heading = "ENCODING")]
+    pub preset: Option<String>,
+
+    /// The pixel format of the output, e.g. `yuv420p10le` for 10-bit video
+    #[clap(long, help_heading = "ENCODING")]
+    pub pix_fmt: Option<String>,
+
+    /// Encode on the GPU with this hardware encoder, much faster for 4K grids
+    #[clap(long, value_enum, help_heading = "ENCODING")]
+    pub hwaccel: Option<HwAccel>,
+
+    /// Print the ffmpeg commands and the filtergraph, one filter per line, to stderr without
+    /// running them. The inputs are still probed, so the printed commands are the real ones
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Print the ffmpeg commands and the filtergraph to stderr before running them
+    #[clap(long)]
+    pub print_command: bool,
+
+    /// How the progress of the encode is reported: a progress bar, or a JSON line per update on
+    /// stdout for scripts
+    #[clap(long, value_enum, default_value_t = ProgressFormat::Bar, help_heading = "OUTPUT")]
+    pub progress: ProgressFormat,
+
+    /// Print nothing but errors
+    #[clap(long, short = 'q', help_heading = "OUTPUT")]
+    pub quiet: bool,
+
+    /// Write a machine-readable JSON summary of the grid to this path, including the integrated
+    /// loudness and true peak of every cell that is heard
+    #[clap(long, help_heading = "OUTPUT")]
+    pub summary: Option<PathBuf>,
+
+    /// Measure the PSNR, SSIM and, when ffmpeg has libvmaf, the VMAF of every input file against
+    /// that of `--metrics-reference` before rendering, e.g. to compare encodes of one source.
+    /// Reported in the `--summary`
+    #[clap(long, help_heading = "OUTPUT")]
+    pub metrics: bool,
+
+    /// The cell the others are measured against with `--metrics`
+    #[clap(long, value_name = "CELL", default_value_t = 1, value_parser = parse_cell_number, requires = "metrics", help_heading = "OUTPUT")]
+    pub metrics_reference: usize,
+
+    /// Burn the VMAF score of every cell measured with `--metrics` into its top-left corner, or
+    /// its SSIM when ffmpeg has no libvmaf, so the result travels with the picture
+    #[clap(long, requires = "metrics", help_heading = "EFFECTS")]
+    pub metrics_overlay: bool,
+
+    /// Fail, after rendering, when an assertion about the summary holds, e.g. `duration<10` or
+    /// `framerate<29`, so CI pipelines can gate on the grid. The outcomes are recorded in the
+    /// summary. May be given several times
+    #[clap(long, value_name = "ASSERTION", help_heading = "OUTPUT")]
+    pub fail_on: Vec<Assertion>,
+
+    /// Record hashes of all input files and of the output in the summary, e.g. to document chain
+    /// of custody
+    #[clap(long, value_enum, requires = "summary", help_heading = "OUTPUT")]
+    pub checksums: Option<ChecksumAlgorithm>,
+
+    /// Also write small thumbnails of every cell to this directory, one every `--thumb-interval`
+    /// seconds, named `cell<n>_<index>.jpg`, e.g. for hover-scrub previews
+    #[clap(long, value_name = "DIR", help_heading = "OUTPUT")]
+    pub thumb_track: Option<PathBuf>,
+
+    /// Seconds between the thumbnails of `--thumb-track`
+    #[clap(
+        long,
+        default_value_t = 5.0,
+        requires = "thumb_track",
+        help_heading = "OUTPUT"
+    )]
+    pub thumb_interval: f64,
+
+    /// Randomly assign the inputs to cells, e.g. for unbiased quality evaluations. The assignment
+    /// is recorded in the summary
+    #[clap(long)]
+    pub shuffle: bool,
+
+    /// Shuffle the inputs and label the cells A/B/C/D instead of showing which input is which.
+    /// The answer key is sealed in a separate file, readable with `vidgrid reveal`, along with the
+    /// job that rendered the grid, which the grid doesn't embed
+    #[clap(long)]
+    pub blind: bool,
+
+    /// Where to write the sealed answer key of `--blind`. Defaults to the output path with a
+    /// `.key.json` extension
+    #[clap(long, requires = "blind")]
+    pub key_path: Option<PathBuf>,
+
+    /// The seed used by `--shuffle` and `--blind`, to reproduce a previous assignment
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Label every video cell with this metadata of its input, e.g. camera names stored as titles
+    /// by a recorder. Cells without that metadata are left unlabeled
+    #[clap(long, value_enum, help_heading = "EFFECTS")]
+    pub label_from: Option<LabelSource>,
+
+    /// Label a cell with the text of a UTF-8 file, as `<cell>:<label.txt>`. Right-to-left scripts
+    /// are shaped when ffmpeg is built with libfribidi and libharfbuzz
+    #[clap(long, value_name = "CELL:FILE", value_parser = parse_cell_option::<PathBuf>, help_heading = "EFFECTS")]
+    pub label_file: Vec<(usize, PathBuf)>,
+
+    /// Render the grid once per language, labeling its cells from a JSON file named after the
+    /// language, e.g. `--labels-file labels.de.json --labels-file labels.fr.json`. Each file is a
+    /// list of labels in cell order, with `null` for unlabeled cells. A `.toml` file gives the
+    /// list as `labels`, with `""` for unlabeled cells. The grid of each language
+    /// is written with the language before the output's extension, e.g. `output.de.mp4`
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["manifest", "template", "input_dir"], help_heading = "EFFECTS")]
+    pub labels_file: Vec<PathBuf>,
+
+    /// Burn subtitles into a cell, as `<cell>:<file>` for a subtitle file or `<cell>:track:<n>`
+    /// for the n-th subtitle stream of its input, counting from 0. The text is sized to the cell
+    #[clap(long, value_name = "CELL:SUBTITLES", value_parser = parse_cell_option::<Subtitles>, help_heading = "EFFECTS")]
+    pub subs: Vec<(usize, Subtitles)>,
+
+    /// A transcription command run for every input file, e.g. `transcribe --srt {input}`, whose
+    /// captions are burned into the input's cell. It either prints SRT subtitles or writes them to
+    /// `{output}`
+    #[clap(long, value_name = "COMMAND", help_heading = "EFFECTS")]
+    pub captions_cmd: Option<String>,
+
+    /// Add the captions of `--captions-cmd` to the output as a subtitle track per cell, titled
+    /// with its label, instead of burning them in. Needs an MP4, MOV, MKV or WebM output
+    #[clap(long, requires = "captions_cmd", help_heading = "EFFECTS")]
+    pub captions_mux: bool,
+
+    /// Show an image on top of a cell for the last seconds it plays, as `<cell>:<image>:<time>`,
+    /// e.g. `2:logo.png:3s` for each team's logo or result. May be given once per cell
+    #[clap(long, value_name = "CELL:IMAGE:TIME", value_parser = parse_cell_option::<EndCard>, help_heading = "EFFECTS")]
+    pub endcard: Vec<(usize, EndCard)>,
+
+    /// Label a cell, as `<cell>:<text>`, e.g. `1:x264 crf 23`. May be given once per cell
+    #[clap(long, value_name = "CELL:TEXT", value_parser = parse_cell_option::<String>, help_heading = "EFFECTS")]
+    pub label: Vec<(usize, String)>,
+
+    /// The size of labels, relative to the default of 1/16 of the cell height
+    #[clap(long, default_value_t = 1.0, help_heading = "EFFECTS")]
+    pub label_scale: f64,
+
+    /// The color of labels, e.g. `yellow` or `#ffcc00`
+    #[clap(long, default_value = "white", help_heading = "EFFECTS")]
+    pub label_color: String,
+
+    /// The edge of the cell labels are drawn along
+    #[clap(long, value_enum, default_value_t = LabelPosition::Top, help_heading = "EFFECTS")]
+    pub label_position: LabelPosition,
+
+    /// Draw a running timestamp in the corner of every cell, e.g. to spot sources drifting apart
+    #[clap(long, help_heading = "EFFECTS")]
+    pub timestamp: bool,
+
+    /// Draw the date and time a cell's input was recorded at, advancing with it, given the time
+    /// of its first frame as `<cell>:<date and time>`, e.g. `2:2024-05-07T13:15:02.250`. The time
+    /// is shown as it reads on the clock given, whatever its UTC offset
+    #[clap(long, value_name = "CELL:DATETIME", value_parser = parse_wall_clock_option, help_heading = "EFFECTS")]
+    pub wallclock: Vec<(usize, f64)>,
+
+    /// What the `--timestamp` shows
+    #[clap(long, value_enum, default_value_t = TimestampFormat::Time, requires = "timestamp", help_heading = "EFFECTS")]
+    pub timestamp_format: TimestampFormat,
+
+    /// Where the time of the `--timestamp` is taken from. `source` shows the timecode embedded in
+    /// each input file, e.g. by a professional camera, counted in the input's own frames. Cells
+    /// that are not input files keep showing the running time
+    #[clap(long, value_enum, default_value_t = TimecodeSource::Output, requires = "timestamp", help_heading = "EFFECTS")]
+    pub timecode_from: TimecodeSource,
+
+    /// The font of burned-in text, as a font file or a fontconfig family such as `Noto Sans
+    /// Arabic`. Pick one covering the scripts of your labels
+    #[clap(long, help_heading = "EFFECTS")]
+    pub font: Option<String>,
+
+    /// Format numbers and units of burned-in readouts, the decimals of `--timestamp` and the date
+    /// and 12- or 24-hour time of `--wallclock` for this locale, e.g. `de-DE` or `en-US`
+    #[clap(long, env = "VIDGRID_LOCALE", help_heading = "EFFECTS")]
+    pub locale: Option<Locale>,
+
+    /// Draw a small "muted" badge on cells that don't contribute sound to the output
+    #[clap(long, help_heading = "EFFECTS")]
+    pub show_mute_icons: bool,
+
+    /// Add a cell showing a magnified region of a cell's source, as `<cell>:<x>:<y>:<w>:<h>` in
+    /// source pixels. The grid grows to make room for the extra cells
+    #[clap(long, value_name = "CELL:X:Y:W:H", value_parser = parse_cell_option::<Region>, help_heading = "EFFECTS")]
+    pub magnify: Vec<(usize, Region)>,
+
+    /// Obscure a region of a cell's source, e.g. faces or license plates, as
+    /// `<cell>:<x>:<y>:<w>:<h>[:<start>-<end>]` in source pixels, optionally only between two times
+    /// of the source. The region stays obscured wherever the source is shown. May be given several
+    /// times
+    #[clap(long, value_name = "CELL:X:Y:W:H[:START-END]", value_parser = parse_cell_option::<Redaction>, help_heading = "EFFECTS")]
+    pub blur_region: Vec<(usize, Redaction)>,
+
+    /// A detector command run for every input file, e.g. `detect-faces --json {input}`, whose
+    /// output lists regions to obscure like `--blur-region` does: a JSON array of
+    /// `{"x", "y", "width", "height"}` rectangles in source pixels, with optional `"start"` and
+    /// `"end"` times in seconds
+    #[clap(long, value_name = "COMMAND", help_heading = "EFFECTS")]
+    pub redact_cmd: Option<String>,
+
+    /// How `--blur-region` and `--redact-cmd` obscure regions
+    #[clap(long, value_enum, default_value_t = RedactStyle::Blur, help_heading = "EFFECTS")]
+    pub redact_style: RedactStyle,
+
+    /// Show only the Y, U or V plane of a cell, as `<cell>:<y|u|v>`, to inspect luma or chroma
+    /// artifacts in isolation
+    #[clap(long, value_name = "CELL:PLANE", value_parser = parse_cell_option::<Plane>, help_heading = "EFFECTS")]
+    pub plane: Vec<(usize, Plane)>,
+
+    /// Blend the last N frames of a cell into a motion trail, as `<cell>:<frames>`.
+    /// May be given once per cell
+    #[clap(long, value_name = "CELL:FRAMES", value_parser = parse_cell_option::<u32>, help_heading = "EFFECTS")]
+    pub trail: Vec<(usize, u32)>,
+
+    /// Hold a cell's frame for a while as the other cells play on, as
+    /// `<cell>:at=<time>:for=<seconds>` with the time in the cell's source, e.g. `2:at=12s:for=3s`.
+    /// The cell's audio falls silent meanwhile, and the rest of the cell is heard and shown
+    /// afterwards. May be given several times
+    #[clap(long, value_name = "CELL:at=TIME:for=SECONDS", value_parser = parse_cell_option::<Freeze>, help_heading = "EFFECTS")]
+    pub freeze: Vec<(usize, Freeze)>,
+
+    /// Keep a cell heavily blurred until a time of the grid, then reveal it, as
+    /// `<cell>:until=<time>[:fade=<time>][:style=blur|pixelate]`, e.g. `3:until=30s:fade=2s` to
+    /// hide a result until the other cells have played up to it. The fade brings the cell into
+    /// view over the seconds before the reveal. May be given once per cell
+    #[clap(long, value_name = "CELL:until=TIME", value_parser = parse_cell_option::<Spoiler>, help_heading = "EFFECTS")]
+    pub spoiler: Vec<(usize, Spoiler)>,
+
+    /// Caption timestamped events from a CSV file on a cell, as `<cell>:<events.csv>`. Each row
+    /// is `time,text[,duration]`, with times relative to the cell's source
+    #[clap(long, value_name = "CELL:CSV", value_parser = parse_cell_option::<PathBuf>, help_heading = "EFFECTS")]
+    pub overlay_data: Vec<(usize, PathBuf)>,
+
+    /// Draw reviewers' notes from a JSON file on the cells: a list of `text`, `box` and `arrow`
+    /// annotations, each on a cell for a stretch of the grid, e.g.
+    /// `[{"type": "box", "cell": 2, "start": "0:05", "end": "0:09", "x": 0.4, "y": 0.3,
+    /// "width": 0.2, "height": 0.25, "color": "red"}]`. Positions are fractions of the cell
+    #[clap(long, value_name = "FILE", help_heading = "EFFECTS")]
+    pub annotations: Option<PathBuf>,
+
+    /// Draw the text of a file at the top of a cell, as `<cell>:<file.txt>`, reading it again
+    /// for every frame so scoreboards, sensor readings or titles written to it update live.
+    /// Replace the file atomically (write another and rename it) so no frame reads it half-written
+    #[clap(long, value_name = "CELL:FILE", value_parser = parse_cell_option::<PathBuf>, help_heading = "EFFECTS")]
+    pub live_text: Vec<(usize, PathBuf)>,
+
+    /// Draw speed, heart rate and position readouts from a GPX or CSV track on a cell, as
+    /// `<cell>:<track.gpx>`. The first point of the track is aligned with the start of the source
+    #[clap(long, value_name = "CELL:TRACK", value_parser = parse_cell_option::<PathBuf>, help_heading = "EFFECTS")]
+    pub telemetry: Vec<(usize, PathBuf)>,
+
+    /// Shift a cell's telemetry track, as `<cell>:<seconds>`, when the data logger was started
+    /// before the camera
+    #[clap(long, value_name = "CELL:SECONDS", value_parser = parse_cell_option::<f64>, allow_hyphen_values = true, help_heading = "EFFECTS")]
+    pub telemetry_offset: Vec<(usize, f64)>,
+}
+
+impl Args {
+    /// The cells referred to by per-cell options, which must exist
+    pub fn referenced_cells(&self) -> impl Iterator<Item = usize> + '_ {
+        self.stream
+            .iter()
+            .map(|(cell, _)| *cell)
+            .chain(self.start.iter().map(|(cell, _)| *cell))
+            .chain(self.end.iter().map(|(cell, _)| *cell))
+            .chain(self.sync_point.iter().map(|(cell, _)| *cell))
+            .chain(self.sync.map(|_| self.sync_reference))
+            .chain(self.metrics.then_some(self.metrics_reference))
+            .chain(self.magnify.iter().map(|(cell, _)| *cell))
+            .chain(self.plane.iter().map(|(cell, _)| *cell))
+            .chain(self.fit_cell.iter().map(|(cell, _)| *cell))
+            .chain(self.trail.iter().map(|(cell, _)| *cell))
+            .chain(self.label.iter().map(|(cell, _)| *cell))
+            .chain(self.label_file.iter().map(|(cell, _)| *cell))
+            .chain(self.subs.iter().map(|(cell, _)| *cell))
+            .chain(self.endcard.iter().map(|(cell, _)| *cell))
+            .chain(self.audio_filter.iter().map(|(cell, _)| *cell))
+            .chain(self.blur_region.iter().map(|(cell, _)| *cell))
+            .chain(self.freeze.iter().map(|(cell, _)| *cell))
+            .chain(self.spoiler.iter().map(|(cell, _)| *cell))
+            .chain(self.wallclock.iter().map(|(cell, _)| *cell))
+            .chain(self.decryption_key.iter().map(|(cell, _)| *cell))
+            .chain(self.hwaccel_decode.iter().map(|(cell, _)| *cell))
+            .chain(self.overlay_data.iter().map(|(cell, _)| *cell))
+            .chain(self.live_text.iter().map(|(cell, _)| *cell))
+            .chain(self.telemetry.iter().map(|(cell, _)| *cell))
+            .chain(self.telemetry_offset.iter().map(|(cell, _)| *cell))
+            .chain(match self.audio {
+                AudioMode::Cell(cell) => Some(cell),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Render audio files as a grid of labeled audio visualizations
+    AudioGrid(AudioGridArgs),
+    /// Re-render a grid with more cells, from the job embedded in its metadata. The grid is
+    /// replaced unless another output is given
+    Append {
+        /// A grid previously rendered by vidgrid
+        grid: PathBuf,
+        /// A video file to add as a new cell after the existing ones. May be given any number of
+        /// times
+        #[clap(long, short = 'i', required = true)]
+        input: Vec<PathBuf>,
+        /// Where to write the grown grid instead of replacing `grid`
+        #[clap(long, short = 'o')]
+        output_path: Option<PathBuf>,
+        /// Options overriding those of the embedded job, given after `--`, e.g. `-- --width 1280`
+        #[clap(last = true)]
+        overrides: Vec<String>,
+    },
+    /// Render many grids, each described by a manifest, in parallel. A failed grid doesn't stop
+    /// the others, and the outcome of every grid is summarized at the end
+    Batch(BatchArgs),
+    /// Choose in and out points for each input from thumbnail previews, then render the grid
+    Pick(PickArgs),
+    /// Re-render an output from the job embedded in its metadata. Any options given after the
+    /// output override the embedded ones. A grid that would replace the replayed output is written
+    /// next to it as `<name>_replay.<ext>` instead
+    Replay {
+        /// An output previously rendered by vidgrid
+        output: PathBuf,
+        /// Options overriding those of the embedded job, e.g. `--width 1280 -o new.mp4`
+        #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+        overrides: Vec<String>,
+    },
+    /// Reveal which input is behind each label of a `--blind` test
+    Reveal {
+        /// The sealed answer key written by `--blind`
+        key: PathBuf,
+    },
+    /// Compare two outputs frame by frame, e.g. before and after a change to vidgrid, writing a
+    /// video of where they differ and printing statistics of the differences as JSON
+    Diff(DiffArgs),
+    /// Print how a grid described in JSON would be rendered, without probing or rendering
+    /// anything: where its cells go and the ffmpeg command, as JSON, e.g. to preview a layout
+    /// before the grid is rendered on another machine
+    Plan {
+        /// The grid, with the size, frame rate and duration of every video file it reads, e.g.
+        /// `{"width": 1280, "height": 720, "cells": [{"source": "a.mp4", "media": {"width":
+        /// 1920, "height": 1080, "fps": 30, "duration": 60}}]}`
+        spec: PathBuf,
+    },
+    /// Encode a clip once per value of an encoder parameter, e.g. `--param crf=18,23,28,33`, and
+    /// render the encodes side by side, labeled with their value, size and quality against the
+    /// clip. The results are also printed as JSON
+    Sweep(SweepArgs),
+    /// Check a job for missing files, impossible layouts, conflicting options and unsupported
+    /// encoder settings without rendering it, printing the problems found as JSON
+    Validate {
+        /// A job file in the JSON form vidgrid embeds in its outputs:
+        /// `{"version": ..., "cwd": ..., "args": [...]}`, or the same keys in a `.toml` file
+        job: PathBuf,
+    },
+    /// Ask for the inputs, layout, labels and output one question at a time, then print the
+    /// equivalent command and render the grid
+    Wizard,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BatchArgs {
+    /// A JSON array of manifests, each describing a grid as for `--manifest`. Relative input
+    /// paths are relative to this file
+    pub list: PathBuf,
+
+    /// The number of grids rendered at the same time
+    #[clap(long, short = 'j', default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+
+    /// The directory the output of each grid is logged to, as `job_<number>.log`, and the report
+    /// of the run is written to, as `report.json`
+    #[clap(long, default_value = "vidgrid_logs")]
+    pub log_dir: PathBuf,
+
+    /// How long ffprobe and ffmpeg may run on an input of a grid before it is given up on
+    #[clap(long, value_name = "TIME", default_value = "60", value_parser = parse_seconds)]
+    pub input_timeout: f64,
+
+    /// How many times an input is probed before it is quarantined
+    #[clap(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u16).range(1..))]
+    pub probe_attempts: u16,
+
+    /// Where inputs that fail every probe are moved, each with a `<name>.reason.txt` explaining
+    /// why. The grids using them are failed without being rendered
+    #[clap(long, value_name = "DIR", default_value = "quarantine")]
+    pub quarantine_dir: PathBuf,
+
+    /// Stop a grid that is still rendering after this long, reporting it as timed out
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds)]
+    pub job_timeout: Option<f64>,
+
+    /// Skip grids whose output is newer than every one of their input files, reporting them as
+    /// cached, e.g. to resume a nightly run
+    #[clap(long)]
+    pub skip_existing: bool,
+
+    /// Options for rendering every grid, given after `--`, overriding those of the manifests,
+    /// e.g. `-- --codec h265`
+    #[clap(last = true)]
+    pub grid_args: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    /// The output rendered before the change
+    pub old: PathBuf,
+
+    /// The output rendered after the change
+    pub new: PathBuf,
+
+    /// Where to write the video of the differences, amplified so that small ones are visible
+    #[clap(long, short = 'o', default_value = "diff.mp4")]
+    pub output: PathBuf,
+
+    /// Differences of at most this much, on a scale of 0 to 255, don't count as divergence, e.g.
+    /// to allow for encoders that aren't bit-exact
+    #[clap(long, default_value_t = 0)]
+    pub threshold: u8,
+
+    /// Also measure the PSNR, SSIM and, when ffmpeg has libvmaf, the VMAF of the new output
+    /// against the old one
+    #[clap(long)]
+    pub metrics: bool,
+
+    /// Fail when an assertion about the report holds, e.g. `metrics:vmaf<85` or
+    /// `max_difference>8`, instead of whenever the outputs diverge. May be given several times
+    #[clap(long, value_name = "ASSERTION")]
+    pub fail_on: Vec<Assertion>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SweepArgs {
+    /// The clip encoded at every value
+    #[clap(long, short = 'i')]
+    pub input: PathBuf,
+
+    /// The parameter swept and its values, as `<name>=<value>,<value>...`. `codec`, `crf`,
+    /// `preset` and `pix_fmt` work like the options of the same name; any other name is passed
+    /// to ffmpeg as an output option, e.g. `b:v=1M,2M,4M`
+    #[clap(long, value_name = "NAME=VALUES")]
+    pub param: SweepParam,
+
+    /// The codec of the encodes, unless it is the parameter swept
+    #[clap(long, value_enum)]
+    pub codec: Option<VideoCodec>,
+
+    /// Encode and compare only this much of the clip, in seconds or `[hh:]mm:ss`
+    #[clap(long, value_name = "TIME", value_parser = parse_seconds)]
+    pub duration: Option<f64>,
+
+    /// The directory the encodes are written to, as `<name>_<value>.mp4`
+    #[clap(long, default_value = "sweep")]
+    pub work_dir: PathBuf,
+
+    /// Where to write the grid of the encodes
+    #[clap(long, short = 'o', default_value = "sweep.mp4")]
+    pub output: PathBuf,
+
+    /// Don't report each encode as it finishes
+    #[clap(long, short = 'q')]
+    pub quiet: bool,
+
+    /// Options for rendering the grid, given after `--`, e.g. `-- --width 3840`
+    #[clap(last = true)]
+    pub grid_args: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct PickArgs {
+    /// The video files to pick clips from, in reading order of the grid
+    #[clap(required = true, help_heading = "INPUT")]
+    pub inputs: Vec<PathBuf>,
+
+    /// The number of thumbnails shown per preview
+    #[clap(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    pub thumbnails: u32,
+
+    /// The directory preview images are written to
+    #[clap(long, default_value = "vidgrid_previews")]
+    pub preview_dir: PathBuf,
+
+    /// Where to save the picks, as an OpenTimelineIO timeline usable with `--edl`
+    #[clap(long, default_value = "picks.otio")]
+    pub picks: PathBuf,
+
+    /// Options for rendering the grid, given after `--`, e.g. `-- --width 1280 -o grid.mp4`
+    #[clap(last = true)]
+    pub grid_args: Vec<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct AudioGridArgs {
+    /// The audio files to visualize, in reading order of the grid
+    #[clap(required = true, help_heading = "INPUT")]
+    pub inputs: Vec<PathBuf>,
+
+    /// How each audio file is drawn
+    #[clap(long, value_enum, default_value_t = Visualizer::Waveform)]
+    pub visualizer: Visualizer,
+
+    /// The resolution width of the output video file
+    #[clap(long, default_value_t = 1920)]
+    pub width: u32,
+
+    /// The resolution height of the output video file
+    #[clap(long, default_value_t = 1080)]
+    pub height: u32,
+
+    /// the maximum length of the output video in seconds. Audio longer than this will be truncated.
+    #[clap(long, default_value_t = u32::MAX)]
+    pub duration: u32,
+
+    /// The frame rate of the output video
+    #[clap(long, default_value_t = 30.0)]
+    pub framerate: f64,
+
+    /// The path to which to write the output video file
     #[clap(
         long,
         short = 'o',
@@ -48,3 +1019,62 @@ pub struct Args {
     #[clap(long)]
     pub open: bool,
 }
+
+/// Parses a per-cell option of the form `<cell>:<value>`, where cell is 1-based
+pub fn parse_cell_option<T>(s: &str) -> Result<(usize, T), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let (cell, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected <cell>:<value>, got '{}'", s))?;
+    let value = T::from_str(value).map_err(|e| format!("Invalid value '{}': {}", value, e))?;
+    Ok((parse_cell_number(cell)?, value))
+}
+
+/// Parses a video stream selection of the form `<cell>:v:<index>` (or `<cell>:<index>`)
+fn parse_stream_option(s: &str) -> Result<(usize, usize), String> {
+    let (cell, stream) = parse_cell_option::<String>(s)?;
+    let index = stream.strip_prefix("v:").unwrap_or(&stream);
+    let index = usize::from_str(index)
+        .map_err(|_| format!("Invalid video stream '{}', expected v:<index>", stream))?;
+    Ok((cell, index))
+}
+
+/// Parses a time within a cell's input of the form `<cell>:<time>`
+fn parse_time_option(s: &str) -> Result<(usize, f64), String> {
+    let (cell, time) = parse_cell_option::<String>(s)?;
+    Ok((cell, parse_seconds(&time)?))
+}
+
+/// Parses a wall clock of the form `<cell>:<ISO 8601 date and time>`
+fn parse_wall_clock_option(s: &str) -> Result<(usize, f64), String> {
+    let (cell, time) = parse_cell_option::<String>(s)?;
+    Ok((cell, parse_wall_clock(&time)?))
+}
+
+/// Parses a decryption key of the form `<cell>:<hex key>`
+fn parse_decryption_key_option(s: &str) -> Result<(usize, String), String> {
+    let (cell, key) = parse_cell_option::<String>(s)?;
+    if key.is_empty() || key.len() % 2 != 0 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid decryption key '{}', expected hex", key));
+    }
+    Ok((cell, key))
+}
+
+/// Parses a fraction between 0 and 1
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match f64::from_str(s) {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("Invalid fraction '{}', expected 0 to 1", s)),
+    }
+}
+
+/// Parses a variable of the form `<name>=<value>`
+fn parse_variable(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("Expected <name>=<value>, got '{}'", s)),
+    }
+}
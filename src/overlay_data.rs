@@ -0,0 +1,99 @@
+#[cfg(feature = "render")]
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::time::parse_seconds;
+use crate::VidGridError;
+
+/// How long an event stays on screen when the data file does not say
+const DEFAULT_EVENT_DURATION: f64 = 2.0;
+
+/// A timestamped event drawn as a caption on a cell
+#[derive(Debug, Clone)]
+pub struct DataEvent {
+    /// Time in the cell's source, in seconds, at which the event appears
+    pub time: f64,
+    /// How long the event stays on screen, in seconds
+    pub duration: f64,
+    /// The caption text
+    pub text: String,
+}
+
+/// Reads events from a CSV file with `time,text[,duration]` rows.
+///
+/// Times may be plain seconds or `hh:mm:ss.ms` timestamps. A header row is skipped, as are
+/// empty lines and lines starting with `#`. Text containing commas can be quoted.
+#[cfg(feature = "render")]
+pub fn read_events(path: &Path) -> Result<Vec<DataEvent>, VidGridError> {
+    parse_events(&fs::read_to_string(path)?, path)
+}
+
+/// Parses events written as `read_events` reads them, e.g. from a file read elsewhere. Errors
+/// name the file as `path`.
+pub fn parse_events(contents: &str, path: &Path) -> Result<Vec<DataEvent>, VidGridError> {
+    let mut events = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        let time = match fields.first().map(|time| parse_seconds(time)) {
+            Some(Ok(time)) => time,
+            // Tolerate a header row
+            _ if line_number == 0 => continue,
+            _ => {
+                return Err(VidGridError::Parse(format!(
+                    "{}:{}: expected a time in the first column",
+                    path.display(),
+                    line_number + 1
+                )))
+            }
+        };
+        let text = fields.get(1).cloned().unwrap_or_default();
+        let duration = match fields.get(2) {
+            Some(duration) => f64::from_str(duration.trim()).map_err(|_| {
+                VidGridError::Parse(format!(
+                    "{}:{}: invalid duration '{}'",
+                    path.display(),
+                    line_number + 1,
+                    duration
+                ))
+            })?,
+            None => DEFAULT_EVENT_DURATION,
+        };
+
+        events.push(DataEvent {
+            time,
+            duration,
+            text,
+        });
+    }
+
+    Ok(events)
+}
+
+/// Splits a CSV line into fields, honoring double-quoted fields
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
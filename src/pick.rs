@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use vidgrid::get_video_duration;
+use vidgrid::time::parse_seconds;
+
+use crate::edl::{self, EdlClip};
+use crate::options::PickArgs;
+use crate::prompt::prompt;
+
+/// Lets the user choose in and out points for each input from thumbnail strips, then renders the
+/// grid of the picked clips.
+///
+/// The picks are saved as an OpenTimelineIO timeline, so the same grid can be rendered again with
+/// `--edl` without picking anew.
+pub fn run(args: &PickArgs) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(&args.preview_dir)?;
+
+    let mut clips = Vec::new();
+    for (index, path) in args.inputs.iter().enumerate() {
+        let duration = get_video_duration(path)?;
+        let strip = args
+            .preview_dir
+            .join(format!("input{}_strip.png", index + 1));
+        println!("{} ({:.1}s)", path.display(), duration);
+
+        let mut start = 0.0;
+        let mut end = duration;
+        loop {
+            render_strip(path, start, end, args.thumbnails, &strip)?;
+            show_strip(&strip)?;
+            let step = (end - start) / args.thumbnails as f64;
+            let times: Vec<String> = (0..args.thumbnails)
+                .map(|thumbnail| format!("{:.1}s", start + step * thumbnail as f64))
+                .collect();
+            println!("  Thumbnails at {}", times.join(" "));
+
+            start = prompt_seconds("  In point", start)?;
+            end = prompt_seconds("  Out point", end)?.min(duration);
+            if end <= start {
+                println!("  The out point must come after the in point");
+                (start, end) = (0.0, duration);
+                continue;
+            }
+
+            // Show the picked range so it can be checked before rendering the whole grid
+            render_strip(path, start, end, args.thumbnails, &strip)?;
+            show_strip(&strip)?;
+            let answer = prompt(&format!("  Keep {:.1}s to {:.1}s? [Y/n]", start, end))?;
+            if !answer.eq_ignore_ascii_case("n") {
+                break;
+            }
+        }
+
+        clips.push(EdlClip {
+            path: fs::canonicalize(path)?,
+            start,
+            end,
+        });
+    }
+
+    edl::write_otio(&clips, &args.picks)?;
+    println!("Picks saved to {}", args.picks.display());
+
+    let mut grid_args = vec!["--edl".to_string(), args.picks.display().to_string()];
+    grid_args.extend(args.grid_args.iter().cloned());
+    let grid: crate::options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(grid_args.iter().cloned()),
+    )?;
+    crate::run_grid(&grid, &grid_args)
+}
+
+/// Renders `count` evenly spaced thumbnails of a video between `start` and `end` side by side
+/// into a single image
+fn render_strip(
+    path: &Path,
+    start: f64,
+    end: f64,
+    count: u32,
+    strip: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let status = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-ss")
+        .arg(start.to_string())
+        .arg("-to")
+        .arg(end.to_string())
+        .arg("-i")
+        .arg(path)
+        .arg("-vf")
+        .arg(format!(
+            "fps={}/{},scale=240:-2,tile={}x1",
+            count,
+            end - start,
+            count
+        ))
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-y")
+        .arg(strip)
+        .status()?;
+
+    if !status.success() {
+        return Err(format!("Failed to render a preview of {}", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Shows a thumbnail strip inline in terminals supporting the kitty graphics protocol, or
+/// points to the image otherwise
+fn show_strip(strip: &Path) -> Result<(), Box<dyn Error>> {
+    if std::env::var_os("KITTY_WINDOW_ID").is_some() {
+        let path = fs::canonicalize(strip)?;
+        // Have the terminal read the PNG from disk rather than streaming it through the tty
+        println!(
+            "\x1b_Gf=100,a=T,t=f;{}\x1b\\",
+            base64(path.to_string_lossy().as_bytes())
+        );
+    } else {
+        println!("  Preview: {}", strip.display());
+    }
+    Ok(())
+}
+
+/// Asks for a time in seconds, keeping `default` if nothing is entered
+fn prompt_seconds(question: &str, default: f64) -> Result<f64, Box<dyn Error>> {
+    loop {
+        let answer = prompt(&format!("{} [{:.1}s]", question, default))?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match parse_seconds(&answer) {
+            Ok(seconds) => return Ok(seconds),
+            Err(e) => println!("  {}", e),
+        }
+    }
+}
+
+/// Standard base64 encoding, as required by terminal graphics escape sequences
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
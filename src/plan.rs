@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::graph::{self, GridGeometry};
+use crate::input::InputSource;
+use crate::probe::{MediaInfo, VideoStream};
+use crate::settings::{GridSettings, Layout};
+use crate::VidGridError;
+
+/// How a grid would be rendered, worked out from described inputs without running ffmpeg or
+/// ffprobe, by the same code that builds the command of a render
+#[derive(Debug, Clone, Serialize)]
+pub struct GridPlan {
+    /// Width of the grid, before any timeline strip or rotation
+    pub width: u32,
+    /// Height of the grid, before any timeline strip or rotation
+    pub height: u32,
+    /// Where every cell goes
+    pub geometry: GridGeometry,
+    /// Duration of the output in seconds
+    pub duration: u32,
+    /// Frame rate of the output
+    pub framerate: f64,
+    /// The ffmpeg command rendering the grid, program first
+    pub command: Vec<String>,
+    /// The filters of the command's filtergraph, one per line
+    pub filtergraph: Vec<String>,
+}
+
+/// Plans a grid of `cells` whose video files are described by `media`, in the order of the file
+/// cells. The plan is that of a plain render: pre-scaling, the health overlay, loudness
+/// measurements and atomic writes are left to the machine rendering the grid, as they depend on
+/// its processes and files.
+pub fn plan_video_grid(
+    cells: &[Cell],
+    settings: &GridSettings,
+    media: &[MediaInfo],
+) -> Result<GridPlan, VidGridError> {
+    let settings = GridSettings {
+        prescale: false,
+        health_overlay: false,
+        measure_loudness: false,
+        atomic: false,
+        print_commands: false,
+        dry_run: false,
+        ..settings.clone()
+    };
+    let grid = graph::build(
+        cells,
+        &settings,
+        &graph::Inputs {
+            media,
+            scratch: None,
+            health_dir: None,
+            loudness_dir: None,
+            partial_path: None,
+        },
+    )?;
+    Ok(GridPlan {
+        width: settings.width,
+        height: settings.height,
+        geometry: grid.geometry,
+        duration: grid.info.duration,
+        framerate: grid.info.framerate,
+        command: std::iter::once("ffmpeg".to_string())
+            .chain(grid.args)
+            .collect(),
+        filtergraph: graph::filtergraph_lines(&grid.filter_complex),
+    })
+}
+
+/// A grid to plan as JSON, e.g.
+///
+/// ```json
+/// {
+///   "width": 1280, "height": 720, "layout": "grid", "gap": 4,
+///   "cells": [
+///     { "source": "cam1.mp4", "label": "Camera 1",
+///       "media": { "width": 1920, "height": 1080, "fps": 30, "duration": 60 } },
+///     { "source": "cam2.mp4",
+///       "media": { "width": 1280, "height": 720, "fps": 25, "duration": 45, "audio_streams": 1 } },
+///     { "source": "onion:1,2:0.5" }
+///   ]
+/// }
+/// ```
+///
+/// Cells are given as on the command line. Video files are described rather than probed.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlanSpec {
+    width: Option<u32>,
+    height: Option<u32>,
+    /// Most seconds of the grid
+    duration: Option<u32>,
+    max_framerate: Option<f64>,
+    layout: Option<String>,
+    columns: Option<usize>,
+    rows: Option<usize>,
+    #[serde(default)]
+    gap: u32,
+    #[serde(default)]
+    border: u32,
+    output: Option<PathBuf>,
+    cells: Vec<CellSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CellSpec {
+    source: String,
+    label: Option<String>,
+    /// What probing the cell's video file would find. Only file cells are described
+    media: Option<MediaSpec>,
+}
+
+/// What ffprobe would report about a video file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediaSpec {
+    width: u32,
+    height: u32,
+    fps: f64,
+    duration: f64,
+    #[serde(default)]
+    audio_streams: usize,
+    #[serde(default)]
+    timecode: Option<String>,
+}
+
+impl MediaSpec {
+    fn media_info(&self) -> MediaInfo {
+        MediaInfo {
+            duration: self.duration,
+            video: vec![VideoStream {
+                width: self.width,
+                height: self.height,
+                fps: self.fps,
+                rotation: None,
+                codec: String::new(),
+                // Untagged, so the usual convention holds
+                color_matrix: if self.height >= 720 { "bt709" } else { "bt601" },
+                full_range: false,
+                color_primaries: None,
+            }],
+            audio_streams: self.audio_streams,
+            timecode: self.timecode.clone(),
+        }
+    }
+}
+
+/// Plans the grid described by a [`PlanSpec`] in JSON, returning the [`GridPlan`] as JSON
+pub fn plan_json(spec: &str) -> Result<String, VidGridError> {
+    let spec: PlanSpec = serde_json::from_str(spec)
+        .map_err(|e| VidGridError::Parse(format!("Invalid grid plan: {}", e)))?;
+    let defaults = GridSettings::default();
+    let layout = match &spec.layout {
+        Some(layout) => Layout::from_str(layout, true)
+            .map_err(|_| VidGridError::Parse(format!("Invalid layout '{}'", layout)))?,
+        None => defaults.layout,
+    };
+    let settings = GridSettings {
+        width: spec.width.unwrap_or(defaults.width),
+        height: spec.height.unwrap_or(defaults.height),
+        duration: spec.duration.unwrap_or(defaults.duration),
+        max_framerate: spec.max_framerate.unwrap_or(defaults.max_framerate),
+        layout,
+        columns: spec.columns,
+        rows: spec.rows,
+        gap: spec.gap,
+        border: spec.border,
+        output_path: spec.output.unwrap_or(defaults.output_path.clone()),
+        ..defaults
+    };
+
+    let mut cells = Vec::new();
+    let mut media = Vec::new();
+    for (index, entry) in spec.cells.iter().enumerate() {
+        let source: InputSource = entry.source.parse().map_err(VidGridError::Parse)?;
+        match (&source, &entry.media) {
+            (InputSource::File(_) | InputSource::Audio { .. }, Some(described)) => {
+                media.push(described.media_info())
+            }
+            (InputSource::File(_) | InputSource::Audio { .. }, None) => {
+                return Err(VidGridError::Parse(format!(
+                    "Cell {} reads a file, which needs its `media` described",
+                    index + 1
+                )))
+            }
+            _ => {}
+        }
+        let mut cell = Cell::new(source);
+        cell.label = entry.label.clone();
+        cells.push(cell);
+    }
+
+    let plan = plan_video_grid(&cells, &settings, &media)?;
+    serde_json::to_string(&plan).map_err(|e| VidGridError::Parse(e.to_string()))
+}
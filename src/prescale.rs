@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{process, VidGridError};
+
+/// A temporary directory for intermediate files, removed with everything in it when dropped
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn create(name: &str) -> Result<Self, VidGridError> {
+        let path = std::env::temp_dir().join(format!("vidgrid_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&path)?;
+        Ok(ScratchDir(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Builds an ffmpeg command that reads a video stream with `input_args` (e.g. trimming it) and
+/// shrinks it to fit within `width`x`height`, writing a lossless intermediate to `output`. The
+/// size is kept even, as yuv420p chroma needs
+pub fn prescale_command(
+    input: &Path,
+    stream: usize,
+    input_args: &[String],
+    width: u32,
+    height: u32,
+    output: &Path,
+) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-v")
+        .arg("error")
+        .args(input_args)
+        .arg("-i")
+        .arg(input)
+        .arg("-map")
+        .arg(format!("0:v:{}", stream))
+        .arg("-vf")
+        .arg(format!(
+            "scale={}:{}:force_original_aspect_ratio=decrease:force_divisible_by=2",
+            width, height
+        ))
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-qp")
+        .arg("0")
+        .arg("-preset")
+        .arg("ultrafast")
+        .arg("-an")
+        .arg("-y")
+        .arg(output);
+    command
+}
+
+/// Runs the commands with at most `max_running` processes at a time, one per CPU core by default,
+/// failing if any of them runs for longer than `timeout`
+pub fn run_parallel(
+    commands: Vec<Command>,
+    max_running: Option<usize>,
+    timeout: Option<Duration>,
+) -> Result<(), VidGridError> {
+    let max_running =
+        max_running.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let mut running: Vec<(Child, Instant)> = Vec::new();
+    for mut command in commands {
+        if running.len() >= max_running {
+            wait_for(running.remove(0), timeout)?;
+        }
+        running.push((command.spawn()?, Instant::now()));
+    }
+    for child in running {
+        wait_for(child, timeout)?;
+    }
+
+    Ok(())
+}
+
+fn wait_for(
+    (mut child, started): (Child, Instant),
+    timeout: Option<Duration>,
+) -> Result<(), VidGridError> {
+    let Some(status) = process::wait_until(&mut child, started, timeout)? else {
+        return Err(VidGridError::TimedOut {
+            task: "pre-scale an input".to_string(),
+            after: timeout.unwrap_or_default(),
+        });
+    };
+    if !status.success() {
+        return Err(VidGridError::Ffmpeg {
+            task: "pre-scale an input".to_string(),
+            status,
+        });
+    }
+    Ok(())
+}
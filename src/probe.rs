@@ -0,0 +1,213 @@
+use std::path::Path;
+#[cfg(feature = "render")]
+use std::time::Duration;
+
+use serde_json::Value;
+
+#[cfg(feature = "render")]
+use crate::ffprobe;
+use crate::settings::Rotation;
+use crate::VidGridError;
+
+/// What ffprobe knows about a media file
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    /// Duration of the file in seconds
+    pub duration: f64,
+    /// The video streams of the file, in order. Cover art is left out
+    pub video: Vec<VideoStream>,
+    /// The number of audio streams of the file
+    pub audio_streams: usize,
+    /// The timecode of the file's first frame, e.g. `01:00:00;00` from a professional camera,
+    /// when it has one
+    pub timecode: Option<String>,
+}
+
+/// What ffprobe knows about a video stream
+#[derive(Debug, Clone)]
+pub struct VideoStream {
+    /// Width of the coded frames, before they are rotated for display
+    pub width: u32,
+    /// Height of the coded frames, before they are rotated for display
+    pub height: u32,
+    /// Frame rate of the stream
+    pub fps: f64,
+    /// How the frames must be turned clockwise to be shown upright, e.g. for videos shot on a
+    /// phone held upright
+    pub rotation: Option<Rotation>,
+    /// Name of the stream's codec, e.g. `h264`
+    pub codec: String,
+    /// The color matrix of the stream, as understood by the `scale` filter's `in_color_matrix`
+    /// option. Untagged streams are assumed to follow the usual convention of BT.601 for SD and
+    /// BT.709 for HD
+    pub color_matrix: &'static str,
+    /// Whether the stream's values span their full range, as JPEG does, rather than the limited
+    /// range of video. Untagged streams are assumed to be limited
+    pub full_range: bool,
+    /// The color primaries of the stream, as understood by the `colorspace` filter's `iall`
+    /// option, when they differ from the BT.709 primaries of the grid. Untagged streams are
+    /// assumed to share them
+    pub color_primaries: Option<&'static str>,
+}
+
+impl MediaInfo {
+    /// Probes a file with a single run of ffprobe
+    #[cfg(feature = "render")]
+    pub fn probe(path: &Path) -> Result<Self, VidGridError> {
+        Self::probe_within(path, None)
+    }
+
+    /// Probes a file with a single run of ffprobe, giving up once it has run for `timeout`, e.g.
+    /// on a truncated file ffprobe keeps searching for streams in
+    #[cfg(feature = "render")]
+    pub fn probe_within(path: &Path, timeout: Option<Duration>) -> Result<Self, VidGridError> {
+        let output = ffprobe(
+            path,
+            &["-print_format", "json", "-show_streams", "-show_format"],
+            timeout,
+        )?;
+        Self::from_json(path, &output)
+    }
+
+    /// Reads what `ffprobe -print_format json -show_streams -show_format` printed for the file at
+    /// `path`, e.g. when ffprobe was run elsewhere
+    pub fn from_json(path: &Path, output: &str) -> Result<Self, VidGridError> {
+        let invalid = |message: String| {
+            VidGridError::Parse(format!(
+                "Invalid ffprobe output for {}: {}",
+                path.display(),
+                message
+            ))
+        };
+        let probe: Value = serde_json::from_str(output).map_err(|e| invalid(e.to_string()))?;
+
+        // ffprobe prints numbers of the format as strings
+        let duration = probe["format"]["duration"]
+            .as_str()
+            .and_then(|duration| duration.parse().ok())
+            .ok_or_else(|| invalid("no duration".to_string()))?;
+
+        let streams = probe["streams"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let mut video = Vec::new();
+        let mut audio_streams = 0;
+        for stream in streams {
+            match stream["codec_type"].as_str() {
+                Some("video") if stream["disposition"]["attached_pic"] != 1 => {
+                    video.push(VideoStream::parse(stream).map_err(invalid)?)
+                }
+                Some("audio") => audio_streams += 1,
+                _ => {}
+            }
+        }
+
+        // Kept in the tags of the format, the video stream or a timecode stream of its own
+        let timecode = std::iter::once(&probe["format"])
+            .chain(streams)
+            .find_map(|tagged| tagged["tags"]["timecode"].as_str())
+            .map(str::to_string);
+
+        Ok(MediaInfo {
+            duration,
+            video,
+            audio_streams,
+            timecode,
+        })
+    }
+
+    /// The video stream with the given index among the video streams, failing clearly if the
+    /// file has no such stream
+    pub fn video_stream(&self, path: &Path, stream: usize) -> Result<&VideoStream, VidGridError> {
+        self.video.get(stream).ok_or_else(|| {
+            VidGridError::InvalidGrid(match self.video.len() {
+                0 => format!("{} has no video stream", path.display()),
+                count => format!(
+                    "{} has no video stream {}, only {} of them",
+                    path.display(),
+                    stream,
+                    count
+                ),
+            })
+        })
+    }
+
+    /// Whether the file has an audio stream with the given index among the audio streams
+    pub fn has_audio(&self, stream: usize) -> bool {
+        stream < self.audio_streams
+    }
+}
+
+impl VideoStream {
+    /// Reads a video stream from the JSON ffprobe prints for it
+    fn parse(stream: &Value) -> Result<Self, String> {
+        let dimension = |key: &str| stream[key].as_u64().unwrap_or(0) as u32;
+        let (width, height) = (dimension("width"), dimension("height"));
+
+        // The frame rate is given as a fraction such as "30000/1001"
+        let fps_str = stream["r_frame_rate"].as_str().unwrap_or_default();
+        let invalid = || format!("invalid frame rate '{}'", fps_str);
+        let fps = match fps_str.split_once('/') {
+            Some((numerator, denominator)) => {
+                let numerator: f64 = numerator.parse().map_err(|_| invalid())?;
+                let denominator: f64 = denominator.parse().map_err(|_| invalid())?;
+                if denominator == 0.0 {
+                    return Err(invalid());
+                }
+                numerator / denominator
+            }
+            None => fps_str.parse().map_err(|_| invalid())?,
+        };
+
+        // Newer ffmpeg reports the counterclockwise angle of the display matrix, older ffmpeg a
+        // clockwise `rotate` tag
+        let display_matrix = stream["side_data_list"]
+            .as_array()
+            .and_then(|side_data| side_data.iter().find_map(|data| data["rotation"].as_f64()))
+            .map(|rotation| -rotation);
+        let tag = stream["tags"]["rotate"]
+            .as_str()
+            .and_then(|rotate| rotate.parse::<f64>().ok());
+        let degrees = display_matrix.or(tag).map(|degrees| degrees.round() as i64);
+        let rotation = match degrees.map(|degrees| degrees.rem_euclid(360)) {
+            Some(90) => Some(Rotation::Quarter),
+            Some(180) => Some(Rotation::Half),
+            Some(270) => Some(Rotation::ThreeQuarters),
+            _ => None,
+        };
+
+        let color_matrix = match stream["color_space"].as_str().unwrap_or("unknown") {
+            "bt709" => "bt709",
+            "bt470bg" | "smpte170m" => "bt601",
+            "fcc" => "fcc",
+            "smpte240m" => "smpte240m",
+            "bt2020nc" | "bt2020c" => "bt2020",
+            _ if height >= 720 => "bt709",
+            _ => "bt601",
+        };
+
+        let color_primaries = match stream["color_primaries"].as_str().unwrap_or("unknown") {
+            "bt470m" => Some("bt470m"),
+            "bt470bg" => Some("bt470bg"),
+            "smpte170m" => Some("smpte170m"),
+            "smpte240m" => Some("smpte240m"),
+            "bt2020" => Some("bt2020"),
+            _ => None,
+        };
+
+        Ok(VideoStream {
+            width,
+            height,
+            fps,
+            rotation,
+            codec: stream["codec_name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            color_matrix,
+            full_range: stream["color_range"].as_str() == Some("pc"),
+            color_primaries,
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::{self, Read};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a process with a time limit is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits for `child`, started at `started`, to exit, killing it once it has run for `timeout`.
+/// Returns `None` if it was killed.
+pub fn wait_until(
+    child: &mut Child,
+    started: Instant,
+    timeout: Option<Duration>,
+) -> io::Result<Option<ExitStatus>> {
+    wait_or_kill(child, started, timeout, Child::kill)
+}
+
+/// Starts `command` as the leader of a process group of its own, which the processes it starts
+/// join, so [`wait_group_until`] can stop them all
+pub fn spawn_group(command: &mut Command) -> io::Result<Child> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    command.spawn()
+}
+
+/// Like [`wait_until`] for a `child` started by [`spawn_group`], killing everything it started
+/// along with it, e.g. the ffmpeg of a grid
+pub fn wait_group_until(
+    child: &mut Child,
+    started: Instant,
+    timeout: Option<Duration>,
+) -> io::Result<Option<ExitStatus>> {
+    wait_or_kill(child, started, timeout, kill_group)
+}
+
+fn wait_or_kill(
+    child: &mut Child,
+    started: Instant,
+    timeout: Option<Duration>,
+    kill: fn(&mut Child) -> io::Result<()>,
+) -> io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let left = timeout.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            kill(child)?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(left.min(POLL_INTERVAL));
+    }
+}
+
+/// Kills the process group `child` leads. Where the group can't be signalled, `child` is still
+/// killed on its own
+fn kill_group(child: &mut Child) -> io::Result<()> {
+    let id = child.id().to_string();
+    #[cfg(unix)]
+    let killed = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", id)])
+        .status();
+    #[cfg(windows)]
+    let killed = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &id])
+        .status();
+    #[cfg(not(any(unix, windows)))]
+    let killed: io::Result<ExitStatus> = Err(io::ErrorKind::Unsupported.into());
+    match killed {
+        Ok(status) if status.success() => Ok(()),
+        _ => child.kill(),
+    }
+}
+
+/// Runs `command` and collects its output like [`Command::output`], killing it once it has run
+/// for `timeout`. Returns `None` if it was killed.
+pub(crate) fn output_within(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> io::Result<Option<Output>> {
+    if timeout.is_none() {
+        return command.output().map(Some);
+    }
+    let started = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // The pipes are drained while the process runs, so that it never blocks on a full one
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    // The readers of a killed process are left behind, as whatever it started may still hold
+    // its pipes open
+    let Some(status) = wait_until(&mut child, started, timeout)? else {
+        return Ok(None);
+    };
+    let stdout = stdout.join().expect("reading a pipe panicked")?;
+    let stderr = stderr.join().expect("reading a pipe panicked")?;
+    Ok(Some(Output {
+        status,
+        stdout,
+        stderr,
+    }))
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut bytes)?;
+        }
+        Ok(bytes)
+    })
+}
@@ -0,0 +1,95 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Instant;
+
+use crate::settings::ProgressFormat;
+
+/// How far an encode has come
+struct Progress {
+    /// Seconds of the output encoded so far
+    encoded: f64,
+    /// Encoding speed relative to real time
+    speed: Option<f64>,
+}
+
+/// Runs an ffmpeg command whose global options include `-progress pipe:1`, reporting its progress
+/// through an output of `duration` seconds in `format` as it goes
+pub fn run(command: &mut Command, duration: f64, format: ProgressFormat) -> io::Result<ExitStatus> {
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let started = Instant::now();
+
+    // ffmpeg writes a block of key=value lines per update, each ending with a `progress` line
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut progress = Progress {
+        encoded: 0.0,
+        speed: None,
+    };
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        match line.split_once('=') {
+            Some(("out_time_us", value)) => {
+                if let Ok(micros) = value.trim().parse::<f64>() {
+                    progress.encoded = (micros / 1e6).max(0.0);
+                }
+            }
+            Some(("speed", value)) => {
+                progress.speed = value.trim().trim_end_matches('x').parse().ok();
+            }
+            Some(("progress", state)) => {
+                report(&progress, duration, started, format);
+                if state == "end" {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    if format == ProgressFormat::Bar {
+        eprintln!();
+    }
+
+    child.wait()
+}
+
+/// Prints one progress update
+fn report(progress: &Progress, duration: f64, started: Instant, format: ProgressFormat) {
+    let fraction = if duration > 0.0 {
+        (progress.encoded / duration).min(1.0)
+    } else {
+        0.0
+    };
+    // Assume the rest encodes as fast as what is done so far
+    let elapsed = started.elapsed().as_secs_f64();
+    let eta = (fraction > 0.0).then(|| elapsed * (1.0 - fraction) / fraction);
+
+    match format {
+        ProgressFormat::Bar => {
+            const WIDTH: usize = 30;
+            let filled = (fraction * WIDTH as f64).round() as usize;
+            let eta = match eta {
+                Some(eta) => format!("{}:{:02}", eta as u64 / 60, eta as u64 % 60),
+                None => "--:--".to_string(),
+            };
+            eprint!(
+                "\r[{}{}] {:>3.0}%  ETA {}",
+                "#".repeat(filled),
+                "-".repeat(WIDTH - filled),
+                fraction * 100.0,
+                eta
+            );
+            let _ = io::stderr().flush();
+        }
+        ProgressFormat::Json => {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "percent": fraction * 100.0,
+                    "encoded": progress.encoded,
+                    "duration": duration,
+                    "speed": progress.speed,
+                    "eta": eta,
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+/// Asks a question on the terminal and returns the trimmed answer
+pub fn prompt(question: &str) -> Result<String, Box<dyn Error>> {
+    print!("{}: ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err("Input ended before the question was answered".into());
+    }
+    Ok(answer.trim().to_string())
+}
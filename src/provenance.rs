@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+/// Container metadata key the job spec is stored under
+pub const METADATA_KEY: &str = "vidgrid_job";
+
+/// Options whose values must not end up in outputs
+const SECRET_OPTIONS: &[&str] = &["--decryption-key"];
+
+/// Everything needed to regenerate an output, embedded in the output itself
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobSpec {
+    /// Version of vidgrid that rendered the output
+    pub version: String,
+    /// Working directory the job was run from, against which relative paths are resolved
+    pub cwd: PathBuf,
+    /// Command line arguments of the job, without the program name
+    pub args: Vec<String>,
+}
+
+impl JobSpec {
+    /// Describes the current invocation of vidgrid. Secrets such as decryption keys are left out
+    /// of the recorded arguments.
+    pub fn current(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut recorded = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if SECRET_OPTIONS.contains(&arg.as_str()) {
+                args.next();
+            } else if !SECRET_OPTIONS
+                .iter()
+                .any(|option| arg.starts_with(&format!("{}=", option)))
+            {
+                recorded.push(arg.clone());
+            }
+        }
+
+        Ok(JobSpec {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            cwd: std::env::current_dir()?,
+            args: recorded,
+        })
+    }
+
+    /// Reads the job spec embedded in an output rendered by vidgrid
+    pub fn read_from(output: &Path) -> Result<Self, Box<dyn Error>> {
+        let probe = Command::new("ffprobe")
+            .arg("-v")
+            .arg("error")
+            .arg("-show_entries")
+            .arg(format!("format_tags={}", METADATA_KEY))
+            .arg("-of")
+            .arg("default=noprint_wrappers=1:nokey=1")
+            .arg(output)
+            .output()?;
+
+        if !probe.status.success() {
+            return Err(format!("ffprobe failed for {}", output.display()).into());
+        }
+
+        let spec = String::from_utf8(probe.stdout)?.trim().to_string();
+        if spec.is_empty() {
+            return Err(format!("{} has no embedded vidgrid job", output.display()).into());
+        }
+        Ok(serde_json::from_str(&spec)?)
+    }
+}
@@ -0,0 +1,199 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::hook;
+
+/// The command uploading to YouTube when `--publish youtube` names none, from
+/// https://github.com/porjo/youtubeuploader
+const YOUTUBE_COMMAND: &str = "youtubeuploader -filename {output}";
+
+/// Sends a finished output to where it is published
+pub trait Uploader {
+    /// Uploads `file` and returns where it went
+    fn upload(&self, file: &Path) -> Result<String, Box<dyn Error>>;
+}
+
+/// Where `--publish` sends finished outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishTarget {
+    /// A local or mounted directory the output is copied into
+    Directory(PathBuf),
+    /// An S3 bucket and prefix (`s3://bucket/prefix`), uploaded to with the AWS CLI
+    S3(String),
+    /// A directory on an SSH server (`sftp://user@host[:port]/path`), uploaded to with scp
+    Sftp {
+        host: String,
+        port: Option<u16>,
+        path: String,
+    },
+    /// YouTube, uploaded to with an external command (`youtube[:<command>]`)
+    YouTube(String),
+    /// Any other destination, reached with an external command (`cmd:<command>`)
+    Command(String),
+}
+
+impl FromStr for PublishTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(bucket) = s.strip_prefix("s3://") {
+            if bucket.is_empty() {
+                return Err("Invalid S3 target, expected s3://bucket/prefix".to_string());
+            }
+            return Ok(PublishTarget::S3(s.trim_end_matches('/').to_string()));
+        }
+        if let Some(location) = s.strip_prefix("sftp://") {
+            let (server, path) = location.split_once('/').unwrap_or((location, ""));
+            let (host, port) = match server.rsplit_once(':') {
+                Some((host, port)) => (
+                    host,
+                    Some(
+                        port.parse()
+                            .map_err(|_| format!("Invalid SFTP port '{}'", port))?,
+                    ),
+                ),
+                None => (server, None),
+            };
+            if host.is_empty() {
+                return Err("Invalid SFTP target, expected sftp://user@host/path".to_string());
+            }
+            return Ok(PublishTarget::Sftp {
+                host: host.to_string(),
+                port,
+                path: format!("/{}", path),
+            });
+        }
+        if s == "youtube" {
+            return Ok(PublishTarget::YouTube(YOUTUBE_COMMAND.to_string()));
+        }
+        if let Some(command) = s.strip_prefix("youtube:") {
+            return Ok(PublishTarget::YouTube(command.to_string()));
+        }
+        if let Some(command) = s.strip_prefix("cmd:") {
+            return Ok(PublishTarget::Command(command.to_string()));
+        }
+        Ok(PublishTarget::Directory(PathBuf::from(s)))
+    }
+}
+
+impl PublishTarget {
+    /// The uploader sending outputs to the target
+    pub fn uploader(&self) -> Box<dyn Uploader> {
+        match self {
+            PublishTarget::Directory(dir) => Box::new(LocalCopy { dir: dir.clone() }),
+            PublishTarget::S3(prefix) => Box::new(S3 {
+                prefix: prefix.clone(),
+            }),
+            PublishTarget::Sftp { host, port, path } => Box::new(Sftp {
+                host: host.clone(),
+                port: *port,
+                path: path.clone(),
+            }),
+            PublishTarget::YouTube(command) | PublishTarget::Command(command) => {
+                Box::new(ExternalCommand {
+                    template: command.clone(),
+                })
+            }
+        }
+    }
+}
+
+/// Copies outputs into a directory, creating it if needed
+struct LocalCopy {
+    dir: PathBuf,
+}
+
+impl Uploader for LocalCopy {
+    fn upload(&self, file: &Path) -> Result<String, Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Cannot create {}: {}", self.dir.display(), e))?;
+        let copy = self.dir.join(file.file_name().unwrap_or_default());
+        fs::copy(file, &copy).map_err(|e| format!("Cannot copy to {}: {}", copy.display(), e))?;
+        Ok(copy.display().to_string())
+    }
+}
+
+/// Uploads outputs under an S3 prefix with `aws s3 cp`, using the AWS CLI's own credentials
+struct S3 {
+    prefix: String,
+}
+
+impl Uploader for S3 {
+    fn upload(&self, file: &Path) -> Result<String, Box<dyn Error>> {
+        let object = format!(
+            "{}/{}",
+            self.prefix,
+            file.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let mut command = Command::new("aws");
+        command
+            .args(["s3", "cp", "--only-show-errors"])
+            .arg(file)
+            .arg(&object);
+        run("aws", command)?;
+        Ok(object)
+    }
+}
+
+/// Uploads outputs into a directory on an SSH server with `scp`, authenticating with the SSH
+/// agent or keys, as there is no one to type a password
+struct Sftp {
+    host: String,
+    port: Option<u16>,
+    path: String,
+}
+
+impl Uploader for Sftp {
+    fn upload(&self, file: &Path) -> Result<String, Box<dyn Error>> {
+        let remote = format!(
+            "{}:{}/{}",
+            self.host,
+            self.path.trim_end_matches('/'),
+            file.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let mut command = Command::new("scp");
+        command.args(["-q", "-B"]);
+        if let Some(port) = self.port {
+            command.arg("-P").arg(port.to_string());
+        }
+        command.arg(file).arg(&remote);
+        run("scp", command)?;
+        Ok(remote)
+    }
+}
+
+/// Hands outputs to a command template, with `{output}` replaced by the output's path
+struct ExternalCommand {
+    template: String,
+}
+
+impl Uploader for ExternalCommand {
+    fn upload(&self, file: &Path) -> Result<String, Box<dyn Error>> {
+        let printed = hook::run_hook(&self.template, file, Some(file))?;
+        // Uploaders usually finish by printing where the video went, e.g. its URL
+        Ok(printed
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map_or_else(|| self.template.clone(), |line| line.trim().to_string()))
+    }
+}
+
+/// Runs an uploading program, failing with what it printed if it fails
+fn run(program: &str, mut command: Command) -> Result<(), Box<dyn Error>> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Cannot run '{}': {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'{}' failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+#[cfg(feature = "render")]
+use std::{fs, path::Path, process::Command, time::SystemTime};
+
+#[cfg(feature = "render")]
+use crate::VidGridError;
+
+/// Seconds of the grid in every segment of a replay buffer. Exports are cut on segment
+/// boundaries, so they run up to this much longer than the buffer
+pub const SEGMENT_SECONDS: u32 = 10;
+
+/// A rolling recording of the last minutes of the grid, kept alongside its output as a ring of
+/// short segments that are overwritten oldest first. [`ReplayBuffer::export`] joins them into
+/// one file, e.g. to replay what just happened on a live stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayBuffer {
+    /// Directory the segments are written to, as `segment_<n>.ts`
+    pub dir: PathBuf,
+    /// Seconds of the grid kept
+    pub length: f64,
+}
+
+impl ReplayBuffer {
+    /// How many segments the ring holds: enough for `length`, plus the one being written
+    pub fn segments(&self) -> u32 {
+        (self.length / f64::from(SEGMENT_SECONDS)).ceil() as u32 + 1
+    }
+
+    /// The ffmpeg output options writing the segments from the `video` and `audio` pads of the
+    /// filtergraph. Keyframes are forced on the segment boundaries, so every segment plays on
+    /// its own
+    pub(crate) fn output_args(
+        &self,
+        video: &str,
+        audio: Option<&str>,
+        duration: u32,
+    ) -> Vec<String> {
+        let mut args = vec!["-map".to_string(), video.to_string()];
+        if let Some(audio) = audio {
+            args.extend(["-map", audio, "-c:a", "aac"].map(String::from));
+        }
+        args.extend(
+            [
+                "-t",
+                &duration.to_string(),
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-pix_fmt",
+                "yuv420p",
+                "-force_key_frames",
+                &format!("expr:gte(t,n_forced*{})", SEGMENT_SECONDS),
+                "-f",
+                "segment",
+                "-segment_time",
+                &SEGMENT_SECONDS.to_string(),
+                "-segment_wrap",
+                &self.segments().to_string(),
+                "-segment_format",
+                "mpegts",
+                "-reset_timestamps",
+                "1",
+            ]
+            .map(String::from),
+        );
+        args.push(
+            self.dir
+                .join("segment_%03d.ts")
+                .to_string_lossy()
+                .into_owned(),
+        );
+        args
+    }
+
+    /// The segments written so far, oldest first
+    #[cfg(feature = "render")]
+    fn written_segments(&self) -> Result<Vec<PathBuf>, VidGridError> {
+        let mut segments: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with("segment_") && name.ends_with(".ts") {
+                segments.push((entry.metadata()?.modified()?, entry.path()));
+            }
+        }
+        segments.sort();
+        Ok(segments.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Removes the segments an earlier recording left in the directory, so exports only join
+    /// segments of this one
+    #[cfg(feature = "render")]
+    pub fn clear(&self) -> Result<(), VidGridError> {
+        for segment in self.written_segments()? {
+            fs::remove_file(segment)?;
+        }
+        Ok(())
+    }
+
+    /// Joins the segments recorded so far into `path`, without encoding them again. The segment
+    /// still being written is included up to where ffmpeg has got with it
+    #[cfg(feature = "render")]
+    pub fn export(&self, path: &Path) -> Result<(), VidGridError> {
+        let segments = self.written_segments()?;
+        if segments.is_empty() {
+            return Err(VidGridError::InvalidGrid(
+                "Nothing has been recorded to replay yet".to_string(),
+            ));
+        }
+        // The concat demuxer reads paths relative to the list, quoted like shell words
+        let list = self.dir.join("replay.txt");
+        let entries: String = segments
+            .iter()
+            .filter_map(|segment| segment.file_name())
+            .map(|name| format!("file '{}'\n", name.to_string_lossy().replace('\'', "'\\''")))
+            .collect();
+        fs::write(&list, entries)?;
+        let status = Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin"])
+            .args(["-f", "concat", "-safe", "0", "-i"])
+            .arg(&list)
+            .args(["-c", "copy", "-y"])
+            .arg(path)
+            .status();
+        let _ = fs::remove_file(&list);
+        let status = status?;
+        if !status.success() {
+            return Err(VidGridError::Ffmpeg {
+                task: "export the replay".to_string(),
+                status,
+            });
+        }
+        Ok(())
+    }
+}
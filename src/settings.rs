@@ -0,0 +1,640 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::encoder::EncoderOptions;
+use crate::escape::escape_filter_option;
+use crate::input::parse_cell_number;
+use crate::replay::ReplayBuffer;
+use crate::timeline::TimelineEvent;
+
+/// Output-level settings shared by every cell of the grid
+#[derive(Debug, Clone)]
+pub struct GridSettings {
+    /// Width of the output video
+    pub width: u32,
+    /// Height of the output video
+    pub height: u32,
+    /// Maximum duration of the output video in seconds
+    pub duration: u32,
+    /// Maximum frame rate for the output video
+    pub max_framerate: f64,
+    /// Color range the output is converted to and tagged with
+    pub color_range: ColorRange,
+    /// Colorspace the output is converted to and tagged with
+    pub colorspace: ColorSpace,
+    /// Number of columns of the grid. Chosen to fit the cells when not given
+    pub columns: Option<usize>,
+    /// Number of rows of the grid. Chosen to fit the cells when not given
+    pub rows: Option<usize>,
+    /// How the cells are arranged. `columns` and `rows` only apply to the grid layout
+    pub layout: Layout,
+    /// Combine exactly two cells into one picture instead of arranging them. Replaces `layout`
+    pub compare: Option<Comparison>,
+    /// Events marked on a strip under the grid, with a playhead advancing over it. The strip adds
+    /// to the height of the output
+    pub timeline_strip: Option<Vec<TimelineEvent>>,
+    /// How the area of a cell not covered by its input is filled
+    pub pad: PadMode,
+    /// Space between neighboring cells, in pixels, showing the background color
+    pub gap: u32,
+    /// Width of the border drawn around every cell, in pixels
+    pub border: u32,
+    /// Color of the cell borders, as an ffmpeg color
+    pub border_color: String,
+    /// Color showing through the gaps and unused cells of the grid, as an ffmpeg color
+    pub background_color: String,
+    /// What cells do once their input ends before the grid does
+    pub short_inputs: ShortInputs,
+    /// Trade speed for a bounded memory footprint, for very high resolution grids
+    pub low_memory: bool,
+    /// Most threads ffmpeg filters and encodes with, and most inputs pre-scaled at once. Left to
+    /// ffmpeg and the number of CPU cores when `None`
+    pub threads: Option<usize>,
+    /// Stop ffprobe and the pre-scaling ffmpeg of an input once they have run this long on it,
+    /// e.g. on a corrupted file. Left unlimited when `None`
+    pub input_timeout: Option<Duration>,
+    /// The program and arguments of the gateway a WHIP stream is piped into as MPEG-TS, e.g. a
+    /// WebRTC bridge publishing it to the endpoint
+    pub stream_gateway: Option<Vec<String>>,
+    /// Refuse to render outputs larger than this, e.g. to keep a typo from taking down a machine
+    pub limits: Option<OutputLimits>,
+    /// Shrink the inputs to the cell size in parallel processes before compositing them
+    pub prescale: bool,
+    /// Metadata tags written into the output container
+    pub metadata: Vec<(String, String)>,
+    /// How redacted regions of cells are obscured
+    pub redact_style: RedactStyle,
+    /// Which cells the audio track of the output is taken from
+    pub audio: AudioMode,
+    /// Normalize the loudness of mixed audio, so adding up the cells doesn't clip
+    pub normalize_audio: bool,
+    /// Mark cells that don't contribute sound to the output with a small badge
+    pub show_mute_icons: bool,
+    /// Rotate the finished grid, e.g. for portrait-mounted displays
+    pub rotate: Option<Rotation>,
+    /// Interlace the output with this field order, e.g. for broadcast deliverables
+    pub interlace: Option<FieldOrder>,
+    /// Encode to a temporary file and rename it to the output path once complete
+    pub atomic: bool,
+    /// Render a single frame of the grid to an image instead of a video
+    pub still: Option<StillFrame>,
+    /// Render the grid to a silent, looping animated image instead of a video
+    pub animation: Option<AnimatedFormat>,
+    /// Write small thumbnails of every cell at regular intervals alongside the output
+    pub thumb_track: Option<ThumbTrack>,
+    /// Directory the audio of every cell is written to as a WAV file, on the timeline of the grid
+    pub audio_stems: Option<PathBuf>,
+    /// File an MD5 hash of every frame of the grid is written to, before it is encoded
+    pub frame_hashes: Option<PathBuf>,
+    /// Keep the last minutes of the grid in a ring of segments, to export replays from while it
+    /// renders
+    pub replay_buffer: Option<ReplayBuffer>,
+    /// Write the output as a series of files of a fixed length rather than as one, e.g. to
+    /// record a grid of live feeds for hours
+    pub segments: Option<Segments>,
+    /// Draw the frame rate, bitrate and time since the last frame each video file is arriving
+    /// with on its cell, to spot degraded live feeds
+    pub health_overlay: bool,
+    /// Cover the cells of video files with a "NO SIGNAL" slate while their picture stands still
+    /// for this many seconds, e.g. when a live feed freezes, until it moves again
+    pub no_signal_after: Option<f64>,
+    /// Measure the integrated loudness and true peak of the audio of every cell that is heard,
+    /// reported in [`crate::GridInfo::loudness`]
+    pub measure_loudness: bool,
+    /// Codec, quality and hardware of the video encoder
+    pub encoder: EncoderOptions,
+    /// Print every ffmpeg command before running it
+    pub print_commands: bool,
+    /// Print the ffmpeg commands without running them. Inputs are still probed
+    pub dry_run: bool,
+    /// Report the progress of the encode in this format, instead of ffmpeg's own statistics
+    pub progress: Option<ProgressFormat>,
+    /// Keep ffmpeg from printing anything but errors
+    pub quiet: bool,
+    /// Path to save the output video
+    pub output_path: PathBuf,
+}
+
+impl Default for GridSettings {
+    /// A 1920x1080 BT.709 grid of at most 60 fps, written to `output.mp4`
+    fn default() -> Self {
+        GridSettings {
+            width: 1920,
+            height: 1080,
+            duration: u32::MAX,
+            max_framerate: 60.0,
+            color_range: ColorRange::Limited,
+            colorspace: ColorSpace::Bt709,
+            pad: PadMode::Black,
+            gap: 0,
+            border: 0,
+            border_color: "white".to_string(),
+            background_color: "black".to_string(),
+            short_inputs: ShortInputs::Hold,
+            low_memory: false,
+            threads: None,
+            input_timeout: None,
+            stream_gateway: None,
+            limits: None,
+            prescale: false,
+            metadata: Vec::new(),
+            columns: None,
+            rows: None,
+            layout: Layout::Grid,
+            compare: None,
+            timeline_strip: None,
+            audio: AudioMode::None,
+            normalize_audio: true,
+            show_mute_icons: false,
+            redact_style: RedactStyle::Blur,
+            rotate: None,
+            interlace: None,
+            atomic: true,
+            still: None,
+            animation: None,
+            thumb_track: None,
+            audio_stems: None,
+            frame_hashes: None,
+            replay_buffer: None,
+            segments: None,
+            health_overlay: false,
+            no_signal_after: None,
+            measure_loudness: false,
+            encoder: EncoderOptions::default(),
+            print_commands: false,
+            dry_run: false,
+            progress: None,
+            quiet: false,
+            output_path: PathBuf::from("output.mp4"),
+        }
+    }
+}
+
+impl GridSettings {
+    /// The settings rendering the frame of the grid `at` seconds in to the image `path`, e.g. to
+    /// preview it. Side outputs, measurements, metadata and streaming are left out, nothing is
+    /// printed, and the image is written even when the grid is only a dry run
+    pub fn for_still(&self, at: f64, path: &Path) -> GridSettings {
+        GridSettings {
+            still: Some(StillFrame::At(at)),
+            animation: None,
+            thumb_track: None,
+            audio_stems: None,
+            frame_hashes: None,
+            replay_buffer: None,
+            segments: None,
+            health_overlay: false,
+            no_signal_after: None,
+            measure_loudness: false,
+            metadata: Vec::new(),
+            stream_gateway: None,
+            atomic: false,
+            print_commands: false,
+            dry_run: false,
+            progress: None,
+            quiet: true,
+            output_path: path.to_path_buf(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Bounds on the size of an output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputLimits {
+    /// Most pixels in a frame of the output
+    pub max_pixels: u64,
+    /// Longest duration of the output, in seconds
+    pub max_duration: f64,
+}
+
+/// How the progress of an encode is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressFormat {
+    /// A progress bar with the percentage done and the time left, on stderr
+    Bar,
+    /// A JSON object per update on stdout, for scripts wrapping vidgrid
+    Json,
+}
+
+/// The moment of the grid a still image shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StillFrame {
+    /// Halfway through the grid
+    Middle,
+    /// This many seconds into the grid
+    At(f64),
+}
+
+/// An animated image format, for sharing short grids where videos don't play inline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AnimatedFormat {
+    /// GIF, limited to a palette of 256 colors chosen for the grid
+    Gif,
+    /// Animated WebP, smaller and in full color
+    Webp,
+}
+
+impl AnimatedFormat {
+    /// The format an output path's extension asks for, if any
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(AnimatedFormat::Gif),
+            "webp" => Some(AnimatedFormat::Webp),
+            _ => None,
+        }
+    }
+
+    /// Output options for the encoder of the format. Both loop forever.
+    pub(crate) fn output_args(&self) -> Vec<String> {
+        let args: &[&str] = match self {
+            AnimatedFormat::Gif => &["-c:v", "gif", "-loop", "0"],
+            AnimatedFormat::Webp => &[
+                "-c:v",
+                "libwebp_anim",
+                "-lossless",
+                "0",
+                "-quality",
+                "75",
+                "-loop",
+                "0",
+            ],
+        };
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+}
+
+/// Where and how often per-cell thumbnails are written, e.g. for hover-scrub previews
+#[derive(Debug, Clone)]
+pub struct ThumbTrack {
+    /// Directory the thumbnails are written to, as `cell<n>_<index>.jpg`
+    pub dir: PathBuf,
+    /// Seconds between thumbnails. Thumbnail `<index>` shows the cell at `index * interval`
+    pub interval: f64,
+}
+
+/// How the output is cut into files of a fixed length. The files are named after the output with
+/// their number, `grid_000.mp4`, `grid_001.mp4` and so on for `grid.mp4`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segments {
+    /// Seconds of the grid in every file
+    pub length: f64,
+    /// How many of the latest files are kept, when not all of them. The numbers then wrap around,
+    /// so the oldest file is overwritten by the next one
+    pub keep_last: Option<u32>,
+}
+
+impl Segments {
+    /// The file name ffmpeg numbers the segments of `output` into
+    pub fn pattern(output: &Path) -> PathBuf {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{}_%03d", stem);
+        if let Some(extension) = output.extension() {
+            name = format!("{}.{}", name, extension.to_string_lossy());
+        }
+        output.with_file_name(name)
+    }
+
+    /// The output options cutting the grid into segments. Keyframes are forced on the segment
+    /// boundaries, so every segment plays on its own
+    pub(crate) fn output_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "-force_key_frames",
+            &format!("expr:gte(t,n_forced*{})", self.length),
+            "-f",
+            "segment",
+            "-segment_time",
+            &self.length.to_string(),
+            "-reset_timestamps",
+            "1",
+        ]
+        .map(String::from)
+        .to_vec();
+        if let Some(keep_last) = self.keep_last {
+            args.extend(["-segment_wrap".to_string(), keep_last.to_string()]);
+        }
+        args
+    }
+}
+
+/// Where the audio track of the output comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioMode {
+    /// The output is silent
+    None,
+    /// The audio of every cell that has some, mixed together
+    Mix,
+    /// The audio of a single cell, 1-based in reading order
+    Cell(usize),
+}
+
+impl FromStr for AudioMode {
+    type Err = String;
+
+    /// Parses `none`, `mix` or a cell number
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(AudioMode::None),
+            "mix" => Ok(AudioMode::Mix),
+            _ => parse_cell_number(s)
+                .map(AudioMode::Cell)
+                .map_err(|_| format!("Invalid audio '{}', expected none, mix or a cell", s)),
+        }
+    }
+}
+
+impl fmt::Display for AudioMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioMode::None => write!(f, "none"),
+            AudioMode::Mix => write!(f, "mix"),
+            AudioMode::Cell(cell) => write!(f, "{}", cell),
+        }
+    }
+}
+
+/// A clockwise rotation of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Rotation {
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+impl Rotation {
+    /// The filter chain applying this rotation
+    pub fn filter(self) -> String {
+        match self {
+            Rotation::Quarter => "transpose=clock",
+            Rotation::Half => "hflip,vflip",
+            Rotation::ThreeQuarters => "transpose=cclock",
+        }
+        .to_string()
+    }
+
+    /// The angle of the rotation in degrees
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::Quarter => 90,
+            Rotation::Half => 180,
+            Rotation::ThreeQuarters => 270,
+        }
+    }
+}
+
+/// How redacted regions are made unrecognizable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RedactStyle {
+    /// Blur the region heavily
+    Blur,
+    /// Replace the region with large blocks
+    Pixelate,
+}
+
+impl RedactStyle {
+    /// The filter chain obscuring a `width`x`height` region cropped out of a frame
+    pub fn filter(self, width: u32, height: u32) -> String {
+        match self {
+            // Radii are capped by the size of the region, and of its subsampled chroma
+            RedactStyle::Blur => format!(
+                "boxblur=luma_radius={}:luma_power=3:chroma_radius={}:chroma_power=3",
+                escape_filter_option("min(w,h)/4"),
+                escape_filter_option("min(cw,ch)/4")
+            ),
+            RedactStyle::Pixelate => format!(
+                "scale={}:{},scale={}:{}:flags=neighbor",
+                (width / 16).max(1),
+                (height / 16).max(1),
+                width,
+                height
+            ),
+        }
+    }
+}
+
+/// Which field of an interlaced frame comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FieldOrder {
+    /// Top field first
+    Tff,
+    /// Bottom field first
+    Bff,
+}
+
+impl FieldOrder {
+    /// The name the `interlace` filter uses for this field order
+    pub fn scan_name(self) -> &'static str {
+        match self {
+            FieldOrder::Tff => "tff",
+            FieldOrder::Bff => "bff",
+        }
+    }
+
+    /// The value of ffmpeg's `-field_order` option for this field order
+    pub fn flag_name(self) -> &'static str {
+        match self {
+            FieldOrder::Tff => "tt",
+            FieldOrder::Bff => "bb",
+        }
+    }
+
+    /// The value of ffmpeg's `-top` option for this field order
+    pub fn top_field(self) -> &'static str {
+        match self {
+            FieldOrder::Tff => "1",
+            FieldOrder::Bff => "0",
+        }
+    }
+}
+
+/// How inputs shorter than the grid are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShortInputs {
+    /// The cell holds the last frame of its input
+    Hold,
+    /// The input plays again from its start, until the grid ends
+    Loop,
+    /// The grid ends with its shortest input
+    Shortest,
+}
+
+/// How the cells are arranged in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// Equal cells in rows and columns
+    Grid,
+    /// The first cell fills the output, with up to four others small in its corners
+    Pip,
+    /// Every cell side by side in a single row
+    StripH,
+    /// Every cell on top of each other in a single column
+    StripV,
+    /// The first cell large on the left, the others stacked small on the right
+    MainPlusThumbs,
+}
+
+/// How the two cells of a comparison are combined into one picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompareMode {
+    /// Where the cells differ, amplified so small coding errors stand out against black
+    Diff,
+    /// Both cells at half opacity on top of each other
+    Blend,
+    /// The first cell left of a vertical line, the second right of it
+    Split,
+}
+
+/// Two cells compared in the same picture, e.g. an encode and its source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub mode: CompareMode,
+    /// Where the line of a split is, as a fraction of the width from the left
+    pub split_position: f64,
+    /// Move the line of a split across the whole picture over the duration of the output,
+    /// instead of keeping it at `split_position`
+    pub animate_split: bool,
+}
+
+impl Comparison {
+    /// How much the differences between the cells are amplified by
+    const DIFF_GAIN: u32 = 4;
+
+    /// The filter combining the two cells `first` and `second` into `output`. Splits that are
+    /// animated take `duration` seconds to cross the picture.
+    pub(crate) fn filter(&self, first: &str, second: &str, output: &str, duration: f64) -> String {
+        let combine = match self.mode {
+            // Differences in color would show up as a green cast, so only brightness is kept
+            CompareMode::Diff => format!(
+                "blend=all_mode=difference,lutyuv=y=val*{}:u=128:v=128",
+                Self::DIFF_GAIN
+            ),
+            CompareMode::Blend => "blend=all_mode=average".to_string(),
+            // Every plane is split at the same fraction of its own width, so chroma planes of
+            // lower resolution line up with the brightness
+            CompareMode::Split => {
+                let line = if self.animate_split {
+                    format!("W*T/{}", duration)
+                } else {
+                    format!("W*{}", self.split_position)
+                };
+                format!(
+                    "blend=all_expr={}",
+                    escape_filter_option(&format!("if(lt(X,{}),A,B)", line))
+                )
+            }
+        };
+        format!("[{first}][{second}]{combine}[{output}];")
+    }
+}
+
+/// Where a streamed output goes instead of a file. Streams are written as they are encoded, so
+/// they can be watched before the grid is complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTarget {
+    /// Standard output, given as `-`, e.g. to pipe into a player
+    Stdout,
+    /// An RTMP server, e.g. `rtmp://live.example.com/app/key`
+    Rtmp,
+    /// Any other network protocol ffmpeg can push to, such as `udp://`
+    Network,
+    /// An SRT listener or caller, e.g. `srt://monitor.example.com:9000`, watched with sub-second
+    /// delay
+    Srt,
+    /// A WHIP endpoint, e.g. `whips://sfu.example.com/whip/grid`, reached through a WebRTC
+    /// gateway command the stream is piped into
+    Whip,
+}
+
+impl StreamTarget {
+    /// The target an output path names, if it isn't a file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let path = path.to_str()?;
+        if path == "-" {
+            return Some(StreamTarget::Stdout);
+        }
+        match path.split_once("://")?.0.to_ascii_lowercase().as_str() {
+            "rtmp" | "rtmps" => Some(StreamTarget::Rtmp),
+            "udp" | "tcp" | "rtp" => Some(StreamTarget::Network),
+            "srt" => Some(StreamTarget::Srt),
+            "whip" | "whips" => Some(StreamTarget::Whip),
+            _ => None,
+        }
+    }
+
+    /// The muxer of the stream, which can't be told from an extension. RTMP carries FLV;
+    /// MPEG-TS can be joined at any point, as players and servers do
+    pub(crate) fn muxer(&self) -> &'static str {
+        match self {
+            StreamTarget::Rtmp => "flv",
+            StreamTarget::Stdout
+            | StreamTarget::Network
+            | StreamTarget::Srt
+            | StreamTarget::Whip => "mpegts",
+        }
+    }
+
+    /// Whether the stream is watched live from afar, so every frame is sent as soon as it can be
+    pub fn is_low_latency(&self) -> bool {
+        matches!(self, StreamTarget::Srt | StreamTarget::Whip)
+    }
+}
+
+/// How a cell is filled where its input doesn't cover it, e.g. when aspect ratios differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PadMode {
+    /// Solid black bars
+    Black,
+    /// A blurred, scaled-up copy of the input
+    Blur,
+}
+
+/// The quantization range of the output's pixel values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorRange {
+    /// Limited (TV/MPEG) range
+    Limited,
+    /// Full (PC/JPEG) range
+    Full,
+}
+
+impl ColorRange {
+    /// The name ffmpeg uses for this range
+    pub fn ffmpeg_name(self) -> &'static str {
+        match self {
+            ColorRange::Limited => "tv",
+            ColorRange::Full => "pc",
+        }
+    }
+}
+
+/// The colorspace of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorSpace {
+    Bt709,
+    Bt2020,
+}
+
+impl ColorSpace {
+    /// The name the `colorspace` filter uses for this colorspace
+    pub fn filter_name(self) -> &'static str {
+        match self {
+            ColorSpace::Bt709 => "bt709",
+            ColorSpace::Bt2020 => "bt2020",
+        }
+    }
+
+    /// Values for ffmpeg's `-colorspace`, `-color_primaries` and `-color_trc` output tags
+    pub fn tags(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            ColorSpace::Bt709 => ("bt709", "bt709", "bt709"),
+            ColorSpace::Bt2020 => ("bt2020nc", "bt2020", "bt2020-10"),
+        }
+    }
+}
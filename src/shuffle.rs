@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use vidgrid::cell::Cell;
+use vidgrid::input::InputSource;
+
+/// A seed derived from the current time, for runs that don't ask for a specific one
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Randomly reorders the cells using `seed`.
+///
+/// Returns, for each cell position, the 1-based number of the input that now occupies it.
+/// Virtual inputs referring to other cells are updated to follow the cells they refer to.
+pub fn shuffle_cells(cells: &mut [Cell], seed: u64) -> Vec<usize> {
+    let mut rng = SplitMix64(seed);
+
+    // Fisher-Yates shuffle of the input numbers
+    let mut mapping: Vec<usize> = (1..=cells.len()).collect();
+    for i in (1..mapping.len()).rev() {
+        let j = (rng.next() % (i as u64 + 1)) as usize;
+        mapping.swap(i, j);
+    }
+
+    let position_of = |input: usize| mapping.iter().position(|&m| m == input).unwrap() + 1;
+    let mut shuffled: Vec<Cell> = mapping
+        .iter()
+        .map(|&input| cells[input - 1].clone())
+        .collect();
+    for cell in &mut shuffled {
+        match &mut cell.source {
+            InputSource::Onion { base, overlay, .. } => {
+                *base = position_of(*base);
+                *overlay = position_of(*overlay);
+            }
+            InputSource::Magnify { cell, .. } => *cell = position_of(*cell),
+            _ => {}
+        }
+    }
+    cells.clone_from_slice(&shuffled);
+
+    mapping
+}
+
+/// A small, stable PRNG so that a seed always produces the same shuffle
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use vidgrid::loudness::Loudness;
+use vidgrid::Adjustment;
+
+use crate::assertion::Outcome;
+use crate::checksum::ChecksumAlgorithm;
+use crate::diff::Metrics;
+use crate::limits::ResourceLimits;
+
+/// Machine-readable description of a finished grid, written with `--summary`
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub output: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// Duration of the output in seconds
+    pub duration: u32,
+    pub framerate: f64,
+    pub cells: Vec<CellSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shuffle: Option<ShuffleSummary>,
+    /// Hashes of the inputs and the output, recorded with `--checksums`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checksums: Vec<FileChecksum>,
+    /// The CPU and memory limits of the container the grid was rendered in, which the threads
+    /// and memory use of the render were sized to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<ResourceLimits>,
+    /// How the `--fail-on` assertions came out
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Outcome>,
+}
+
+/// What ended up in a single cell of the grid
+#[derive(Debug, Serialize)]
+pub struct CellSummary {
+    /// 1-based position of the cell, in reading order
+    pub cell: usize,
+    /// 1-based number of the input shown in the cell. Withheld in blind tests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<usize>,
+    /// Withheld in blind tests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// What vidgrid changed about the cell's input to fit it in, e.g. its frame rate. Withheld in
+    /// blind tests, as it can tell inputs apart
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<Adjustment>,
+    /// How loud the cell's audio was, if it was heard
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loudness: Option<Loudness>,
+    /// How close the cell's input is to that of the reference cell, measured with `--metrics`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Metrics>,
+}
+
+/// How the inputs were shuffled into cells
+#[derive(Debug, Serialize)]
+pub struct ShuffleSummary {
+    /// Seed that reproduces the shuffle with `--shuffle --seed`
+    pub seed: u64,
+}
+
+/// The hash of a single file read or written by the grid
+#[derive(Debug, Serialize)]
+pub struct FileChecksum {
+    pub path: PathBuf,
+    pub algorithm: ChecksumAlgorithm,
+    /// Lowercase hex digest
+    pub hash: String,
+}
+
+impl Summary {
+    /// Writes the summary as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
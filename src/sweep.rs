@@ -0,0 +1,165 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use vidgrid::encoder::{EncoderOptions, VideoCodec};
+use vidgrid::get_video_duration;
+
+use crate::diff::{self, Metrics};
+use crate::options::SweepArgs;
+
+/// An encoder parameter and the values a sweep encodes its input at, e.g. `crf=18,23,28,33`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepParam {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for SweepParam {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, values) = s.split_once('=').ok_or_else(|| {
+            format!(
+                "Invalid parameter '{}', expected <name>=<value>,<value>..., e.g. crf=18,23,28",
+                s
+            )
+        })?;
+        let name = name.trim().trim_start_matches('-').to_string();
+        let values: Vec<String> = values.split(',').map(|v| v.trim().to_string()).collect();
+        if name.is_empty() || values.iter().any(String::is_empty) {
+            return Err(format!("Invalid parameter '{}'", s));
+        }
+        Ok(SweepParam { name, values })
+    }
+}
+
+/// One encode of the sweep, printed as JSON
+#[derive(Debug, Serialize)]
+struct SweepResult {
+    /// The value of the swept parameter
+    value: String,
+    path: PathBuf,
+    /// Size of the encode in bytes
+    size: u64,
+    /// Average bitrate of the encode in kbit/s
+    bitrate: f64,
+    /// Quality of the encode measured against the input
+    metrics: Metrics,
+}
+
+impl SweepResult {
+    /// The label of the encode's cell, e.g. `crf=23  4.2 MB  1120 kb/s  PSNR 41.2  SSIM 0.981`
+    fn label(&self, name: &str) -> String {
+        let mut label = format!(
+            "{}={}  {:.1} MB  {:.0} kb/s  PSNR {:.1}  SSIM {:.3}",
+            name,
+            self.value,
+            self.size as f64 / 1e6,
+            self.bitrate,
+            self.metrics.psnr,
+            self.metrics.ssim
+        );
+        if let Some(vmaf) = self.metrics.vmaf {
+            label.push_str(&format!("  VMAF {:.1}", vmaf));
+        }
+        label
+    }
+}
+
+/// Encodes the input once per value of the swept parameter, measures every encode against the
+/// input and renders the encodes side by side in a grid labeled with the value, size and quality
+/// of each, printing the results as JSON.
+pub fn run(args: &SweepArgs) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(&args.work_dir)?;
+    let duration = match args.duration {
+        Some(duration) => duration,
+        None => get_video_duration(&args.input)?,
+    };
+
+    let mut results = Vec::new();
+    for value in &args.param.values {
+        let path = args.work_dir.join(format!(
+            "{}_{}.mp4",
+            args.param.name.replace(':', "_"),
+            value.replace(['/', ':'], "_")
+        ));
+        encode(args, value, &path)?;
+        let size = fs::metadata(&path)?.len();
+        let result = SweepResult {
+            value: value.clone(),
+            size,
+            bitrate: size as f64 * 8.0 / duration.max(f64::EPSILON) / 1000.0,
+            metrics: diff::measure(&args.input, &path, args.duration)?,
+            path,
+        };
+        if !args.quiet {
+            eprintln!("Encoded {}", result.label(&args.param.name));
+        }
+        results.push(result);
+    }
+    println!("{}", serde_json::to_string_pretty(&results)?);
+
+    // The grid is rendered as if the encodes had been given on the command line
+    let mut grid_args: Vec<String> = results
+        .iter()
+        .map(|result| result.path.display().to_string())
+        .collect();
+    for (index, result) in results.iter().enumerate() {
+        grid_args.push("--label".to_string());
+        grid_args.push(format!("{}:{}", index + 1, result.label(&args.param.name)));
+    }
+    grid_args.extend(["-o".to_string(), args.output.display().to_string()]);
+    grid_args.extend(args.grid_args.iter().cloned());
+    let grid: crate::options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(grid_args.iter().cloned()),
+    )?;
+    crate::run_grid(&grid, &grid_args)
+}
+
+/// Encodes the input with the swept parameter set to `value`. `codec`, `crf`, `preset` and
+/// `pix_fmt` are set like the grid's own options, so they mean the same for every encoder; other
+/// parameters are passed to ffmpeg as they are
+fn encode(args: &SweepArgs, value: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut encoder = EncoderOptions {
+        codec: Some(args.codec.unwrap_or(VideoCodec::H264)),
+        ..Default::default()
+    };
+    let mut extra = Vec::new();
+    let invalid = || format!("Invalid value '{}' for {}", value, args.param.name);
+    match args.param.name.as_str() {
+        "codec" => encoder.codec = Some(VideoCodec::from_str(value, true).map_err(|_| invalid())?),
+        "crf" => encoder.crf = Some(value.parse().map_err(|_| invalid())?),
+        "preset" => encoder.preset = Some(value.to_string()),
+        "pix_fmt" => encoder.pix_fmt = Some(value.to_string()),
+        name => extra.extend([format!("-{}", name), value.to_string()]),
+    }
+
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error", "-i"]).arg(&args.input);
+    if let Some(duration) = args.duration {
+        command.arg("-t").arg(duration.to_string());
+    }
+    let status = command
+        .args(["-map", "0:v:0", "-an"])
+        .args(encoder.output_args()?)
+        .args(extra)
+        .arg("-y")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(format!(
+            "ffmpeg failed to encode {} with {}={} ({})",
+            args.input.display(),
+            args.param.name,
+            value,
+            status
+        )
+        .into());
+    }
+    Ok(())
+}
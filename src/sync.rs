@@ -0,0 +1,158 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::cell::Cell;
+use crate::input::InputSource;
+use crate::VidGridError;
+
+/// Sample rate the audio is decoded at. Enough to hear claps, speech and music onsets
+const SAMPLE_RATE: usize = 8000;
+
+/// Audio samples per step of the loudness envelope, 10 ms
+const ENVELOPE_STEP: usize = SAMPLE_RATE / 100;
+
+/// How the cells are lined up automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SyncMode {
+    /// Match the sound of every cell against the reference cell
+    Audio,
+}
+
+/// How far each cell must be trimmed, in seconds, for its sound to line up with that of the
+/// `reference` cell. Only the first `window` seconds from each cell's start are listened to, so
+/// the cells must overlap within them. Cells other than video files are not trimmed.
+pub fn audio_offsets(
+    cells: &[Cell],
+    reference: usize,
+    window: f64,
+) -> Result<Vec<f64>, VidGridError> {
+    let mut envelopes = Vec::new();
+    for (index, cell) in cells.iter().enumerate() {
+        envelopes.push(match &cell.source {
+            InputSource::File(path) => {
+                let envelope = onset_envelope(path, cell.start.unwrap_or(0.0), window)?;
+                if envelope.iter().all(|&onset| onset == 0.0) {
+                    return Err(VidGridError::InvalidGrid(format!(
+                        "Cell {} has no sound to synchronize by",
+                        index + 1
+                    )));
+                }
+                Some(envelope)
+            }
+            _ => None,
+        });
+    }
+    let reference_envelope = envelopes
+        .get(reference)
+        .and_then(Option::as_ref)
+        .ok_or_else(|| {
+            VidGridError::InvalidGrid(format!(
+                "The reference cell {} is not a video file",
+                reference + 1
+            ))
+        })?;
+
+    let lags: Vec<Option<f64>> = envelopes
+        .iter()
+        .map(|envelope| {
+            let lag = best_lag(reference_envelope, envelope.as_ref()?);
+            Some(lag as f64 * ENVELOPE_STEP as f64 / SAMPLE_RATE as f64)
+        })
+        .collect();
+
+    // A cell whose sound comes earlier than the reference's can't be started before its start, so
+    // every other cell is trimmed by as much instead
+    let earliest = lags
+        .iter()
+        .flatten()
+        .fold(0.0, |earliest: f64, &lag| earliest.min(lag));
+    Ok(lags
+        .into_iter()
+        .map(|lag| lag.map_or(0.0, |lag| lag - earliest))
+        .collect())
+}
+
+/// How far each cell must be trimmed, in seconds, for the moments of `points` to line up. A
+/// point is a cell and the time into it at which an event seen by all cells happens. Cells
+/// without a point are not trimmed.
+pub fn point_offsets(cells: usize, points: &[(usize, f64)]) -> Vec<f64> {
+    let earliest = points
+        .iter()
+        .map(|&(_, time)| time)
+        .fold(f64::INFINITY, f64::min);
+    let mut offsets = vec![0.0; cells];
+    for &(cell, time) in points {
+        offsets[cell - 1] = time - earliest;
+    }
+    offsets
+}
+
+/// Decodes `window` seconds of the first audio stream of a file from `start` on, and returns how
+/// much louder each step of 10 ms got than the one before it, which peaks where sounds begin
+fn onset_envelope(path: &Path, start: f64, window: f64) -> Result<Vec<f64>, VidGridError> {
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-ss"])
+        .arg(start.to_string())
+        .arg("-t")
+        .arg(window.to_string())
+        .arg("-i")
+        .arg(path)
+        .args(["-map", "0:a:0", "-ac", "1", "-ar"])
+        .arg(SAMPLE_RATE.to_string())
+        .args(["-f", "s16le", "-"])
+        .output()?;
+    if !output.status.success() {
+        return Err(VidGridError::Ffmpeg {
+            task: format!("decode the audio of {}", path.display()),
+            status: output.status,
+        });
+    }
+
+    let loudness: Vec<f64> = output
+        .stdout
+        .chunks_exact(2 * ENVELOPE_STEP)
+        .map(|step| {
+            let sum: f64 = step
+                .chunks_exact(2)
+                .map(|sample| f64::from(i16::from_le_bytes([sample[0], sample[1]])).abs())
+                .sum();
+            sum / ENVELOPE_STEP as f64
+        })
+        .collect();
+    Ok(std::iter::once(0.0)
+        .chain(loudness.windows(2).map(|pair| (pair[1] - pair[0]).max(0.0)))
+        .collect())
+}
+
+/// The number of steps `other` lags behind `reference`, found as the shift at which the two
+/// envelopes correlate best. Shifts leaving less than a quarter of the shorter envelope
+/// overlapping are not considered, as a few chance matches would dominate them.
+fn best_lag(reference: &[f64], other: &[f64]) -> isize {
+    let min_overlap = (reference.len().min(other.len()) / 4).max(1);
+    let lags = -(reference.len() as isize) + 1..other.len() as isize;
+
+    let mut best = (0, f64::NEG_INFINITY);
+    for lag in lags {
+        // `reference[t]` is compared with `other[t + lag]`
+        let first = (-lag).max(0) as usize;
+        let last = reference
+            .len()
+            .min((other.len() as isize - lag).max(0) as usize);
+        if last < first + min_overlap {
+            continue;
+        }
+
+        let (mut product, mut reference_energy, mut other_energy) = (0.0, 0.0, 0.0);
+        for t in first..last {
+            let (a, b) = (reference[t], other[(t as isize + lag) as usize]);
+            product += a * b;
+            reference_energy += a * a;
+            other_energy += b * b;
+        }
+        let score = product / (reference_energy * other_energy).sqrt().max(f64::EPSILON);
+        if score > best.1 {
+            best = (lag, score);
+        }
+    }
+    best.0
+}
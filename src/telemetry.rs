@@ -0,0 +1,232 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use vidgrid::time::{parse_iso8601, parse_seconds};
+
+use vidgrid::locale::Locale;
+
+/// A single reading of a data logger
+#[derive(Debug, Clone, Default)]
+pub struct TelemetrySample {
+    /// Seconds since the first sample of the track
+    pub time: f64,
+    /// Speed in km/h
+    pub speed: Option<f64>,
+    /// Heart rate in beats per minute
+    pub heart_rate: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Reads a telemetry track from a GPX file, or from a CSV file whose header names its columns
+/// (`time`, `speed`, `heart_rate`/`hr`, `lat`, `lon`).
+///
+/// Sample times are made relative to the first sample. Speeds missing from a track are derived
+/// from consecutive positions.
+pub fn read_telemetry(path: &Path) -> Result<Vec<TelemetrySample>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+
+    let is_gpx = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gpx"));
+    let mut samples = if is_gpx {
+        parse_gpx(&contents)?
+    } else {
+        parse_csv(&contents)?
+    };
+
+    if let Some(first) = samples.first().map(|sample| sample.time) {
+        for sample in &mut samples {
+            sample.time -= first;
+        }
+    }
+    derive_speeds(&mut samples);
+
+    Ok(samples)
+}
+
+/// Writes the track as SRT subtitles, one readout per sample formatted for `locale`, shifted so
+/// that `offset` seconds into the track lands on the start of the cell
+pub fn write_readout_subtitles(
+    samples: &[TelemetrySample],
+    offset: f64,
+    locale: &Locale,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut srt = String::new();
+    let mut cue = 1;
+    for (index, sample) in samples.iter().enumerate() {
+        let start = sample.time - offset;
+        let end = samples
+            .get(index + 1)
+            .map_or(start + 1.0, |next| next.time - offset);
+        if end <= 0.0 {
+            continue;
+        }
+
+        let mut readout = Vec::new();
+        if let Some(speed) = sample.speed {
+            readout.push(locale.speed(speed));
+        }
+        if let Some(heart_rate) = sample.heart_rate {
+            readout.push(format!("HR {} bpm", locale.number(heart_rate, 0)));
+        }
+        if let (Some(lat), Some(lon)) = (sample.latitude, sample.longitude) {
+            // Coordinates keep their decimal points, as commas would run them together
+            readout.push(format!("{:.5}, {:.5}", lat, lon));
+        }
+        if readout.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            srt,
+            "{}\n{} --> {}\n{}\n",
+            cue,
+            srt_timestamp(start.max(0.0)),
+            srt_timestamp(end),
+            readout.join("  ")
+        )?;
+        cue += 1;
+    }
+
+    fs::write(path, srt)?;
+    Ok(())
+}
+
+/// Formats seconds as an SRT `hh:mm:ss,mmm` timestamp
+fn srt_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Extracts the track points of a GPX file
+fn parse_gpx(contents: &str) -> Result<Vec<TelemetrySample>, Box<dyn Error>> {
+    let mut samples = Vec::new();
+
+    for point in contents.split("<trkpt").skip(1) {
+        let point = point.split("</trkpt>").next().unwrap_or(point);
+        let time = element_text(point, "time").ok_or("GPX track point has no time")?;
+
+        samples.push(TelemetrySample {
+            time: parse_iso8601(time)?,
+            latitude: attribute(point, "lat").and_then(|v| f64::from_str(v).ok()),
+            longitude: attribute(point, "lon").and_then(|v| f64::from_str(v).ok()),
+            // Garmin's TrackPointExtension stores these as namespaced <gpxtpx:hr> elements
+            heart_rate: element_text(point, "hr").and_then(|v| f64::from_str(v).ok()),
+            speed: element_text(point, "speed")
+                .and_then(|v| f64::from_str(v).ok())
+                .map(|meters_per_second| meters_per_second * 3.6),
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Returns the value of an attribute in the opening tag at the start of `xml`
+fn attribute<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let tag = &xml[..xml.find('>')?];
+    let value = tag.split(&format!("{}=", name)).nth(1)?;
+    let quote = value.chars().next()?;
+    value[1..].split(quote).next()
+}
+
+/// Returns the text of the first element named `name`, ignoring any namespace prefix
+fn element_text<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = rest.find('>')?;
+        let tag = &rest[..tag_end];
+        let local_name = tag.rsplit(':').next().unwrap_or(tag);
+        if local_name == name {
+            let text = &rest[tag_end + 1..];
+            return text.find('<').map(|close| text[..close].trim());
+        }
+    }
+    None
+}
+
+/// Parses a CSV track whose first row names the columns
+fn parse_csv(contents: &str) -> Result<Vec<TelemetrySample>, Box<dyn Error>> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .ok_or("Telemetry CSV is empty")?
+        .split(',')
+        .map(|column| column.trim().to_ascii_lowercase())
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|c| names.contains(&c.as_str()));
+
+    let time_column = column(&["time", "timestamp"]).ok_or("Telemetry CSV has no time column")?;
+    let speed_column = column(&["speed", "speed_kmh"]);
+    let heart_rate_column = column(&["heart_rate", "hr", "heartrate"]);
+    let latitude_column = column(&["lat", "latitude"]);
+    let longitude_column = column(&["lon", "lng", "longitude"]);
+
+    let mut samples = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let number = |column: Option<usize>| {
+            column
+                .and_then(|c| fields.get(c))
+                .and_then(|v| f64::from_str(v).ok())
+        };
+
+        let time = fields.get(time_column).ok_or("Telemetry row has no time")?;
+        let time = parse_seconds(time).or_else(|_| parse_iso8601(time))?;
+
+        samples.push(TelemetrySample {
+            time,
+            speed: number(speed_column),
+            heart_rate: number(heart_rate_column),
+            latitude: number(latitude_column),
+            longitude: number(longitude_column),
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Fills in missing speeds from the distance between consecutive positions
+fn derive_speeds(samples: &mut [TelemetrySample]) {
+    for index in 1..samples.len() {
+        let (previous, current) = (&samples[index - 1], &samples[index]);
+        if current.speed.is_some() {
+            continue;
+        }
+        let (Some(lat1), Some(lon1), Some(lat2), Some(lon2)) = (
+            previous.latitude,
+            previous.longitude,
+            current.latitude,
+            current.longitude,
+        ) else {
+            continue;
+        };
+
+        let elapsed = current.time - previous.time;
+        if elapsed > 0.0 {
+            let kilometers = haversine_km(lat1, lon1, lat2, lon2);
+            samples[index].speed = Some(kilometers / (elapsed / 3600.0));
+        }
+    }
+}
+
+/// Great-circle distance between two coordinates in kilometers
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
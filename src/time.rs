@@ -0,0 +1,173 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// A time as written in a JSON file, either in seconds or as `[hh:]mm:ss`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Time {
+    Seconds(f64),
+    Text(String),
+}
+
+impl Time {
+    /// The time in seconds
+    pub fn seconds(&self) -> Result<f64, String> {
+        match self {
+            Time::Seconds(seconds) => Ok(*seconds),
+            Time::Text(text) => parse_seconds(text),
+        }
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Time::Seconds(seconds) => write!(f, "{}", seconds),
+            Time::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Parses a time in seconds. Accepts plain seconds (`12.5`, `12.5s`) as well as
+/// `mm:ss(.ms)` and `hh:mm:ss(.ms)` forms.
+pub fn parse_seconds(s: &str) -> Result<f64, String> {
+    let trimmed = s.trim();
+    let trimmed = trimmed.strip_suffix('s').unwrap_or(trimmed);
+
+    let mut seconds = 0.0;
+    let parts: Vec<&str> = trimmed.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("Invalid time '{}'", s));
+    }
+    for part in parts {
+        let value = f64::from_str(part).map_err(|_| format!("Invalid time '{}'", s))?;
+        seconds = seconds * 60.0 + value;
+    }
+
+    if seconds < 0.0 {
+        return Err(format!("Time '{}' is negative", s));
+    }
+    Ok(seconds)
+}
+
+/// Parses an ISO 8601 date and time such as `2024-05-07T13:15:02.5Z` into seconds since the
+/// Unix epoch. Times without a UTC offset are treated as UTC.
+pub fn parse_iso8601(s: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid date and time '{}'", s);
+    let s = s.trim();
+
+    let (date, time) = s.split_once(['T', ' ']).ok_or_else(invalid)?;
+    let date: Vec<i64> = date
+        .split('-')
+        .map(i64::from_str)
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let [year, month, day] = date[..] else {
+        return Err(invalid());
+    };
+
+    // Split off the UTC offset, if any
+    let (time, offset) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0.0)
+    } else if let Some(sign_at) = time.rfind(['+', '-']) {
+        let (time, offset) = time.split_at(sign_at);
+        let sign = if offset.starts_with('-') { -1.0 } else { 1.0 };
+        let offset = offset[1..].replace(':', "");
+        if offset.len() != 4 {
+            return Err(invalid());
+        }
+        let hours = f64::from_str(&offset[..2]).map_err(|_| invalid())?;
+        let minutes = f64::from_str(&offset[2..]).map_err(|_| invalid())?;
+        (time, sign * (hours * 3600.0 + minutes * 60.0))
+    } else {
+        (time, 0.0)
+    };
+
+    let seconds_of_day = parse_seconds(time).map_err(|_| invalid())?;
+    let days = days_from_civil(year, month, day);
+    Ok(days as f64 * 86400.0 + seconds_of_day - offset)
+}
+
+/// Parses an ISO 8601 date and time as it reads on a clock where it was taken, ignoring any UTC
+/// offset, into seconds since the Unix epoch of the same reading in UTC. Formatting the result as
+/// UTC shows the time as given, e.g. `2024-05-07T13:15:02+02:00` as 13:15:02.
+pub fn parse_wall_clock(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let invalid = || format!("Invalid date and time '{}'", s);
+    let (date, time) = s.split_once(['T', ' ']).ok_or_else(invalid)?;
+    let time = match time.strip_suffix('Z') {
+        Some(time) => time,
+        None => time
+            .rfind(['+', '-'])
+            .map_or(time, |sign_at| &time[..sign_at]),
+    };
+    parse_iso8601(&format!("{}T{}", date, time)).map_err(|_| invalid())
+}
+
+/// Counts the frames of a `hh:mm:ss:ff` timecode since `00:00:00:00` at the given frame rate.
+///
+/// Timecodes count frames at the nominal rate (30 for 29.97 fps). Drop-frame timecodes, whose
+/// frames follow a `;`, skip the first frame numbers of every minute but each tenth so they keep
+/// up with the clock.
+pub fn timecode_frames(timecode: &str, fps: f64) -> Result<u64, String> {
+    let invalid = || format!("Invalid timecode: {}", timecode);
+    let parts = timecode
+        .split([':', ';', '.'])
+        .map(u64::from_str)
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| invalid())?;
+    let &[hours, minutes, seconds, frames] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if fps <= 0.0 {
+        return Err(invalid());
+    }
+
+    let nominal = fps.round() as u64;
+    let mut count = ((hours * 60 + minutes) * 60 + seconds) * nominal + frames;
+    if timecode.contains(';') {
+        let total_minutes = hours * 60 + minutes;
+        count -= nominal / 15 * (total_minutes - total_minutes / 10);
+    }
+    Ok(count)
+}
+
+/// Writes the frame `frames` frames after `00:00:00:00` as a timecode at the given frame rate,
+/// the reverse of [`timecode_frames`]. Hours wrap around after a day, as on a camera.
+pub fn format_timecode(frames: u64, fps: f64, drop_frame: bool) -> String {
+    let nominal = (fps.round() as u64).max(1);
+    let mut count = frames;
+    if drop_frame {
+        // Put back the frame numbers skipped at the start of every minute but each tenth
+        let dropped = nominal / 15;
+        let per_minute = nominal * 60 - dropped;
+        let per_ten_minutes = per_minute * 10 + dropped;
+        let (tens, rest) = (frames / per_ten_minutes, frames % per_ten_minutes);
+        count += dropped * 9 * tens;
+        if rest > dropped {
+            count += dropped * ((rest - dropped) / per_minute);
+        }
+    }
+    let seconds = count / nominal;
+    format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        if drop_frame { ';' } else { ':' },
+        count % nominal
+    )
+}
+
+/// Number of days between the Unix epoch and the given proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
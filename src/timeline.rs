@@ -0,0 +1,139 @@
+#[cfg(feature = "render")]
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::annotation::default_color;
+use crate::escape::escape_filter_option;
+use crate::time::Time;
+use crate::VidGridError;
+
+/// Color of the strip behind its events
+const STRIP_COLOR: &str = "0x202020";
+
+/// A moment or stretch of the grid's timeline marked on the strip under the grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    /// Seconds into the grid at which the event starts
+    pub start: f64,
+    /// Seconds into the grid at which the event ends. Events without an end are drawn as a thin
+    /// marker
+    pub end: Option<f64>,
+    /// The cell the event belongs to, drawn in that cell's lane. Events of no cell span every lane
+    pub cell: Option<usize>,
+    /// Color of the event, e.g. `red` or `#ff8800`
+    pub color: String,
+}
+
+/// An event as written in an events file, with times in seconds or as `[hh:]mm:ss`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventEntry {
+    start: Time,
+    end: Option<Time>,
+    cell: Option<usize>,
+    #[serde(default = "default_color")]
+    color: String,
+}
+
+/// Reads the events of a timeline strip from a JSON array such as
+/// `[{"start": 12, "end": "0:15", "cell": 2, "color": "red"}]`
+#[cfg(feature = "render")]
+pub fn read_events(path: &Path) -> Result<Vec<TimelineEvent>, VidGridError> {
+    parse_events(&fs::read_to_string(path)?, path)
+}
+
+/// Parses events written as `read_events` reads them, e.g. from a file read elsewhere. Errors
+/// name the file as `path`.
+pub fn parse_events(json: &str, path: &Path) -> Result<Vec<TimelineEvent>, VidGridError> {
+    let invalid = |message: String| {
+        VidGridError::Parse(format!(
+            "Invalid events file {}: {}",
+            path.display(),
+            message
+        ))
+    };
+    let entries: Vec<EventEntry> =
+        serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+
+    let mut events = Vec::new();
+    for entry in entries {
+        let start = entry.start.seconds().map_err(invalid)?;
+        let end = entry
+            .end
+            .map(|end| end.seconds())
+            .transpose()
+            .map_err(invalid)?;
+        if end.is_some_and(|end| end < start) {
+            return Err(invalid(format!(
+                "an event ends before its start at {}",
+                start
+            )));
+        }
+        events.push(TimelineEvent {
+            start,
+            end,
+            cell: entry.cell,
+            color: entry.color,
+        });
+    }
+    Ok(events)
+}
+
+/// The height of the strip under a grid `grid_height` pixels high, even for chroma subsampling
+pub(crate) fn strip_height(grid_height: u32) -> u32 {
+    (grid_height / 10).max(32) / 2 * 2
+}
+
+/// Filters drawing the strip of `events` under a grid of `width` and `cells`, `duration` seconds
+/// long, with a playhead advancing over it. Writes the strip to the `output` pad.
+pub(crate) fn strip_filter(
+    events: &[TimelineEvent],
+    cells: usize,
+    width: u32,
+    height: u32,
+    duration: f64,
+    fps: f64,
+    output: &str,
+) -> Result<String, VidGridError> {
+    // A lane per cell when events belong to cells, otherwise a single lane for all of them
+    let lanes = if events.iter().any(|event| event.cell.is_some()) {
+        cells.max(1) as u32
+    } else {
+        1
+    };
+    let lane_height = height / lanes;
+    let x = |time: f64| ((time / duration).clamp(0.0, 1.0) * f64::from(width)) as u32;
+
+    let mut boxes = Vec::new();
+    for event in events {
+        let (y, box_height) = match event.cell {
+            Some(cell) if cell == 0 || cell > cells => {
+                return Err(VidGridError::InvalidGrid(format!(
+                    "A timeline event belongs to cell {}, but the grid has {} cells",
+                    cell, cells
+                )))
+            }
+            Some(cell) => ((cell as u32 - 1) * lane_height, lane_height),
+            None => (0, height),
+        };
+        let left = x(event.start);
+        let right = event.end.map_or(0, x);
+        boxes.push(format!(
+            "drawbox=x={left}:y={y}:w={w}:h={box_height}:color={color}:t=fill",
+            w = right.saturating_sub(left).max(2),
+            color = escape_filter_option(&event.color),
+        ));
+    }
+    if boxes.is_empty() {
+        boxes.push("null".to_string());
+    }
+
+    Ok(format!(
+        "color=c={STRIP_COLOR}:s={width}x{height}:r={fps},{boxes}[{output}events]; \
+         color=c=white:s=2x{height}:r={fps}[{output}playhead]; \
+         [{output}events][{output}playhead]overlay=x=(W-w)*t/{duration}:y=0:eval=frame:shortest=1[{output}];",
+        boxes = boxes.join(","),
+    ))
+}
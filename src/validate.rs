@@ -0,0 +1,238 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use vidgrid::cell::Subtitles;
+use vidgrid::encoder::EncoderOptions;
+use vidgrid::settings::GridSettings;
+
+use crate::options;
+use crate::provenance::JobSpec;
+
+/// What is wrong with a job
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ProblemKind {
+    /// The job file can't be read as a job
+    UnreadableJob,
+    /// The arguments are unknown, malformed or conflict with each other
+    InvalidOptions,
+    /// A file or directory the job reads doesn't exist
+    MissingFile,
+    /// The grid can't hold the cells, or leaves them no room
+    ImpossibleLayout,
+    /// The encoder options can't be combined
+    UnsupportedEncoder,
+}
+
+/// A problem found in a job, which would make its render fail
+#[derive(Debug, Serialize)]
+struct Problem {
+    kind: ProblemKind,
+    message: String,
+}
+
+/// The outcome of validating a job, printed as JSON
+#[derive(Debug, Serialize)]
+struct Report {
+    job: PathBuf,
+    valid: bool,
+    problems: Vec<Problem>,
+}
+
+/// Checks the job in `path` without rendering it, printing the problems found as JSON. The job
+/// is given in the form vidgrid embeds in its outputs; a relative `cwd` is taken from the job
+/// file's directory. Fails if there are any problems.
+pub fn run(path: &Path) -> Result<(), Box<dyn Error>> {
+    let problems = match read_job(path) {
+        Ok(spec) => check_job(&spec),
+        Err(message) => vec![Problem {
+            kind: ProblemKind::UnreadableJob,
+            message,
+        }],
+    };
+
+    let report = Report {
+        job: path.to_path_buf(),
+        valid: problems.is_empty(),
+        problems,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    match report.problems.len() {
+        0 => Ok(()),
+        1 => Err("The job has 1 problem".into()),
+        count => Err(format!("The job has {} problems", count).into()),
+    }
+}
+
+/// Reads a job file, resolving its working directory
+fn read_job(path: &Path) -> Result<JobSpec, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let mut spec: JobSpec = crate::config_file::parse(path, &text)
+        .map_err(|e| format!("{} is not a vidgrid job: {}", path.display(), e))?;
+    if spec.cwd.is_relative() {
+        spec.cwd = path.parent().unwrap_or(Path::new("")).join(&spec.cwd);
+    }
+    Ok(spec)
+}
+
+/// Finds everything that would make the job fail before ffmpeg is run
+fn check_job(spec: &JobSpec) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let args: options::Args = match clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(spec.args.iter().cloned()),
+    ) {
+        Ok(args) => args,
+        Err(e) => {
+            // clap explains itself on the first line, followed by usage hints
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            problems.push(Problem {
+                kind: ProblemKind::InvalidOptions,
+                message: message.trim_start_matches("error: ").to_string(),
+            });
+            return problems;
+        }
+    };
+    if args.command.is_some() {
+        problems.push(Problem {
+            kind: ProblemKind::InvalidOptions,
+            message: "The job runs a subcommand rather than rendering a grid".to_string(),
+        });
+        return problems;
+    }
+
+    // A layout file or manifest is checked along with the options given with it, which override
+    // it
+    let merged = if let Some(layout_file) = &args.layout_file {
+        Some((
+            layout_file,
+            crate::layout_file_args(&args, &spec.args, &spec.cwd),
+        ))
+    } else {
+        args.manifest
+            .as_ref()
+            .or(args.template.as_ref())
+            .map(|manifest| (manifest, crate::manifest_args(&args, &spec.args, &spec.cwd)))
+    };
+    if let Some((file, merged)) = merged {
+        if !spec.cwd.join(file).exists() {
+            problems.push(Problem {
+                kind: ProblemKind::MissingFile,
+                message: format!("{} does not exist", file.display()),
+            });
+            return problems;
+        }
+        return match merged {
+            Ok(merged) => check_job(&JobSpec {
+                version: spec.version.clone(),
+                cwd: spec.cwd.clone(),
+                args: merged,
+            }),
+            Err(e) => {
+                problems.push(Problem {
+                    kind: ProblemKind::InvalidOptions,
+                    message: e.to_string(),
+                });
+                problems
+            }
+        };
+    }
+
+    // Every file read, relative to the job's working directory
+    let inputs: Vec<_> = args
+        .inputs
+        .iter()
+        .chain(&args.input)
+        .chain(
+            [&args.in1, &args.in2, &args.in3, &args.in4]
+                .into_iter()
+                .flatten(),
+        )
+        .collect();
+    let mut files: Vec<&PathBuf> = inputs.iter().filter_map(|input| input.as_file()).collect();
+    files.extend(&args.edl);
+    files.extend(&args.from_chapters);
+    files.extend(&args.input_dir);
+    files.extend(args.label_file.iter().map(|(_, path)| path));
+    files.extend(&args.labels_file);
+    files.extend(
+        args.subs
+            .iter()
+            .filter_map(|(_, subtitles)| match subtitles {
+                Subtitles::File(path) => Some(path),
+                Subtitles::Track(_) => None,
+            }),
+    );
+    files.extend(args.endcard.iter().map(|(_, card)| &card.image));
+    files.extend(args.overlay_data.iter().map(|(_, path)| path));
+    files.extend(args.telemetry.iter().map(|(_, path)| path));
+    files.extend(args.live_text.iter().map(|(_, path)| path));
+    files.extend(&args.annotations);
+    files.extend(&args.timeline_strip);
+    files.extend(&args.audio_describe);
+    for file in files {
+        if !spec.cwd.join(file).exists() {
+            problems.push(Problem {
+                kind: ProblemKind::MissingFile,
+                message: format!("{} does not exist", file.display()),
+            });
+        }
+    }
+
+    // Cells come from files that are only read when rendering for the other sources of cells
+    let known_cells =
+        args.edl.is_none() && args.from_chapters.is_none() && args.input_dir.is_none();
+    if known_cells {
+        let cells = inputs.len() + args.magnify.len();
+        if let Some(cell) = args.referenced_cells().find(|&cell| cell > inputs.len()) {
+            problems.push(Problem {
+                kind: ProblemKind::InvalidOptions,
+                message: format!(
+                    "Cell {} is referenced, but there are {} cells",
+                    cell,
+                    inputs.len()
+                ),
+            });
+        }
+        let settings = GridSettings {
+            width: args.width,
+            height: args.height,
+            columns: args.cols.map(usize::from),
+            rows: args.rows.map(usize::from),
+            layout: args.layout,
+            compare: args.compare.map(|mode| vidgrid::settings::Comparison {
+                mode,
+                split_position: args.split_position,
+                animate_split: args.animate_split,
+            }),
+            gap: args.gap,
+            border: args.border,
+            ..Default::default()
+        };
+        if let Err(e) = vidgrid::grid_geometry(cells, &settings) {
+            problems.push(Problem {
+                kind: ProblemKind::ImpossibleLayout,
+                message: e.to_string(),
+            });
+        }
+    }
+
+    let encoder = EncoderOptions {
+        codec: args.codec,
+        crf: args.crf,
+        preset: args.preset.clone(),
+        pix_fmt: args.pix_fmt.clone(),
+        hwaccel: args.hwaccel,
+        low_latency: false,
+    };
+    if let Err(e) = encoder.check() {
+        problems.push(Problem {
+            kind: ProblemKind::UnsupportedEncoder,
+            message: e.to_string(),
+        });
+    }
+
+    problems
+}
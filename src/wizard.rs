@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use vidgrid::settings::{AudioMode, Layout};
+use vidgrid::shell_quote;
+
+use crate::prompt::prompt;
+
+/// Asks for the inputs, layout, labels and output of a grid one question at a time, then prints
+/// the equivalent command line and renders the grid with it
+pub fn run() -> Result<(), Box<dyn Error>> {
+    println!("Answer each question, or press enter to keep the default in brackets.\n");
+
+    let mut argv = Vec::new();
+    let mut inputs = 0;
+    loop {
+        let question = match inputs {
+            0 => "Input video".to_string(),
+            _ => format!("Input video {} [done]", inputs + 1),
+        };
+        let answer = prompt(&question)?;
+        if answer.is_empty() {
+            if inputs == 0 {
+                println!("  At least one input is needed");
+                continue;
+            }
+            break;
+        }
+        if !Path::new(&answer).is_file() {
+            println!("  {} is not a file", answer);
+            continue;
+        }
+        argv.extend(["--input".to_string(), answer]);
+        inputs += 1;
+    }
+
+    let layouts: Vec<String> = Layout::value_variants()
+        .iter()
+        .filter_map(|layout| Some(layout.to_possible_value()?.get_name().to_string()))
+        .collect();
+    let layout = ask(
+        &format!("Layout ({})", layouts.join(", ")),
+        "grid",
+        |answer| Layout::from_str(answer, true),
+    )?;
+    if let (false, Some(name)) = (layout == Layout::Grid, layout.to_possible_value()) {
+        argv.extend(["--layout".to_string(), name.get_name().to_string()]);
+    }
+
+    for cell in 1..=inputs {
+        let label = prompt(&format!("Label of cell {} [none]", cell))?;
+        if !label.is_empty() {
+            argv.extend(["--label".to_string(), format!("{}:{}", cell, label)]);
+        }
+    }
+
+    let audio = ask(
+        "Audio (none, mix, or the number of a cell)",
+        "none",
+        |answer| match AudioMode::from_str(answer)? {
+            AudioMode::Cell(cell) if cell > inputs => {
+                Err(format!("There are only {} cells", inputs))
+            }
+            audio => Ok(audio),
+        },
+    )?;
+    if audio != AudioMode::None {
+        argv.extend(["--audio".to_string(), audio.to_string()]);
+    }
+
+    let (width, height) = ask("Resolution", "1920x1080", |answer| {
+        let (width, height) = answer
+            .split_once('x')
+            .ok_or_else(|| format!("Expected <width>x<height>, found {}", answer))?;
+        match (width.parse::<u32>(), height.parse::<u32>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => Err(format!("Invalid resolution: {}", answer)),
+        }
+    })?;
+    if (width, height) != (1920, 1080) {
+        argv.extend([
+            "--width".to_string(),
+            width.to_string(),
+            "--height".to_string(),
+            height.to_string(),
+        ]);
+    }
+
+    let output = ask("Output file", "output.mp4", |answer| {
+        Ok::<_, String>(answer.to_string())
+    })?;
+    argv.extend(["--output-path".to_string(), output]);
+
+    let command: Vec<String> = std::iter::once("vidgrid".to_string())
+        .chain(argv.iter().map(|word| shell_quote(word)))
+        .collect();
+    println!(
+        "\nThe same grid can be rendered again with:\n  {}\n",
+        command.join(" ")
+    );
+
+    let args: crate::options::Args = clap::Parser::try_parse_from(
+        std::iter::once("vidgrid".to_string()).chain(argv.iter().cloned()),
+    )?;
+    crate::run_grid(&args, &argv)
+}
+
+/// Asks a question until the answer, or `default` if nothing is entered, can be parsed
+fn ask<T, E: std::fmt::Display>(
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Result<T, Box<dyn Error>> {
+    loop {
+        let answer = prompt(&format!("{} [{}]", question, default))?;
+        let answer = if answer.is_empty() { default } else { &answer };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => println!("  {}", e),
+        }
+    }
+}